@@ -27,4 +27,29 @@ impl Requester for AWCClient {
         let url = Uri::from_str(url).unwrap();
         Box::pin(async move { Ok(self.0.get(url).send().await?.json().await?) })
     }
+
+    fn get_json_with_cookie<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        cookie: &str,
+    ) -> Pin<Box<dyn Future<Output = Result<T, BoxedError>> + '_>> {
+        let url = Uri::from_str(url).unwrap();
+        let req = self.0.get(url).insert_header(("Cookie", cookie));
+        Box::pin(async move { Ok(req.send().await?.json().await?) })
+    }
+
+    fn post_form<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        form: &[(&str, &str)],
+        cookie: &str,
+    ) -> Pin<Box<dyn Future<Output = Result<T, BoxedError>> + '_>> {
+        let url = Uri::from_str(url).unwrap();
+        let resp = self
+            .0
+            .post(url)
+            .insert_header(("Cookie", cookie))
+            .send_form(&form);
+        Box::pin(async move { Ok(resp.await?.json().await?) })
+    }
 }
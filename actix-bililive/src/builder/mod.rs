@@ -12,6 +12,16 @@ pub(crate) mod tests;
 ///
 /// [`by_uid`](ConfigBuilder::by_uid) fetches room id by given user id.
 ///
+/// [`by_room_id`](ConfigBuilder::by_room_id) resolves the real room id and user id by given (short) room id.
+///
 /// [`fetch_conf`](ConfigBuilder::fetch_conf) fetches danmaku server token and list without any input parameter.
+///
+/// [`fetch_danmu_info`](ConfigBuilder::fetch_danmu_info) fetches danmaku server token and list bound to the room and the
+/// [`credential`](ConfigBuilder::credential), which is required for an authenticated connection.
 pub type ConfigBuilder<R, U, T, S> =
     bililive_core::builder::ConfigBuilder<awc::AWCClient, R, U, T, S>;
+
+/// `bililive` live room client, sending danmaku and likes, and querying room info.
+///
+/// See the generic type [`RoomClient`](bililive_core::room::RoomClient) for details.
+pub type RoomClient = bililive_core::room::RoomClient<awc::AWCClient>;
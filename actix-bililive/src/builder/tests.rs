@@ -1,24 +1,80 @@
-use crate::core::config::StreamConfig;
+use bililive_mock::MockServer;
 
-use super::ConfigBuilder;
+use crate::core::config::{Credential, StreamConfig};
+use crate::core::room::OutgoingDanmaku;
 
-pub(crate) async fn build_real_config(override_servers: bool) -> StreamConfig {
-    let builder = ConfigBuilder::new()
-        .by_uid(419220)
+use super::{ConfigBuilder, RoomClient};
+
+pub(crate) async fn build_config(mock: &MockServer) -> StreamConfig {
+    ConfigBuilder::new()
+        .api_host(&mock.api_host())
+        .by_uid(mock.uid())
         .await
         .expect("unable to fetch room_id")
         .fetch_conf()
         .await
-        .expect("unable to fetch server conf");
-    let builder = if override_servers {
-        builder.servers(&["wss://broadcastlv.chat.bilibili.com/sub".to_string()])
-    } else {
-        builder
-    };
-    builder.build()
+        .expect("unable to fetch server conf")
+        .build()
+}
+
+#[actix_rt::test]
+async fn must_build_config_tokio() {
+    let mock = MockServer::start();
+
+    let config = build_config(&mock).await;
+    assert_eq!(config.room_id(), mock.room_id());
+    assert_eq!(config.servers(), [mock.ws_url()]);
+
+    let config = ConfigBuilder::new()
+        .api_host(&mock.api_host())
+        .by_room_id(mock.short_id())
+        .await
+        .expect("unable to resolve room_id")
+        .fetch_danmu_info()
+        .await
+        .expect("unable to fetch danmu info")
+        .build();
+    assert_eq!(config.room_id(), mock.room_id());
+    assert_eq!(config.token(), mock.token());
 }
 
 #[actix_rt::test]
-async fn must_build_real_config_tokio() {
-    build_real_config(false).await;
+async fn must_perform_room_actions() {
+    let mock = MockServer::start();
+    let mut config = build_config(&mock).await;
+    config.set_credential(Credential::new("sess", "jct", "buvid", 12345));
+
+    let client = RoomClient::from_config(&config).api_host(&mock.api_host());
+    client
+        .send_danmaku(OutgoingDanmaku::new("hello").reply_to(42))
+        .await
+        .expect("unable to send danmaku");
+    client.send_likes(1).await.expect("unable to send likes");
+
+    let posts = mock.posts();
+    assert_eq!(posts.len(), 2);
+    assert_eq!(posts[0].field("msg"), Some("hello"));
+    assert_eq!(posts[0].field("reply_mid"), Some("42"));
+    assert_eq!(
+        posts[0].field("roomid"),
+        Some(mock.room_id().to_string().as_str())
+    );
+    assert_eq!(
+        posts[1].path,
+        "/xlive/app-ucenter/v1/like_info_v3/like/likeReportV3"
+    );
+}
+
+#[actix_rt::test]
+async fn must_fetch_room_info() {
+    let mock = MockServer::builder().title("晚间杂谈").start();
+
+    let info = RoomClient::new(mock.room_id())
+        .api_host(&mock.api_host())
+        .room_info()
+        .await
+        .expect("unable to fetch room info");
+    assert_eq!(info.short_id(), mock.short_id());
+    assert_eq!(info.title(), "晚间杂谈");
+    assert_eq!(info.anchor_name(), "mock-anchor");
 }
@@ -8,17 +8,17 @@ use awc::http::{Uri, Version};
 use awc::{BoxedSocket, Client};
 use stream_reconnect::{ReconnectStream, UnderlyingStream};
 
-use crate::core::config::StreamConfig;
+use crate::core::config::{HeartbeatConfig, StreamConfig};
 use crate::core::errors::StreamError;
 use crate::core::packet::Packet;
-use crate::core::retry::{RetryConfig, RetryContext, WsStream, WsStreamTrait};
+use crate::core::retry::{Monitored, RetryConfig, RetryContext, WsStream, WsStreamTrait};
 use crate::core::stream::HeartbeatStream;
-use crate::stream::{Codec, PingPongStream};
+use crate::stream::{ActixTimer, Codec, PingPongStream};
 
 /// Raw websocket stream type.
 pub type InnerStream = PingPongStream<Framed<BoxedSocket, Codec>>;
 /// Bililive stream type.
-pub type DefaultStream = HeartbeatStream<InnerStream, WsClientError>;
+pub type DefaultStream = HeartbeatStream<InnerStream, WsClientError, ActixTimer>;
 /// Bililive stream type with auto-reconnect mechanism.
 pub type RetryStream = ReconnectStream<
     WsStream<Connector, WsClientError>,
@@ -31,6 +31,7 @@ pub struct Connector;
 
 impl WsStreamTrait<WsClientError> for Connector {
     type Stream = DefaultStream;
+    type Timer = ActixTimer;
     fn connect(
         url: &str,
     ) -> Pin<Box<dyn Future<Output = Result<Self::Stream, WsClientError>> + '_>> {
@@ -44,25 +45,43 @@ impl WsStreamTrait<WsClientError> for Connector {
             Ok(HeartbeatStream::new(PingPongStream::new(codec)))
         })
     }
+    fn set_heartbeat(stream: &mut Self::Stream, heartbeat: &HeartbeatConfig) {
+        stream.set_config(heartbeat.clone());
+    }
 }
 
 /// Connect to bilibili live room.
 ///
+/// Heartbeat behavior is configured by [`StreamConfig::set_heartbeat`](StreamConfig::set_heartbeat).
+///
 /// # Errors
 /// Returns an error when websocket connection fails.
 pub async fn connect(config: StreamConfig) -> Result<DefaultStream, StreamError<WsClientError>> {
-    WsStream::<Connector, WsClientError>::establish(config.into()).await
+    WsStream::<Connector, WsClientError>::establish(config.into())
+        .await
+        .map(Monitored::into_inner)
 }
 
 /// Connect to bilibili live room with auto retry.
 ///
+/// A heartbeat timeout (see [`StreamConfig::set_heartbeat`](StreamConfig::set_heartbeat))
+/// triggers a reconnection.
+///
+/// Either a [`StreamConfig`](StreamConfig) or a [`RetryContext`](RetryContext) can be given.
+/// The latter allows sharing a [`ConnectLimiter`](crate::core::retry::ConnectLimiter) between
+/// streams, refreshing expired tokens on reconnection by a
+/// [`ConfigProvider`](crate::core::retry::ConfigProvider), and selecting servers by a
+/// [`ServerSelector`](crate::core::retry::ServerSelector). Reconnections can be observed by
+/// [`RetryContext::subscribe`](RetryContext::subscribe).
+///
 /// # Errors
 /// Returns an error when websocket connection fails.
 pub async fn connect_with_retry(
-    stream_config: StreamConfig,
+    stream_config: impl Into<RetryContext>,
     retry_config: RetryConfig,
 ) -> Result<RetryStream, StreamError<WsClientError>> {
-    let inner: RetryStream =
-        ReconnectStream::connect_with_options(stream_config.into(), retry_config.into()).await?;
+    let ctx = stream_config.into();
+    let options = retry_config.into_options(&ctx);
+    let inner: RetryStream = ReconnectStream::connect_with_options(ctx, options).await?;
     Ok(inner)
 }
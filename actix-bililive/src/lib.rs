@@ -12,7 +12,9 @@
 //! - Easy establishment of connection via given live room id.
 //! - Handles heartbeat packets automatically.
 //! - Auto retry when connection fails (optional).
-//! - Decompresses `Zlib` payloads automatically.
+//! - Sends danmaku and likes, and queries room info via [`RoomClient`](RoomClient).
+//! - Decompresses `Zlib` and `Brotli` payloads automatically.
+//! - Drops unwanted notifications by `cmd` cheaply via [`CmdFilter`](crate::core::stream::CmdFilter).
 //!
 //! ## Example
 //!
@@ -55,7 +57,7 @@
 
 pub use bililive_core as core;
 #[doc(inline)]
-pub use builder::ConfigBuilder;
+pub use builder::{ConfigBuilder, RoomClient};
 pub use connect::{connect, connect_with_retry};
 
 pub use crate::core::packet::*;
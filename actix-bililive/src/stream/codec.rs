@@ -5,8 +5,8 @@ use awc::ws::{Frame, Message};
 use bytes::BytesMut;
 use log::{debug, warn};
 
-use crate::core::errors::{IncompleteResult, StreamError};
-use crate::core::packet::Packet;
+use crate::core::errors::StreamError;
+use crate::core::packet::PacketBuffer;
 
 use super::PacketOrPing;
 
@@ -14,7 +14,7 @@ use super::PacketOrPing;
 #[derive(Debug)]
 pub struct Codec {
     ws_codec: WsCodec,
-    read_buffer: Vec<u8>,
+    read_buffer: PacketBuffer,
 }
 
 impl Codec {
@@ -23,7 +23,7 @@ impl Codec {
     pub const fn new(ws_codec: WsCodec) -> Self {
         Self {
             ws_codec,
-            read_buffer: vec![],
+            read_buffer: PacketBuffer::new(),
         }
     }
 }
@@ -33,47 +33,44 @@ impl Decoder for Codec {
     type Error = StreamError<WsClientError>;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        let ws_frame = if let Some(frame) = self
-            .ws_codec
-            .decode(src)
-            .map_err(|e| StreamError::from_ws_error(e.into()))?
-        {
-            frame
-        } else {
-            return Ok(None);
-        };
-
-        match ws_frame {
-            Frame::Binary(bytes) => {
-                self.read_buffer.extend_from_slice(&bytes);
-
-                match Packet::parse(&self.read_buffer) {
-                    IncompleteResult::Ok((remaining, pack)) => {
-                        debug!("packet parsed, {} bytes remaining", remaining.len());
+        loop {
+            // yield packets parsed from previous frames first
+            if let Some(pack) = self.read_buffer.pop() {
+                return Ok(Some(pack.into()));
+            }
 
-                        // remove parsed bytes
-                        let consume_len = self.read_buffer.len() - remaining.len();
-                        drop(self.read_buffer.drain(..consume_len));
+            let ws_frame = if let Some(frame) = self
+                .ws_codec
+                .decode(src)
+                .map_err(|e| StreamError::from_ws_error(e.into()))?
+            {
+                frame
+            } else {
+                return Ok(None);
+            };
 
-                        Ok(Some(pack.into()))
-                    }
-                    IncompleteResult::Incomplete(needed) => {
-                        debug!("incomplete packet, {:?} needed", needed);
-                        Ok(None)
-                    }
-                    IncompleteResult::Err(e) => {
+            match ws_frame {
+                Frame::Binary(bytes) => {
+                    if let Err(e) = self.read_buffer.feed(bytes) {
                         warn!("error occurred when parsing incoming packet");
-                        Err(e.into())
+                        return Err(e.into());
                     }
                 }
-            }
-            Frame::Ping(bytes) => {
-                debug!("incoming ws ping");
-                Ok(Some(PacketOrPing::PingPong(bytes)))
-            }
-            _ => {
-                debug!("not a binary message, dropping");
-                Ok(None)
+                Frame::Ping(bytes) => {
+                    debug!("incoming ws ping");
+                    return Ok(Some(PacketOrPing::PingPong(bytes)));
+                }
+                Frame::Close(reason) => {
+                    // server closing connection
+                    let (code, reason) = reason.map_or((1005, String::new()), |reason| {
+                        (reason.code.into(), reason.description.unwrap_or_default())
+                    });
+                    debug!("connection closed by server: {} {}", code, reason);
+                    return Err(StreamError::Closed { code, reason });
+                }
+                _ => {
+                    debug!("not a binary message, dropping");
+                }
             }
         }
     }
@@ -4,6 +4,7 @@ use bytes::Bytes;
 
 pub use codec::Codec;
 pub use pingpong::PingPongStream;
+pub use timer::ActixTimer;
 
 use crate::core::packet::Packet;
 
@@ -11,6 +12,7 @@ mod codec;
 mod pingpong;
 #[cfg(test)]
 mod tests;
+mod timer;
 
 /// Either a valid bililive packet or a websocket ping message.
 #[derive(Debug)]
@@ -1,12 +1,21 @@
 use std::time::Duration;
 
+use actix_codec::{Decoder, Encoder};
 use awc::error::WsClientError;
+use awc::ws::{CloseCode, CloseReason, Codec as WsCodec, Message};
+use bililive_mock::{danmu_msg, Action, MockServer};
+use bytes::{Bytes, BytesMut};
 use futures::{Future, Sink, SinkExt, Stream, StreamExt};
+use serde_json::Value;
 
-use crate::builder::tests::build_real_config;
+use crate::builder::tests::build_config;
+use crate::core::config::{Compression, HeartbeatConfig};
 use crate::core::errors::StreamError;
+use crate::core::events::Event;
 use crate::core::packet::{Operation, Packet, Protocol};
-use crate::core::retry::RetryConfig;
+use crate::core::retry::{RetryConfig, RetryContext, RetryEvent};
+use crate::stream::{Codec, PacketOrPing};
+use crate::ConfigBuilder;
 
 async fn must_future_timeout(dur: Duration, fut: impl Future) {
     assert!(
@@ -15,38 +24,136 @@ async fn must_future_timeout(dur: Duration, fut: impl Future) {
     );
 }
 
+#[test]
+fn must_decode_batches() {
+    let mut server = WsCodec::new();
+    let mut buf = BytesMut::new();
+    for msg in [
+        Message::Binary(Bytes::from_static(include_bytes!(
+            "../../../bililive-core/tests/raw/zlib_batch.packet"
+        ))),
+        Message::Text("dropped".into()),
+        Message::Binary(Bytes::from_static(include_bytes!(
+            "../../../bililive-core/tests/raw/brotli_batch.packet"
+        ))),
+    ] {
+        server.encode(msg, &mut buf).unwrap();
+    }
+
+    let mut codec = Codec::new(WsCodec::new().client_mode());
+    let mut cmds = vec![];
+    while let Some(item) = codec.decode(&mut buf).expect("decode error") {
+        if let PacketOrPing::Packet(pack) = item {
+            cmds.push(
+                pack.json::<Value>().unwrap()["cmd"]
+                    .as_str()
+                    .unwrap()
+                    .to_string(),
+            );
+        } else {
+            panic!("unexpected ping");
+        }
+    }
+    assert_eq!(
+        cmds,
+        [
+            "DANMU_MSG",
+            "DANMU_MSG",
+            "ONLINE_RANK_COUNT",
+            "DANMU_MSG",
+            "DANMU_MSG",
+            "ONLINE_RANK_COUNT"
+        ]
+    );
+}
+
+#[test]
+fn must_decode_close_frame() {
+    let mut server = WsCodec::new();
+    let mut buf = BytesMut::new();
+    server
+        .encode(
+            Message::Close(Some(CloseReason {
+                code: CloseCode::Again,
+                description: Some(String::from("try again later")),
+            })),
+            &mut buf,
+        )
+        .unwrap();
+
+    let mut codec = Codec::new(WsCodec::new().client_mode());
+    match codec.decode(&mut buf) {
+        Err(StreamError::Closed { code, reason }) => {
+            assert_eq!(code, 1013);
+            assert_eq!(reason, "try again later");
+        }
+        e => panic!("unexpected item: {:?}", e),
+    }
+}
+
+/// A mock server replaying a plain notification and zlib/brotli compressed batches.
+fn notification_mock() -> MockServer {
+    MockServer::builder()
+        .popularity(42)
+        .action(Action::Notification(danmu_msg("plain")))
+        .action(Action::Batch {
+            notifications: vec![danmu_msg("zlib 1"), danmu_msg("zlib 2")],
+            compression: Compression::Zlib,
+        })
+        .action(Action::Batch {
+            notifications: vec![danmu_msg("brotli")],
+            compression: Compression::Brotli,
+        })
+        .start()
+}
+
+/// Receive notifications until `count` danmaku are collected, returning their decoded texts.
+async fn recv_danmaku(
+    stream: &mut (impl Stream<Item = Result<Packet, StreamError<WsClientError>>> + Unpin),
+    count: usize,
+) -> Vec<String> {
+    let mut texts = vec![];
+    while texts.len() < count {
+        let pack = stream
+            .next()
+            .await
+            .expect("stream closed")
+            .expect("stream error");
+        if pack.op() == Operation::Notification {
+            match pack.event() {
+                Ok(Event::Danmaku(danmaku)) => texts.push(danmaku.text),
+                other => panic!("expect danmaku, got {:?}", other),
+            }
+        }
+    }
+    texts
+}
+
 async fn test_stream(
     mut stream: impl Stream<Item = Result<Packet, StreamError<WsClientError>>>
         + Sink<Packet, Error = StreamError<WsClientError>>
         + Unpin,
 ) {
-    let mut msg_count = 0;
-
-    let stream_try = async {
-        while let Some(msg) = stream.next().await {
-            msg.expect("stream error");
-            msg_count += 1;
-        }
-    };
-    // err means timeout indicating there's no early stop on stream
-    must_future_timeout(Duration::from_secs(3), stream_try).await;
+    assert_eq!(
+        recv_danmaku(&mut stream, 4).await,
+        ["plain", "zlib 1", "zlib 2", "brotli"]
+    );
 
     stream
         .send(Packet::new(Operation::HeartBeat, Protocol::Json, vec![]))
         .await
         .expect("sink error");
-    let mut hb_resp_received = false;
-    let stream_try = async {
-        while let Some(msg) = stream.next().await {
-            let msg = msg.expect("stream error");
-            if msg.op() == Operation::HeartBeatResponse {
-                hb_resp_received = true;
-            }
+    loop {
+        let pack = stream
+            .next()
+            .await
+            .expect("stream closed")
+            .expect("stream error");
+        if pack.op() == Operation::HeartBeatResponse {
+            assert_eq!(pack.int32_be().unwrap(), 42);
+            break;
         }
-    };
-    // err means timeout indicating there's no early stop on stream
-    must_future_timeout(Duration::from_secs(1), stream_try).await;
-    assert!(hb_resp_received, "no heart beat response received");
+    }
 
     stream.close().await.expect("unable to close stream");
 }
@@ -61,14 +168,15 @@ async fn test_stream_heartbeat(
         panic!("connection closed (heartbeat not sent)");
     };
     // err means timeout indicating there's no early stop on stream
-    must_future_timeout(Duration::from_secs(120), stream_try).await;
+    must_future_timeout(Duration::from_secs(3), stream_try).await;
 
     stream.close().await.expect("unable to close stream");
 }
 
 #[actix_rt::test]
 async fn must_stream_tokio() {
-    let config = build_real_config(true).await;
+    let mock = notification_mock();
+    let config = build_config(&mock).await;
 
     let stream = crate::connect::connect(config)
         .await
@@ -78,7 +186,8 @@ async fn must_stream_tokio() {
 
 #[actix_rt::test]
 async fn must_retry_stream_tokio() {
-    let config = build_real_config(false).await;
+    let mock = notification_mock();
+    let config = build_config(&mock).await;
 
     let stream = crate::connect::connect_with_retry(config, RetryConfig::default())
         .await
@@ -87,12 +196,84 @@ async fn must_retry_stream_tokio() {
 }
 
 #[actix_rt::test]
-async fn must_hb_tokio() {
-    if option_env!("FAST_TEST").is_some() {
-        return;
-    }
+async fn must_retry_after_disconnect_tokio() {
+    let mock = MockServer::builder()
+        .action(Action::Notification(danmu_msg("before")))
+        .action(Action::Disconnect)
+        .action(Action::Notification(danmu_msg("after")))
+        .start();
+
+    let ctx = RetryContext::from(mock.config());
+    let mut events = ctx.subscribe();
+
+    let retry = RetryConfig::new(|| vec![Duration::from_millis(10); 3]);
+    let mut stream = crate::connect::connect_with_retry(ctx, retry)
+        .await
+        .expect("unable to establish connection");
+    assert_eq!(recv_danmaku(&mut stream, 2).await, ["before", "after"]);
+    assert_eq!(mock.room_enters().len(), 2);
+
+    let events: Vec<_> = std::iter::from_fn(|| events.try_next().ok().flatten()).collect();
+    assert!(
+        matches!(
+            events.as_slice(),
+            [
+                RetryEvent::Connecting { attempt: 1, .. },
+                RetryEvent::Connected { .. },
+                RetryEvent::Disconnected { .. },
+                RetryEvent::RetryScheduled { .. },
+                RetryEvent::Connecting { attempt: 2, .. },
+                RetryEvent::Connected { .. },
+            ]
+        ),
+        "{:?}",
+        events
+    );
+}
 
-    let config = build_real_config(true).await;
+#[actix_rt::test]
+async fn must_refresh_config_tokio() {
+    let mock = MockServer::builder()
+        .action(Action::Notification(danmu_msg("before")))
+        .start();
+    let (api_host, uid) = (mock.api_host(), mock.uid());
+    let ctx = RetryContext::from(mock.config()).with_config_provider(move || {
+        let api_host = api_host.clone();
+        async move {
+            Ok(ConfigBuilder::new()
+                .api_host(&api_host)
+                .by_uid(uid)
+                .await?
+                .fetch_conf()
+                .await?
+                .build())
+        }
+    });
+
+    let retry = RetryConfig::new(|| vec![Duration::from_millis(10); 3]);
+    let mut stream = crate::connect::connect_with_retry(ctx, retry)
+        .await
+        .expect("unable to establish connection");
+    assert_eq!(recv_danmaku(&mut stream, 1).await, ["before"]);
+
+    // the old token is rejected on reconnection, so the config must be refreshed
+    mock.rotate_token("rotated-token");
+    mock.push(Action::Disconnect);
+    mock.push(Action::Notification(danmu_msg("after")));
+    assert_eq!(recv_danmaku(&mut stream, 1).await, ["after"]);
+    assert_eq!(mock.room_enters()[1]["key"], "rotated-token");
+}
+
+#[actix_rt::test]
+async fn must_hb_tokio() {
+    let mock = MockServer::builder()
+        .heartbeat_timeout(Duration::from_secs(1))
+        .start();
+    let mut config = mock.config();
+    config.set_heartbeat(HeartbeatConfig::new(
+        Duration::from_millis(200),
+        Duration::from_secs(1),
+    ));
 
     let stream = crate::connect::connect(config)
         .await
@@ -0,0 +1,28 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use actix_rt::time::{sleep_until, Sleep};
+
+use crate::core::stream::Timer;
+
+/// Timer backed by the `actix` runtime.
+#[derive(Debug, Default)]
+pub struct ActixTimer(Option<Pin<Box<Sleep>>>);
+
+impl Timer for ActixTimer {
+    fn reset(&mut self, deadline: Instant) {
+        let deadline = deadline.into();
+        match &mut self.0 {
+            Some(sleep) => sleep.as_mut().reset(deadline),
+            None => self.0 = Some(Box::pin(sleep_until(deadline))),
+        }
+    }
+
+    fn poll_expired(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        self.0
+            .as_mut()
+            .map_or(Poll::Pending, |sleep| sleep.as_mut().poll(cx))
+    }
+}
@@ -0,0 +1,93 @@
+use std::fs::read;
+
+use bytes::{Bytes, BytesMut};
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+use bililive_core::config::Compression;
+use bililive_core::events::CmdKind;
+use bililive_core::packet::{Operation, Packet, Protocol};
+
+fn fixtures() -> Vec<(&'static str, Vec<u8>)> {
+    ["json", "buffer", "zlib_batch", "brotli_batch"]
+        .into_iter()
+        .map(|name| (name, read(format!("tests/raw/{}.packet", name)).unwrap()))
+        .collect()
+}
+
+fn notification(text: &str) -> Packet {
+    Packet::new(
+        Operation::Notification,
+        Protocol::Json,
+        format!(
+            r#"{{"cmd":"DANMU_MSG:4:0:2:2:2:0","info":[[0,1,25,16777215,1626324625000],"{}",[1,"user"]]}}"#,
+            text
+        ),
+    )
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for (name, content) in fixtures() {
+        group.throughput(Throughput::Bytes(content.len() as u64));
+        group.bench_function(format!("{}/slice", name), |b| {
+            b.iter(|| Packet::parse_all(black_box(&content)));
+        });
+        group.bench_function(format!("{}/bytes", name), |b| {
+            let content = Bytes::from(content.clone());
+            b.iter(|| Packet::parse_bytes(&mut black_box(content.clone())).unwrap());
+        });
+    }
+
+    // many packets arriving in one frame
+    let stream: Vec<u8> = (0..64)
+        .flat_map(|i| notification(&i.to_string()).encode())
+        .collect();
+    group.throughput(Throughput::Bytes(stream.len() as u64));
+    group.bench_function("stream/buf", |b| {
+        b.iter(|| {
+            let mut buf = BytesMut::from(black_box(&stream[..]));
+            while let Some(packets) = Packet::parse_buf(&mut buf).unwrap() {
+                black_box(packets);
+            }
+        });
+    });
+    group.finish();
+}
+
+fn bench_encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode");
+    let packet = notification("hello");
+    group.bench_function("json", |b| b.iter(|| black_box(&packet).encode()));
+
+    let batch: Vec<_> = (0..16).map(|i| notification(&i.to_string())).collect();
+    for (name, compression) in [("zlib", Compression::Zlib), ("brotli", Compression::Brotli)] {
+        group.bench_function(format!("{}_batch", name), |b| {
+            b.iter(|| {
+                Packet::compress_batch(black_box(&batch), compression)
+                    .unwrap()
+                    .encode()
+            });
+        });
+    }
+    group.finish();
+}
+
+fn bench_event(c: &mut Criterion) {
+    let mut group = c.benchmark_group("event");
+    let packet = notification("hello");
+    group.bench_function("owned", |b| b.iter(|| black_box(&packet).event().unwrap()));
+    group.bench_function("borrowed", |b| {
+        b.iter(|| black_box(&packet).event_ref().unwrap());
+    });
+    group.bench_function("filtered", |b| {
+        b.iter(|| {
+            black_box(&packet)
+                .event_ref_if(|cmd| cmd.kind() == CmdKind::Gift)
+                .unwrap()
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_parse, bench_encode, bench_event);
+criterion_main!(benches);
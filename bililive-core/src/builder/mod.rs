@@ -3,6 +3,8 @@
 use std::future::Future;
 use std::marker::PhantomData;
 use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 /// `bililive` stream config builder.
 ///
@@ -16,14 +18,29 @@ use std::pin::Pin;
 ///
 /// See docs of downstream crates for details.
 use serde::de::DeserializeOwned;
+use serde_json::Value;
 
-use crate::builder::types::{ConfQueryInner, Resp, RoomQueryInner};
-use crate::config::StreamConfig;
+use crate::builder::types::{ConfQueryInner, NavInner, RawResp, Resp, RoomQueryInner};
+use crate::config::{Compression, Credential, StreamConfig};
 use crate::errors::{BoxedError, BuildError};
 
+pub use types::LiveStatus;
+pub(crate) use types::RoomInitInner;
+
+/// Not logged in, or the credential has expired.
+pub(crate) const NOT_LOGGED_IN: i64 = -101;
+
+/// Error of a [`Requester`](Requester) method not implemented.
+fn unsupported(feature: &str) -> BoxedError {
+    format!("{} not supported by the requester", feature).into()
+}
+pub(crate) const LIVE_API: &str = "https://api.live.bilibili.com";
+const MAIN_API: &str = "https://api.bilibili.com";
+
 #[cfg(test)]
 mod tests;
 mod types;
+mod wbi;
 
 /// An abstract HTTP client.
 ///
@@ -35,6 +52,30 @@ pub trait Requester {
         &self,
         url: &str,
     ) -> Pin<Box<dyn Future<Output = Result<T, BoxedError>> + '_>>;
+    /// Make a `GET` request to the url with given `Cookie` header and try to deserialize the response body as JSON.
+    ///
+    /// By default it fails, so that a credential is never dropped silently. Implement it to support
+    /// [`credential`](ConfigBuilder::credential).
+    fn get_json_with_cookie<T: DeserializeOwned>(
+        &self,
+        _url: &str,
+        _cookie: &str,
+    ) -> Pin<Box<dyn Future<Output = Result<T, BoxedError>> + '_>> {
+        Box::pin(async { Err(unsupported("cookies")) })
+    }
+    /// Make a `POST` request to the url with given url-encoded form and `Cookie` header, and try to
+    /// deserialize the response body as JSON.
+    ///
+    /// By default it fails. Implement it to support actions of
+    /// [`RoomClient`](crate::room::RoomClient).
+    fn post_form<T: DeserializeOwned>(
+        &self,
+        _url: &str,
+        _form: &[(&str, &str)],
+        _cookie: &str,
+    ) -> Pin<Box<dyn Future<Output = Result<T, BoxedError>> + '_>> {
+        Box::pin(async { Err(unsupported("forms")) })
+    }
 }
 
 /// An abstract HTTP client.
@@ -47,6 +88,82 @@ pub trait Requester: Send + Sync {
         &self,
         url: &str,
     ) -> Pin<Box<dyn Future<Output = Result<T, BoxedError>> + Send + '_>>;
+    /// Make a `GET` request to the url with given `Cookie` header and try to deserialize the response body as JSON.
+    ///
+    /// By default it fails, so that a credential is never dropped silently. Implement it to support
+    /// [`credential`](ConfigBuilder::credential).
+    fn get_json_with_cookie<T: DeserializeOwned>(
+        &self,
+        _url: &str,
+        _cookie: &str,
+    ) -> Pin<Box<dyn Future<Output = Result<T, BoxedError>> + Send + '_>> {
+        Box::pin(async { Err(unsupported("cookies")) })
+    }
+    /// Make a `POST` request to the url with given url-encoded form and `Cookie` header, and try to
+    /// deserialize the response body as JSON.
+    ///
+    /// By default it fails. Implement it to support actions of
+    /// [`RoomClient`](crate::room::RoomClient).
+    fn post_form<T: DeserializeOwned>(
+        &self,
+        _url: &str,
+        _form: &[(&str, &str)],
+        _cookie: &str,
+    ) -> Pin<Box<dyn Future<Output = Result<T, BoxedError>> + Send + '_>> {
+        Box::pin(async { Err(unsupported("forms")) })
+    }
+}
+
+/// A shared client, so that multiple builders can share one connection pool.
+#[cfg(feature = "not-send")]
+impl<T: Requester> Requester for Arc<T> {
+    fn get_json<D: DeserializeOwned>(
+        &self,
+        url: &str,
+    ) -> Pin<Box<dyn Future<Output = Result<D, BoxedError>> + '_>> {
+        (**self).get_json(url)
+    }
+    fn get_json_with_cookie<D: DeserializeOwned>(
+        &self,
+        url: &str,
+        cookie: &str,
+    ) -> Pin<Box<dyn Future<Output = Result<D, BoxedError>> + '_>> {
+        (**self).get_json_with_cookie(url, cookie)
+    }
+    fn post_form<D: DeserializeOwned>(
+        &self,
+        url: &str,
+        form: &[(&str, &str)],
+        cookie: &str,
+    ) -> Pin<Box<dyn Future<Output = Result<D, BoxedError>> + '_>> {
+        (**self).post_form(url, form, cookie)
+    }
+}
+
+/// A shared client, so that multiple builders can share one connection pool.
+#[cfg(not(feature = "not-send"))]
+impl<T: Requester> Requester for Arc<T> {
+    fn get_json<D: DeserializeOwned>(
+        &self,
+        url: &str,
+    ) -> Pin<Box<dyn Future<Output = Result<D, BoxedError>> + Send + '_>> {
+        (**self).get_json(url)
+    }
+    fn get_json_with_cookie<D: DeserializeOwned>(
+        &self,
+        url: &str,
+        cookie: &str,
+    ) -> Pin<Box<dyn Future<Output = Result<D, BoxedError>> + Send + '_>> {
+        (**self).get_json_with_cookie(url, cookie)
+    }
+    fn post_form<D: DeserializeOwned>(
+        &self,
+        url: &str,
+        form: &[(&str, &str)],
+        cookie: &str,
+    ) -> Pin<Box<dyn Future<Output = Result<D, BoxedError>> + Send + '_>> {
+        (**self).post_form(url, form, cookie)
+    }
 }
 
 #[doc(hidden)]
@@ -63,7 +180,12 @@ pub enum BN {}
 ///
 /// [`by_uid`](ConfigBuilder::by_uid) fetches room id by given user id.
 ///
+/// [`by_room_id`](ConfigBuilder::by_room_id) resolves the real room id and user id by given (short) room id.
+///
 /// [`fetch_conf`](ConfigBuilder::fetch_conf) fetches danmaku server token and list without any input parameter.
+///
+/// [`fetch_danmu_info`](ConfigBuilder::fetch_danmu_info) fetches danmaku server token and list
+/// bound to the room and the [`credential`](ConfigBuilder::credential).
 #[derive(Debug)]
 pub struct ConfigBuilder<H, R, U, T, S> {
     http: H,
@@ -71,6 +193,10 @@ pub struct ConfigBuilder<H, R, U, T, S> {
     uid: Option<u64>,
     token: Option<String>,
     servers: Option<Vec<String>>,
+    credential: Option<Credential>,
+    compression: Compression,
+    api_host: Option<String>,
+    live_status: Option<LiveStatus>,
     __marker: PhantomData<(R, U, T, S)>,
 }
 
@@ -93,6 +219,10 @@ impl<H> ConfigBuilder<H, BN, BN, BN, BN> {
             uid: None,
             token: None,
             servers: None,
+            credential: None,
+            compression: Compression::Zlib,
+            api_host: None,
+            live_status: None,
             __marker: PhantomData,
         }
     }
@@ -107,6 +237,10 @@ impl<H, R, U, T, S> ConfigBuilder<H, R, U, T, S> {
             uid: self.uid,
             token: self.token,
             servers: self.servers,
+            credential: self.credential,
+            compression: self.compression,
+            api_host: self.api_host,
+            live_status: self.live_status,
             __marker: PhantomData,
         }
     }
@@ -134,6 +268,35 @@ impl<H, R, U, T, S> ConfigBuilder<H, R, U, T, S> {
         self.servers = Some(servers.to_vec());
         self.cast()
     }
+    /// Sets the credential used by HTTP requests and the danmaku connection.
+    ///
+    /// The room is entered as the user of the credential, so it takes precedence over
+    /// [`uid`](ConfigBuilder::uid). See [`StreamConfig::set_credential`](StreamConfig::set_credential).
+    #[must_use]
+    pub fn credential(mut self, credential: Credential) -> Self {
+        self.credential = Some(credential);
+        self
+    }
+    /// Sets the payload compression algorithm requested from the danmaku server. By default it's
+    /// `Zlib`.
+    #[must_use]
+    pub const fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+    /// Overrides the host of bilibili apis, e.g. `http://127.0.0.1:8080`. Useful for testing.
+    ///
+    /// Danmaku servers fetched from a plain `http` host are connected with plain `ws`.
+    #[must_use]
+    pub fn api_host(mut self, host: &str) -> Self {
+        self.api_host = Some(host.trim_end_matches('/').to_string());
+        self
+    }
+    /// Live status of the room, if resolved by [`by_room_id`](ConfigBuilder::by_room_id).
+    #[must_use]
+    pub const fn live_status(&self) -> Option<LiveStatus> {
+        self.live_status
+    }
 }
 
 impl<H, R, U, T, S> ConfigBuilder<H, R, U, T, S>
@@ -147,36 +310,136 @@ where
     /// Fills `room_id` and `uid` by given `uid`, fetching `room_id` automatically.
     ///
     /// # Errors
-    /// Returns an error when HTTP api request fails.
+    /// Returns an error when HTTP api request fails, or the user has no live room.
     pub async fn by_uid(mut self, uid: u64) -> Result<ConfigBuilder<H, BF, BF, T, S>, BuildError> {
         let resp: Resp<RoomQueryInner> = self
-            .http
-            .get_json(&*format!(
-                "https://api.live.bilibili.com/bili/living_v2/{}",
-                uid
-            ))
-            .await
-            .map_err(BuildError)?;
-        let room_id = resp.room_id();
+            .get_resp(&self.api_url(LIVE_API, &format!("/bili/living_v2/{}", uid)))
+            .await?;
+        let room_id = resp.room_id()?;
 
         self.room_id = Some(room_id);
         self.uid = Some(uid);
         Ok(self.cast())
     }
 
+    /// Fills `room_id` and `uid` by given (short) `room_id`, resolving the real room id and
+    /// the streamer's user id automatically.
+    ///
+    /// Live status of the room is available via [`live_status`](ConfigBuilder::live_status).
+    ///
+    /// # Errors
+    /// Returns an error when HTTP api request fails, or the room is locked or encrypted.
+    pub async fn by_room_id(
+        mut self,
+        room_id: u64,
+    ) -> Result<ConfigBuilder<H, BF, BF, T, S>, BuildError> {
+        let resp: Resp<RoomInitInner> = self
+            .get_resp(&self.api_url(LIVE_API, &format!("/room/v1/Room/room_init?id={}", room_id)))
+            .await?;
+        resp.check()?;
+
+        self.room_id = Some(resp.room_id());
+        self.uid = Some(resp.uid());
+        self.live_status = Some(resp.live_status());
+        Ok(self.cast())
+    }
+
     /// Fetches danmaku server configs & uris
     ///
     /// # Errors
     /// Returns an error when HTTP api request fails.
     pub async fn fetch_conf(mut self) -> Result<ConfigBuilder<H, R, U, BF, BF>, BuildError> {
         let resp: Resp<ConfQueryInner> = self
-            .http
-            .get_json("https://api.live.bilibili.com/room/v1/Danmu/getConf")
-            .await
-            .map_err(BuildError)?;
+            .get_resp(&self.api_url(LIVE_API, "/room/v1/Danmu/getConf"))
+            .await?;
 
         self.token = Some(resp.token().to_string());
-        self.servers = Some(resp.servers());
+        self.servers = Some(resp.servers(self.is_secure()));
+        Ok(self.cast())
+    }
+
+    /// Url of the api at `path`, respecting the overridden api host.
+    fn api_url(&self, default_host: &str, path: &str) -> String {
+        format!(
+            "{}{}",
+            self.api_host.as_deref().unwrap_or(default_host),
+            path
+        )
+    }
+
+    /// Whether the api host is served over TLS.
+    fn is_secure(&self) -> bool {
+        !self
+            .api_host
+            .as_deref()
+            .is_some_and(|host| host.starts_with("http://"))
+    }
+
+    /// Make a `GET` request to a bilibili api and decode the data of a successful response.
+    async fn get_resp<D: DeserializeOwned>(&self, url: &str) -> Result<Resp<D>, BuildError> {
+        self.get_raw(url).await?.check()?.decode()
+    }
+
+    /// Make a `GET` request to a bilibili api, carrying the credential if there's one.
+    async fn get_raw(&self, url: &str) -> Result<RawResp, BuildError> {
+        let value: Value = match &self.credential {
+            Some(credential) => {
+                self.http
+                    .get_json_with_cookie(url, &credential.cookie())
+                    .await
+            }
+            None => self.http.get_json(url).await,
+        }
+        .map_err(BuildError::Http)?;
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+impl<H, U, T, S> ConfigBuilder<H, BF, U, T, S>
+where
+    H: Requester,
+    U: Send + Sync,
+    T: Send + Sync,
+    S: Send + Sync,
+{
+    /// Fetches danmaku server configs & uris of the room via the `getDanmuInfo` api.
+    ///
+    /// Unlike [`fetch_conf`](ConfigBuilder::fetch_conf), the token is bound to the room and the
+    /// credential, which is required for an authenticated connection.
+    ///
+    /// # Errors
+    /// Returns an error when HTTP api request fails.
+    #[allow(clippy::missing_panics_doc)]
+    pub async fn fetch_danmu_info(mut self) -> Result<ConfigBuilder<H, BF, U, BF, BF>, BuildError> {
+        // SAFETY ensured by type state
+        let room_id = self.room_id.unwrap();
+        let nav = self
+            .get_raw(&self.api_url(MAIN_API, "/x/web-interface/nav"))
+            .await?;
+        // The nav api reports `-101` for anonymous users, but wbi keys are still provided.
+        let nav: Resp<NavInner> = match nav.code() {
+            NOT_LOGGED_IN => nav,
+            _ => nav.check()?,
+        }
+        .decode()?;
+        let wts = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let query = wbi::sign(
+            &[("id", room_id.to_string()), ("type", String::from("0"))],
+            &nav.mixin_key(),
+            wts,
+        );
+        let resp: Resp<ConfQueryInner> = self
+            .get_resp(&self.api_url(
+                LIVE_API,
+                &format!("/xlive/web-room/v1/index/getDanmuInfo?{}", query),
+            ))
+            .await?;
+
+        self.token = Some(resp.token().to_string());
+        self.servers = Some(resp.servers(self.is_secure()));
         Ok(self.cast())
     }
 }
@@ -186,11 +449,16 @@ impl<H> ConfigBuilder<H, BF, BF, BF, BF> {
     #[allow(clippy::missing_panics_doc)]
     pub fn build(self) -> StreamConfig {
         // SAFETY ensured by type state
-        StreamConfig::new(
+        let mut config = StreamConfig::new(
             self.room_id.unwrap(),
             self.uid.unwrap(),
             self.token.unwrap(),
             self.servers.unwrap(),
-        )
+        );
+        config.set_compression(self.compression);
+        if let Some(credential) = self.credential {
+            config.set_credential(credential);
+        }
+        config
     }
 }
@@ -1,13 +1,91 @@
-use crate::builder::ConfigBuilder;
+use std::future::Future;
+use std::pin::Pin;
 
-use super::types::{ConfQueryInner, Resp, RoomQueryInner};
+use serde::de::DeserializeOwned;
+use serde_json::json;
+
+use crate::builder::{ConfigBuilder, Requester};
+
+use crate::config::{Compression, Credential};
+use crate::errors::{BoxedError, BuildError};
+
+use super::types::{
+    ConfQueryInner, LiveStatus, NavInner, RawResp, Resp, RoomInitInner, RoomQueryInner,
+};
+use super::wbi;
 
 #[test]
 fn must_parse_room_id() {
     let data = r#"{"code":0,"msg":"","message":"","data":{"status":0,"url":"https://live.bilibili.com/1016"}}"#;
     let parsed: Resp<RoomQueryInner> =
         serde_json::from_str(data).expect("unable to parse response");
-    assert_eq!(parsed.room_id(), 1016);
+    assert_eq!(parsed.room_id().unwrap(), 1016);
+}
+
+#[test]
+fn must_reject_no_live_room() {
+    for data in [
+        r#"{"code":0,"msg":"","message":"","data":{"status":0,"url":""}}"#,
+        r#"{"code":0,"msg":"","message":"","data":{"status":0,"url":"https://space.bilibili.com/1016"}}"#,
+        r#"{"code":0,"msg":"","message":"","data":{"status":0}}"#,
+    ] {
+        let parsed: Resp<RoomQueryInner> =
+            serde_json::from_str(data).expect("unable to parse response");
+        assert!(matches!(parsed.room_id(), Err(BuildError::NoLiveRoom)));
+    }
+}
+
+#[test]
+fn must_check_api_error() {
+    let data = r#"{"code":60004,"msg":"直播间不存在","message":"直播间不存在","data":[]}"#;
+    let parsed: RawResp = serde_json::from_str(data).expect("unable to parse response");
+    assert!(matches!(
+        parsed.check(),
+        Err(BuildError::Api { code: 60004, message }) if message == "直播间不存在"
+    ));
+
+    let data = r#"{"code":0,"message":"0","data":{"token":"asdf"}}"#;
+    let parsed: RawResp = serde_json::from_str(data).expect("unable to parse response");
+    assert!(matches!(
+        parsed.check().unwrap().decode::<ConfQueryInner>(),
+        Err(BuildError::Decode(_))
+    ));
+
+    let data = r#"{"code":0,"message":"0","data":null}"#;
+    let parsed: RawResp = serde_json::from_str(data).expect("unable to parse response");
+    assert!(matches!(
+        parsed.decode::<RoomInitInner>(),
+        Err(BuildError::Decode(_))
+    ));
+}
+
+#[test]
+fn must_parse_room_init() {
+    let data = include_str!("../../tests/room_init.json");
+    let parsed: Resp<RoomInitInner> = serde_json::from_str(data).expect("unable to parse response");
+    assert!(parsed.check().is_ok());
+    assert_eq!(parsed.room_id(), 5440);
+    assert_eq!(parsed.uid(), 9_617_619);
+    assert_eq!(parsed.live_status(), LiveStatus::Live);
+}
+
+#[test]
+fn must_reject_inaccessible_room() {
+    let locked = r#"{"code":0,"data":{"room_id":5440,"uid":9617619,"live_status":0,"is_locked":true,"lock_till":1893427200,"encrypted":false,"pwd_verified":false}}"#;
+    let parsed: Resp<RoomInitInner> =
+        serde_json::from_str(locked).expect("unable to parse response");
+    assert!(matches!(
+        parsed.check(),
+        Err(BuildError::RoomLocked {
+            until: 1_893_427_200
+        })
+    ));
+
+    let encrypted = r#"{"code":0,"data":{"room_id":5440,"uid":9617619,"live_status":2,"is_locked":false,"lock_till":0,"encrypted":true,"pwd_verified":false}}"#;
+    let parsed: Resp<RoomInitInner> =
+        serde_json::from_str(encrypted).expect("unable to parse response");
+    assert_eq!(parsed.live_status(), LiveStatus::Round);
+    assert!(matches!(parsed.check(), Err(BuildError::RoomEncrypted)));
 }
 
 #[test]
@@ -20,7 +98,7 @@ fn must_parse_conf() {
         "zRLe_Wb0lwdalke2_OMvIxBD7uBQ7pNKepn-fP2rIV91AyCRSAYwsw1CVYGgjtuf8IA1AHLchDXhiekQ3IMWnzBu5zqIK9CqdY-tuaCpVi1fxE_hqBEdsfdgxPJyFQAxtgqK4cdf1dm7"
     );
     assert_eq!(
-        parsed.servers(),
+        parsed.servers(true),
         [
             "wss://tx-gz-live-comet-03.chat.bilibili.com:443/sub",
             "wss://tx-sh-live-comet-03.chat.bilibili.com:443/sub",
@@ -29,6 +107,45 @@ fn must_parse_conf() {
     )
 }
 
+#[test]
+fn must_parse_danmu_info() {
+    let data = r#"{"code":0,"message":"0","ttl":1,"data":{"group":"live","business_id":0,"refresh_row_factor":0.125,"refresh_rate":100,"max_delay":5000,"token":"danmu-token","host_list":[{"host":"zj-cn-live-comet.chat.bilibili.com","port":2243,"wss_port":2245,"ws_port":2244},{"host":"broadcastlv.chat.bilibili.com","port":2243,"wss_port":443,"ws_port":2244}]}}"#;
+    let parsed: Resp<ConfQueryInner> =
+        serde_json::from_str(data).expect("unable to parse response");
+    assert_eq!(parsed.token(), "danmu-token");
+    assert_eq!(
+        parsed.servers(true),
+        [
+            "wss://zj-cn-live-comet.chat.bilibili.com:2245/sub",
+            "wss://broadcastlv.chat.bilibili.com:443/sub"
+        ]
+    );
+    assert_eq!(
+        parsed.servers(false)[1],
+        "ws://broadcastlv.chat.bilibili.com:2244/sub"
+    );
+}
+
+#[test]
+fn must_sign_wbi() {
+    let data = r#"{"code":-101,"message":"账号未登录","ttl":1,"data":{"isLogin":false,"wbi_img":{"img_url":"https://i0.hdslb.com/bfs/wbi/7cd084941338484aae1ad9425b84077c.png","sub_url":"https://i0.hdslb.com/bfs/wbi/4932caff0ff746eab6f01bf08b70ac45.png"}}}"#;
+    let parsed: Resp<NavInner> = serde_json::from_str(data).expect("unable to parse response");
+    let mixin_key = parsed.mixin_key();
+    assert_eq!(mixin_key, "ea1db124af3c7062474693fa704f4ff8");
+    assert_eq!(
+        wbi::sign(
+            &[
+                ("foo", String::from("114")),
+                ("bar", String::from("514")),
+                ("zab", String::from("1919810"))
+            ],
+            &mixin_key,
+            1_702_204_169
+        ),
+        "bar=514&foo=114&wts=1702204169&zab=1919810&w_rid=8f6f2b5b3d485fe1886cec6a0be8c5d4"
+    );
+}
+
 #[test]
 fn must_build_config() {
     ConfigBuilder::<(), _, _, _, _>::new()
@@ -38,3 +155,77 @@ fn must_build_config() {
         .token("asdf")
         .build();
 }
+
+#[test]
+fn must_build_config_with_credential() {
+    let config = ConfigBuilder::<(), _, _, _, _>::new()
+        .room_id(1016)
+        .uid(0)
+        .servers(&["wss://".to_string()])
+        .token("asdf")
+        .credential(Credential::new("secret-session", "jct", "buvid", 12345))
+        .build();
+    assert_eq!(config.credential().map(Credential::uid), Some(12345));
+    assert_eq!(config.uid(), 12345);
+    assert!(!format!("{:?}", config).contains("secret-session"));
+}
+
+#[test]
+fn must_build_config_with_compression() {
+    let config = ConfigBuilder::<(), _, _, _, _>::new()
+        .room_id(1016)
+        .uid(0)
+        .servers(&["wss://".to_string()])
+        .token("asdf")
+        .build();
+    assert_eq!(config.compression(), Compression::Zlib);
+
+    let config = ConfigBuilder::<(), _, _, _, _>::new()
+        .room_id(1016)
+        .uid(0)
+        .servers(&["wss://".to_string()])
+        .token("asdf")
+        .compression(Compression::Brotli)
+        .build();
+    assert_eq!(config.compression(), Compression::Brotli);
+}
+
+/// A requester implementing only `get_json`, answering every request with `getConf`.
+struct PlainRequester;
+
+#[cfg(not(feature = "not-send"))]
+impl Requester for PlainRequester {
+    fn get_json<T: DeserializeOwned>(
+        &self,
+        _: &str,
+    ) -> Pin<Box<dyn Future<Output = Result<T, BoxedError>> + Send + '_>> {
+        let resp = json!({"code": 0, "data": {"token": "asdf", "host_server_list": []}});
+        Box::pin(async move { Ok(serde_json::from_value(resp)?) })
+    }
+}
+
+#[cfg(not(feature = "not-send"))]
+#[test]
+fn must_reject_cookie_by_default() {
+    let builder = ConfigBuilder::new_with_client(PlainRequester).room_id(1016);
+    assert!(futures::executor::block_on(builder.fetch_conf()).is_ok());
+
+    let builder = ConfigBuilder::new_with_client(PlainRequester)
+        .room_id(1016)
+        .credential(Credential::new("sess", "jct", "buvid", 12345));
+    assert!(matches!(
+        futures::executor::block_on(builder.fetch_conf()),
+        Err(BuildError::Http(_))
+    ));
+}
+
+#[cfg(not(feature = "not-send"))]
+#[test]
+fn must_reject_form_by_default() {
+    let client = crate::room::RoomClient::new_with_client(PlainRequester, 5440)
+        .credential(Credential::new("sess", "jct", "buvid", 12345));
+    assert!(matches!(
+        futures::executor::block_on(client.send_danmaku("hello")),
+        Err(crate::errors::ActionError::Http(_))
+    ));
+}
@@ -1,51 +1,186 @@
+use serde::de::DeserializeOwned;
 use serde::Deserialize;
+use serde_json::Value;
 use url::Url;
 
+use crate::errors::BuildError;
+
+use super::wbi;
+
 #[derive(Clone, Eq, PartialEq, Deserialize, Hash)]
 pub struct Resp<T> {
+    code: i64,
+    #[serde(default)]
+    message: String,
     data: T,
 }
 
+/// A response whose data hasn't been decoded yet.
+pub type RawResp = Resp<Option<Value>>;
+
+impl RawResp {
+    pub const fn code(&self) -> i64 {
+        self.code
+    }
+    /// Reject the response if the api reports a failure.
+    pub fn check(self) -> Result<Self, BuildError> {
+        if self.code == 0 {
+            Ok(self)
+        } else {
+            Err(BuildError::Api {
+                code: self.code,
+                message: self.message,
+            })
+        }
+    }
+    /// Decode the data into the given type.
+    pub fn decode<T: DeserializeOwned>(self) -> Result<Resp<T>, BuildError> {
+        Ok(Resp {
+            code: self.code,
+            message: self.message,
+            data: serde_json::from_value(self.data.unwrap_or_default())?,
+        })
+    }
+}
+
 impl Resp<ConfQueryInner> {
     pub fn token(&self) -> &str {
         &self.data.token
     }
-    pub fn servers(&self) -> Vec<String> {
+    /// Danmaku server urls, using `wss` if `secure`, or plain `ws` otherwise.
+    pub fn servers(&self, secure: bool) -> Vec<String> {
         self.data
             .host_server_list
             .iter()
-            .map(|server| format!("wss://{}:{}/sub", server.host, server.wss_port))
+            .map(|server| {
+                if secure {
+                    format!("wss://{}:{}/sub", server.host, server.wss_port)
+                } else {
+                    format!("ws://{}:{}/sub", server.host, server.ws_port)
+                }
+            })
             .collect()
     }
 }
 
 impl Resp<RoomQueryInner> {
-    pub fn room_id(&self) -> u64 {
-        let url = &self.data.url;
-        assert_eq!(url.host_str().unwrap(), "live.bilibili.com");
-        url.path_segments()
-            .into_iter()
-            .flatten()
-            .last()
-            .unwrap()
-            .parse()
-            .unwrap()
+    pub fn room_id(&self) -> Result<u64, BuildError> {
+        Url::parse(&self.data.url)
+            .ok()
+            .filter(|url| url.host_str() == Some("live.bilibili.com"))
+            .and_then(|url| url.path_segments()?.next_back()?.parse().ok())
+            .ok_or(BuildError::NoLiveRoom)
+    }
+}
+
+impl Resp<RoomInitInner> {
+    pub const fn room_id(&self) -> u64 {
+        self.data.room_id
+    }
+    pub const fn uid(&self) -> u64 {
+        self.data.uid
+    }
+    pub const fn live_status(&self) -> LiveStatus {
+        self.data.live_status
+    }
+    /// Check whether the room is accessible.
+    pub const fn check(&self) -> Result<(), BuildError> {
+        if self.data.is_locked {
+            Err(BuildError::RoomLocked {
+                until: self.data.lock_till,
+            })
+        } else if self.data.encrypted && !self.data.pwd_verified {
+            Err(BuildError::RoomEncrypted)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Live status of a room.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Deserialize)]
+#[serde(from = "u8")]
+pub enum LiveStatus {
+    /// The room is offline.
+    Offline,
+    /// The room is live.
+    Live,
+    /// The room is replaying recorded videos.
+    Round,
+    /// A status not recognized by this crate, with the raw `live_status` value.
+    Unknown(u8),
+}
+
+impl From<u8> for LiveStatus {
+    fn from(i: u8) -> Self {
+        match i {
+            0 => Self::Offline,
+            1 => Self::Live,
+            2 => Self::Round,
+            _ => Self::Unknown(i),
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Deserialize, Hash)]
+pub struct RoomInitInner {
+    room_id: u64,
+    uid: u64,
+    live_status: LiveStatus,
+    #[serde(default)]
+    is_locked: bool,
+    #[serde(default)]
+    lock_till: u64,
+    #[serde(default)]
+    encrypted: bool,
+    #[serde(default)]
+    pwd_verified: bool,
+}
+
+impl RoomInitInner {
+    pub const fn uid(&self) -> u64 {
+        self.uid
     }
 }
 
 #[derive(Clone, Eq, PartialEq, Deserialize, Hash)]
 pub struct RoomQueryInner {
-    url: Url,
+    #[serde(default)]
+    url: String,
 }
 
 #[derive(Clone, Eq, PartialEq, Deserialize, Hash)]
 pub struct ConfQueryInner {
     token: String,
+    #[serde(alias = "host_list")]
     host_server_list: Vec<WSServer>,
 }
 
+impl Resp<NavInner> {
+    pub fn mixin_key(&self) -> String {
+        let wbi_img = &self.data.wbi_img;
+        wbi::mixin_key(
+            wbi::key_from_url(&wbi_img.img_url),
+            wbi::key_from_url(&wbi_img.sub_url),
+        )
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Deserialize, Hash)]
+pub struct NavInner {
+    wbi_img: WbiImg,
+}
+
+#[derive(Clone, Eq, PartialEq, Deserialize, Hash)]
+struct WbiImg {
+    img_url: String,
+    sub_url: String,
+}
+
 #[derive(Clone, Eq, PartialEq, Deserialize, Hash)]
 struct WSServer {
     host: String,
     wss_port: u16,
+    #[serde(default)]
+    ws_port: u16,
 }
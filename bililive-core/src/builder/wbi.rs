@@ -0,0 +1,45 @@
+//! WBI signature for bilibili web APIs.
+
+use url::form_urlencoded;
+
+const MIXIN_KEY_ENC_TAB: [usize; 64] = [
+    46, 47, 18, 2, 53, 8, 23, 32, 15, 50, 10, 31, 58, 3, 45, 35, 27, 43, 5, 49, 33, 9, 42, 19, 29,
+    28, 14, 39, 12, 38, 41, 13, 37, 48, 7, 16, 24, 55, 40, 61, 26, 17, 0, 1, 60, 51, 30, 4, 22, 25,
+    54, 21, 56, 59, 6, 63, 57, 62, 11, 36, 20, 34, 44, 52,
+];
+
+/// Extract the key from an `img_url` or `sub_url` returned by the nav api.
+///
+/// The key is the file stem of the url.
+pub fn key_from_url(url: &str) -> &str {
+    let file = url.rsplit('/').next().unwrap_or_default();
+    file.split('.').next().unwrap_or_default()
+}
+
+/// Shuffle `img_key` and `sub_key` into the mixin key.
+pub fn mixin_key(img_key: &str, sub_key: &str) -> String {
+    let raw: Vec<u8> = img_key.bytes().chain(sub_key.bytes()).collect();
+    MIXIN_KEY_ENC_TAB
+        .iter()
+        .filter_map(|&i| raw.get(i).map(|&c| c as char))
+        .take(32)
+        .collect()
+}
+
+/// Sign the query parameters, returning the signed query string.
+pub fn sign(params: &[(&str, String)], mixin_key: &str, wts: u64) -> String {
+    let mut params: Vec<(&str, String)> = params
+        .iter()
+        .map(|(k, v)| (*k, v.chars().filter(|c| !"!'()*".contains(*c)).collect()))
+        .chain(Some(("wts", wts.to_string())))
+        .collect();
+    params.sort_by_key(|(k, _)| *k);
+
+    // bilibili encodes spaces as `%20` rather than `+`.
+    let query = form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(params)
+        .finish()
+        .replace('+', "%20");
+    let w_rid = md5::compute(format!("{}{}", query, mixin_key));
+    format!("{}&w_rid={:x}", query, w_rid)
+}
@@ -0,0 +1,41 @@
+//! Recording and replaying live sessions.
+//!
+//! A capture file records raw packets together with the time they are received, so that a live
+//! session can be archived and reprocessed later.
+//!
+//! [`Recorder`](Recorder) wraps a bililive stream and writes every packet received into a
+//! capture, and [`CaptureReader`](CaptureReader) reads packets back, either synchronously or as a
+//! stream paced by the original timing.
+//!
+//! [`ReplayStream`](ReplayStream) replays a capture as a live connection, with real-time,
+//! accelerated or stepped pacing. It's useful for running clients against a recorded session
+//! without network.
+//!
+//! # Format
+//!
+//! All integers are big endian.
+//!
+//! | Field            | Size     | Description                                   |
+//! |------------------|----------|-----------------------------------------------|
+//! | magic            | 5 bytes  | `BLCAP`                                       |
+//! | version          | 1 byte   | format version, currently `1`                 |
+//! | started at       | 8 bytes  | unix timestamp of the capture in milliseconds |
+//! | records          | -        | until EOF                                     |
+//!
+//! Each record consists of the milliseconds elapsed since the previous record (or the start of
+//! the capture) as a `u32`, followed by the packet encoded by [`Packet::encode`](crate::packet::Packet::encode).
+
+pub use reader::{CaptureReader, CaptureStream, Pacing, Record};
+pub use recorder::{CaptureWriter, Recorder};
+pub use replay::{ReplayStream, Stepper};
+
+mod reader;
+mod recorder;
+mod replay;
+#[cfg(all(test, any(feature = "tokio", feature = "async-std")))]
+mod tests;
+
+/// Magic bytes at the beginning of a capture file.
+const MAGIC: &[u8; 5] = b"BLCAP";
+/// Current version of the capture format.
+const VERSION: u8 = 1;
@@ -0,0 +1,270 @@
+use std::collections::VecDeque;
+use std::io::{self, ErrorKind, Read};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+use futures::{ready, Stream};
+
+use crate::errors::CaptureError;
+use crate::packet::Packet;
+use crate::stream::Timer;
+
+use super::replay::Stepper;
+use super::{MAGIC, VERSION};
+
+/// Length of a packet header.
+const PACKET_HEADER_LENGTH: usize = 16;
+/// Maximum length of a packet accepted, to avoid allocating huge buffers for corrupted captures.
+const MAX_PACKET_LENGTH: usize = 16 * 1024 * 1024;
+
+/// A packet read from a capture.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Record {
+    delay: Duration,
+    packet: Packet,
+}
+
+impl Record {
+    /// Get the time elapsed since the previous record, or the start of the capture.
+    #[must_use]
+    pub const fn delay(&self) -> Duration {
+        self.delay
+    }
+    /// Get the packet.
+    #[must_use]
+    pub const fn packet(&self) -> &Packet {
+        &self.packet
+    }
+    /// Consume the record and return the packet.
+    #[must_use]
+    pub fn into_packet(self) -> Packet {
+        self.packet
+    }
+}
+
+/// Reader of the capture format.
+///
+/// Records can be read synchronously by iterating the reader, or asynchronously by converting it
+/// into a [`CaptureStream`](CaptureStream).
+#[derive(Debug)]
+pub struct CaptureReader<R> {
+    reader: R,
+    started_at: SystemTime,
+    /// packets left in a compressed record
+    queue: VecDeque<Packet>,
+}
+
+impl<R: Read> CaptureReader<R> {
+    /// Open a capture by reading the header.
+    ///
+    /// # Errors
+    /// Returns an error if the header is invalid or the format version is unsupported.
+    pub fn new(mut reader: R) -> Result<Self, CaptureError> {
+        let mut header = [0; 14];
+        reader.read_exact(&mut header).map_err(truncated)?;
+        if &header[..5] != MAGIC {
+            return Err(CaptureError::InvalidMagic);
+        }
+        if header[5] != VERSION {
+            return Err(CaptureError::UnsupportedVersion(header[5]));
+        }
+        let started_at = u64::from_be_bytes(header[6..].try_into().unwrap());
+
+        Ok(Self {
+            reader,
+            started_at: UNIX_EPOCH + Duration::from_millis(started_at),
+            queue: VecDeque::new(),
+        })
+    }
+
+    /// Get the time when the capture started.
+    #[must_use]
+    pub const fn started_at(&self) -> SystemTime {
+        self.started_at
+    }
+
+    /// Read the next record. Returns `None` at the end of the capture.
+    ///
+    /// A compressed packet is split into multiple records, all but the first one with zero delay.
+    ///
+    /// # Errors
+    /// Returns an error if the capture is truncated, a packet is larger than 16 MiB, or a packet
+    /// can't be parsed.
+    pub fn read_record(&mut self) -> Result<Option<Record>, CaptureError> {
+        if let Some(packet) = self.queue.pop_front() {
+            return Ok(Some(Record {
+                delay: Duration::ZERO,
+                packet,
+            }));
+        }
+
+        let mut delay = [0; 4];
+        if !read_exact_or_eof(&mut self.reader, &mut delay)? {
+            return Ok(None);
+        }
+        let delay = Duration::from_millis(u32::from_be_bytes(delay) as u64);
+
+        let mut buf = vec![0; PACKET_HEADER_LENGTH];
+        self.reader.read_exact(&mut buf).map_err(truncated)?;
+        let packet_length = u32::from_be_bytes(buf[..4].try_into().unwrap()) as usize;
+        if packet_length > MAX_PACKET_LENGTH {
+            return Err(CaptureError::PacketTooLarge(packet_length));
+        }
+        if packet_length > PACKET_HEADER_LENGTH {
+            buf.resize(packet_length, 0);
+            self.reader
+                .read_exact(&mut buf[PACKET_HEADER_LENGTH..])
+                .map_err(truncated)?;
+        }
+
+        match Packet::parse_bytes(&mut Bytes::from(buf)) {
+            Ok(Some(packets)) => {
+                self.queue.extend(packets);
+                Ok(self
+                    .queue
+                    .pop_front()
+                    .map(|packet| Record { delay, packet }))
+            }
+            Ok(None) => Err(CaptureError::Truncated),
+            Err(e) => Err(CaptureError::Parse(e)),
+        }
+    }
+
+    /// Convert the reader into a stream of packets with given pacing.
+    ///
+    /// `T` is the timer used to wait between packets. See [`Timer`](Timer) for details.
+    ///
+    /// # Panics
+    /// Panics if the factor of [`Pacing::Accelerated`](Pacing::Accelerated) is not positive.
+    pub fn into_stream<T: Timer>(self, pacing: Pacing) -> CaptureStream<R, T> {
+        if let Pacing::Accelerated(factor) = pacing {
+            assert!(factor > 0.0, "acceleration factor must be positive");
+        }
+        CaptureStream {
+            reader: self,
+            pacing,
+            timer: T::default(),
+            waiting: false,
+            stepper: Stepper::default(),
+            last_deadline: None,
+            pending: None,
+        }
+    }
+}
+
+impl<R: Read> Iterator for CaptureReader<R> {
+    type Item = Result<Record, CaptureError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+/// Read exactly enough bytes to fill `buf`. Returns `false` if the reader is at EOF.
+fn read_exact_or_eof(reader: &mut impl Read, buf: &mut [u8]) -> Result<bool, CaptureError> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) if read == 0 => return Ok(false),
+            Ok(0) => return Err(CaptureError::Truncated),
+            Ok(n) => read += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(true)
+}
+
+fn truncated(e: io::Error) -> CaptureError {
+    if e.kind() == ErrorKind::UnexpectedEof {
+        CaptureError::Truncated
+    } else {
+        e.into()
+    }
+}
+
+/// Pacing of packets yielded by a [`CaptureStream`](CaptureStream).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Pacing {
+    /// Yield packets with the original timing.
+    RealTime,
+    /// Yield packets with the original timing sped up by given factor.
+    Accelerated(f64),
+    /// Yield packets as fast as possible.
+    AsFastAsPossible,
+    /// Yield a packet each time the [`Stepper`](Stepper) of the stream is stepped.
+    Stepped,
+}
+
+/// Stream of packets read from a capture.
+///
+/// # Blocking
+/// Records are read from the underlying [`Read`](Read) synchronously inside `poll_next`, which
+/// blocks the executor thread until the read completes. It's fine for in-memory buffers and local
+/// files, but slow readers (e.g. network filesystems or pipes) stall other tasks on the same
+/// thread. Read such captures with [`CaptureReader`](CaptureReader) on a blocking thread instead.
+#[derive(Debug)]
+pub struct CaptureStream<R, T> {
+    reader: CaptureReader<R>,
+    pacing: Pacing,
+    timer: T,
+    /// whether the timer is armed for the pending packet
+    waiting: bool,
+    stepper: Stepper,
+    /// time when the previous packet is scheduled
+    last_deadline: Option<Instant>,
+    /// packet waiting to be yielded
+    pending: Option<Packet>,
+}
+
+impl<R, T> CaptureStream<R, T> {
+    /// Get a reference to the capture reader.
+    pub const fn get_ref(&self) -> &CaptureReader<R> {
+        &self.reader
+    }
+    /// Get a handle to step the stream under [`Pacing::Stepped`](Pacing::Stepped).
+    pub fn stepper(&self) -> Stepper {
+        self.stepper.clone()
+    }
+}
+
+impl<R: Read + Unpin, T: Timer> Stream for CaptureStream<R, T> {
+    type Item = Result<Packet, CaptureError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.pending.is_none() {
+            let record = match self.reader.read_record() {
+                Ok(Some(record)) => record,
+                Ok(None) => return Poll::Ready(None),
+                Err(e) => return Poll::Ready(Some(Err(e))),
+            };
+            let delay = match self.pacing {
+                Pacing::RealTime => Some(record.delay),
+                Pacing::Accelerated(factor) => Some(record.delay.div_f64(factor)),
+                Pacing::AsFastAsPossible | Pacing::Stepped => None,
+            };
+            if let Some(delay) = delay {
+                // schedule packets relative to the previous deadline so that delays don't accumulate
+                let now = Instant::now();
+                let deadline = self.last_deadline.unwrap_or(now) + delay;
+                self.last_deadline = Some(deadline);
+                if deadline > now {
+                    self.timer.reset(deadline);
+                    self.waiting = true;
+                }
+            }
+            self.pending = Some(record.packet);
+        }
+
+        if self.waiting {
+            ready!(self.timer.poll_expired(cx));
+            self.waiting = false;
+        }
+        if self.pacing == Pacing::Stepped {
+            ready!(self.stepper.poll_step(cx));
+        }
+        Poll::Ready(self.pending.take().map(Ok))
+    }
+}
@@ -0,0 +1,161 @@
+use std::io::{self, Write};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use futures::{ready, Sink, Stream};
+
+use crate::packet::Packet;
+
+use super::{MAGIC, VERSION};
+
+/// Writer of the capture format.
+///
+/// Packets are timestamped when written. Writes are unbuffered, so you may want to wrap the
+/// underlying writer in a [`BufWriter`](std::io::BufWriter).
+#[derive(Debug)]
+pub struct CaptureWriter<W> {
+    writer: W,
+    last: Instant,
+}
+
+impl<W: Write> CaptureWriter<W> {
+    /// Start a capture by writing the header.
+    ///
+    /// # Errors
+    /// Returns an error if the header can't be written.
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        let started_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        writer.write_all(&started_at.to_be_bytes())?;
+        Ok(Self {
+            writer,
+            last: Instant::now(),
+        })
+    }
+
+    /// Write a packet, timestamped with the current time.
+    ///
+    /// # Errors
+    /// Returns an error if the record can't be written.
+    pub fn write(&mut self, pack: &Packet) -> io::Result<()> {
+        let now = Instant::now();
+        let delay = u32::try_from((now - self.last).as_millis()).unwrap_or(u32::MAX);
+        let mut record = delay.to_be_bytes().to_vec();
+        record.extend(pack.encode());
+        self.writer.write_all(&record)?;
+        self.last = now;
+        Ok(())
+    }
+
+    /// Flush the underlying writer.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying writer fails to flush.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Consume the capture writer and return the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Wrapper that records all packets received from a bililive stream into a capture.
+///
+/// Packets are passed through unchanged. If a packet can't be recorded, the io error is yielded
+/// first, and the packet is yielded on the next poll.
+///
+/// The sink half of the underlying stream is exposed as well, so a recorder can be used in place of
+/// the stream it wraps.
+#[derive(Debug)]
+pub struct Recorder<S, W> {
+    stream: S,
+    writer: CaptureWriter<W>,
+    pending: Option<Packet>,
+}
+
+impl<S, W: Write> Recorder<S, W> {
+    /// Record packets received from `stream` into `writer`.
+    ///
+    /// # Errors
+    /// Returns an error if the capture header can't be written.
+    pub fn new(stream: S, writer: W) -> io::Result<Self> {
+        Ok(Self {
+            stream,
+            writer: CaptureWriter::new(writer)?,
+            pending: None,
+        })
+    }
+}
+
+impl<S, W> Recorder<S, W> {
+    /// Get a reference to the underlying stream.
+    pub const fn get_ref(&self) -> &S {
+        &self.stream
+    }
+    /// Get a mutable reference to the underlying stream.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+    /// Consume the recorder, returning the underlying stream and the capture writer.
+    pub fn into_inner(self) -> (S, CaptureWriter<W>) {
+        (self.stream, self.writer)
+    }
+}
+
+impl<S, W, E> Stream for Recorder<S, W>
+where
+    S: Stream<Item = Result<Packet, E>> + Unpin,
+    W: Write + Unpin,
+    E: From<io::Error>,
+{
+    type Item = Result<Packet, E>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(pack) = self.pending.take() {
+            return Poll::Ready(Some(Ok(pack)));
+        }
+
+        let item = ready!(Pin::new(&mut self.stream).poll_next(cx));
+        Poll::Ready(match item {
+            Some(Ok(pack)) => match self.writer.write(&pack) {
+                Ok(()) => Some(Ok(pack)),
+                Err(e) => {
+                    self.pending = Some(pack);
+                    Some(Err(e.into()))
+                }
+            },
+            item => item,
+        })
+    }
+}
+
+impl<S, W, I> Sink<I> for Recorder<S, W>
+where
+    S: Sink<I> + Unpin,
+    W: Unpin,
+{
+    type Error = S::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.stream).poll_ready(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: I) -> Result<(), Self::Error> {
+        Pin::new(&mut self.stream).start_send(item)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.stream).poll_close(cx)
+    }
+}
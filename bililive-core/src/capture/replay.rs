@@ -0,0 +1,176 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use futures::{ready, Sink, Stream};
+
+use crate::errors::{CaptureError, StreamError};
+use crate::packet::{Operation, Packet, Protocol};
+use crate::stream::Timer;
+
+use super::{CaptureReader, CaptureStream, Pacing};
+
+/// Handle to step a stream under [`Pacing::Stepped`](Pacing::Stepped).
+///
+/// Each step allows one more packet to be yielded. Steps can be taken before the stream is polled.
+#[derive(Debug, Clone, Default)]
+pub struct Stepper(Arc<Mutex<StepperState>>);
+
+#[derive(Debug, Default)]
+struct StepperState {
+    steps: usize,
+    waker: Option<Waker>,
+}
+
+impl Stepper {
+    /// Allow one more packet to be yielded.
+    pub fn step(&self) {
+        self.step_by(1);
+    }
+
+    /// Allow `n` more packets to be yielded.
+    #[allow(clippy::missing_panics_doc)]
+    pub fn step_by(&self, n: usize) {
+        let mut state = self.0.lock().unwrap();
+        state.steps += n;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+
+    pub(crate) fn poll_step(&self, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.0.lock().unwrap();
+        if state.steps > 0 {
+            state.steps -= 1;
+            Poll::Ready(())
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+/// A bililive stream replayed from a capture.
+///
+/// `ReplayStream` has the same `Stream + Sink<Packet>` shape as a live connection, so it can be
+/// used in place of one, e.g. wrapped by [`HeartbeatStream`](crate::stream::HeartbeatStream).
+///
+/// Heartbeats sent to the stream are answered with the latest popularity in the capture (`1` if
+/// none is seen yet), and other packets sent are discarded. The stream ends when the capture ends
+/// or the sink is closed.
+///
+/// Errors of the capture are yielded as [`StreamError::Parse`](StreamError::Parse) if a packet
+/// can't be parsed, or [`StreamError::IO`](StreamError::IO) otherwise, e.g. the capture is
+/// truncated.
+///
+/// The capture is read with blocking io, see [`CaptureStream`](super::CaptureStream#blocking).
+#[derive(Debug)]
+pub struct ReplayStream<R, T> {
+    stream: CaptureStream<R, T>,
+    popularity: u32,
+    /// heartbeat responses to be yielded
+    responses: VecDeque<Packet>,
+    rx_waker: Option<Waker>,
+    closed: bool,
+}
+
+impl<R: Read, T: Timer> ReplayStream<R, T> {
+    /// Replay a capture with given pacing.
+    ///
+    /// # Panics
+    /// Panics if the factor of [`Pacing::Accelerated`](Pacing::Accelerated) is not positive.
+    pub fn new(reader: CaptureReader<R>, pacing: Pacing) -> Self {
+        Self {
+            stream: reader.into_stream(pacing),
+            popularity: 1,
+            responses: VecDeque::new(),
+            rx_waker: None,
+            closed: false,
+        }
+    }
+}
+
+impl<T: Timer> ReplayStream<BufReader<File>, T> {
+    /// Replay a capture file with given pacing.
+    ///
+    /// # Errors
+    /// Returns an error if the file can't be opened or isn't a valid capture.
+    ///
+    /// # Panics
+    /// Panics if the factor of [`Pacing::Accelerated`](Pacing::Accelerated) is not positive.
+    pub fn open(path: impl AsRef<Path>, pacing: Pacing) -> Result<Self, CaptureError> {
+        let reader = CaptureReader::new(BufReader::new(File::open(path)?))?;
+        Ok(Self::new(reader, pacing))
+    }
+}
+
+impl<R, T> ReplayStream<R, T> {
+    /// Get a handle to step the stream under [`Pacing::Stepped`](Pacing::Stepped).
+    pub fn stepper(&self) -> Stepper {
+        self.stream.stepper()
+    }
+}
+
+impl<R: Read + Unpin, T: Timer> Stream for ReplayStream<R, T> {
+    type Item = Result<Packet, StreamError<CaptureError>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.closed {
+            return Poll::Ready(None);
+        }
+        if let Some(pack) = self.responses.pop_front() {
+            return Poll::Ready(Some(Ok(pack)));
+        }
+
+        self.rx_waker = Some(cx.waker().clone());
+        let item = ready!(Pin::new(&mut self.stream).poll_next(cx));
+        Poll::Ready(item.map(|item| {
+            let pack = item.map_err(StreamError::from)?;
+            if pack.op() == Operation::HeartBeatResponse {
+                if let Ok(popularity) = pack.int32_be() {
+                    self.popularity = popularity as u32;
+                }
+            }
+            Ok(pack)
+        }))
+    }
+}
+
+impl<R: Unpin, T: Unpin> Sink<Packet> for ReplayStream<R, T> {
+    type Error = StreamError<CaptureError>;
+
+    fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Packet) -> Result<(), Self::Error> {
+        if item.op() == Operation::HeartBeat {
+            let resp = Packet::new(
+                Operation::HeartBeatResponse,
+                Protocol::Int32BE,
+                self.popularity.to_be_bytes(),
+            );
+            self.responses.push_back(resp);
+            if let Some(waker) = self.rx_waker.take() {
+                waker.wake();
+            }
+        }
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.closed = true;
+        if let Some(waker) = self.rx_waker.take() {
+            waker.wake();
+        }
+        Poll::Ready(Ok(()))
+    }
+}
@@ -0,0 +1,279 @@
+use std::io::{self, Cursor, Write};
+use std::time::{Duration, Instant};
+
+use futures::{stream, FutureExt, SinkExt, StreamExt};
+
+use crate::config::HeartbeatConfig;
+use crate::errors::{CaptureError, StreamError};
+use crate::events::Popularity;
+use crate::packet::{Operation, Packet, Protocol};
+
+use crate::stream::{DefaultTimer as TestTimer, HeartbeatStream};
+use crate::test_utils::{block_on, danmaku};
+
+use super::{CaptureReader, CaptureWriter, Pacing, Recorder, ReplayStream, MAGIC, VERSION};
+
+/// Build a capture with given delays in milliseconds.
+fn capture(records: &[(u32, Packet)]) -> Vec<u8> {
+    let mut buf = MAGIC.to_vec();
+    buf.push(VERSION);
+    buf.extend(0u64.to_be_bytes());
+    for (delay, pack) in records {
+        buf.extend(delay.to_be_bytes());
+        buf.extend(pack.encode());
+    }
+    buf
+}
+
+#[test]
+fn must_round_trip_capture() {
+    let packs = vec![
+        danmaku("first"),
+        Packet::new(
+            Operation::HeartBeatResponse,
+            Protocol::Int32BE,
+            42i32.to_be_bytes(),
+        ),
+        danmaku("second"),
+    ];
+    let items = packs
+        .iter()
+        .cloned()
+        .map(Ok::<_, StreamError<io::Error>>)
+        .collect::<Vec<_>>();
+    let mut recorder = Recorder::new(stream::iter(items), vec![]).unwrap();
+
+    let received: Vec<_> = block_on(recorder.by_ref().collect::<Vec<_>>())
+        .into_iter()
+        .map(|pack| pack.expect("stream error"))
+        .collect();
+    assert_eq!(received, packs);
+
+    let buf = recorder.into_inner().1.into_inner();
+    let reader = CaptureReader::new(buf.as_slice()).unwrap();
+    assert!(reader.started_at().elapsed().unwrap() < Duration::from_secs(60));
+    let replayed: Vec<_> = reader
+        .map(|record| record.expect("capture error").into_packet())
+        .collect();
+    assert_eq!(replayed, packs);
+}
+
+#[test]
+fn must_split_compressed_record() {
+    let batch = Packet::compress_batch(
+        &[danmaku("first"), danmaku("second")],
+        crate::config::Compression::Zlib,
+    )
+    .unwrap();
+    let buf = capture(&[(100, batch)]);
+
+    let records: Vec<_> = CaptureReader::new(buf.as_slice())
+        .unwrap()
+        .map(|record| record.expect("capture error"))
+        .collect();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].delay(), Duration::from_millis(100));
+    assert_eq!(records[0].packet(), &danmaku("first"));
+    assert_eq!(records[1].delay(), Duration::ZERO);
+    assert_eq!(records[1].packet(), &danmaku("second"));
+}
+
+#[test]
+fn must_reject_invalid_capture() {
+    assert!(matches!(
+        CaptureReader::new(&b"NOTCAP\0\0\0\0\0\0\0\0"[..]),
+        Err(CaptureError::InvalidMagic)
+    ));
+
+    let mut buf = capture(&[]);
+    buf[5] = VERSION + 1;
+    assert!(matches!(
+        CaptureReader::new(buf.as_slice()),
+        Err(CaptureError::UnsupportedVersion(v)) if v == VERSION + 1
+    ));
+
+    let buf = capture(&[(0, danmaku("first"))]);
+    let mut reader = CaptureReader::new(&buf[..buf.len() - 1]).unwrap();
+    assert!(matches!(reader.read_record(), Err(CaptureError::Truncated)));
+
+    // a corrupted length must not be allocated
+    let mut buf = capture(&[(0, danmaku("first"))]);
+    buf[18..22].copy_from_slice(&u32::MAX.to_be_bytes());
+    let mut reader = CaptureReader::new(buf.as_slice()).unwrap();
+    assert!(matches!(
+        reader.read_record(),
+        Err(CaptureError::PacketTooLarge(len)) if len == u32::MAX as usize
+    ));
+}
+
+#[test]
+fn must_replay_with_timing() {
+    let buf = capture(&[
+        (0, danmaku("first")),
+        (100, danmaku("second")),
+        (100, danmaku("third")),
+    ]);
+    let replay = |pacing| {
+        let stream = CaptureReader::new(buf.as_slice())
+            .unwrap()
+            .into_stream::<TestTimer>(pacing);
+        let start = Instant::now();
+        let packs = block_on(stream.map(Result::unwrap).collect::<Vec<_>>());
+        assert_eq!(packs.len(), 3);
+        start.elapsed()
+    };
+
+    assert!(replay(Pacing::RealTime) >= Duration::from_millis(200));
+    let accelerated = replay(Pacing::Accelerated(10.0));
+    assert!(accelerated >= Duration::from_millis(20) && accelerated < Duration::from_millis(100));
+    assert!(replay(Pacing::AsFastAsPossible) < Duration::from_millis(100));
+}
+
+fn replay_stream(
+    records: &[(u32, Packet)],
+    pacing: Pacing,
+) -> ReplayStream<Cursor<Vec<u8>>, TestTimer> {
+    let reader = CaptureReader::new(Cursor::new(capture(records))).unwrap();
+    ReplayStream::new(reader, pacing)
+}
+
+#[test]
+fn must_replay_stepped() {
+    let mut stream = replay_stream(
+        &[(0, danmaku("first")), (60_000, danmaku("second"))],
+        Pacing::Stepped,
+    );
+    let stepper = stream.stepper();
+
+    block_on(async {
+        assert!(stream.next().now_or_never().is_none());
+        stepper.step();
+        assert_eq!(stream.next().await.unwrap().unwrap(), danmaku("first"));
+        assert!(stream.next().now_or_never().is_none());
+        stepper.step_by(2);
+        assert_eq!(stream.next().await.unwrap().unwrap(), danmaku("second"));
+        assert!(stream.next().await.is_none());
+    });
+}
+
+#[test]
+fn must_answer_replayed_heartbeats() {
+    let popularity = Packet::new(
+        Operation::HeartBeatResponse,
+        Protocol::Int32BE,
+        42i32.to_be_bytes(),
+    );
+    let mut stream = replay_stream(
+        &[(0, popularity.clone()), (0, danmaku("first"))],
+        Pacing::Stepped,
+    );
+    let stepper = stream.stepper();
+
+    block_on(async {
+        stream
+            .send(Packet::new(Operation::HeartBeat, Protocol::Json, vec![]))
+            .await
+            .unwrap();
+        let resp = stream.next().await.unwrap().unwrap();
+        assert_eq!(resp.int32_be().unwrap(), 1);
+
+        stepper.step();
+        assert_eq!(stream.next().await.unwrap().unwrap(), popularity);
+
+        stream.close().await.unwrap();
+        stepper.step();
+        assert!(stream.next().await.is_none());
+    });
+}
+
+#[test]
+fn must_yield_replay_error() {
+    let buf = capture(&[(0, danmaku("first")), (0, danmaku("second"))]);
+    let reader = CaptureReader::new(Cursor::new(buf[..buf.len() - 1].to_vec())).unwrap();
+    let mut stream: ReplayStream<_, TestTimer> =
+        ReplayStream::new(reader, Pacing::AsFastAsPossible);
+
+    block_on(async {
+        assert_eq!(stream.next().await.unwrap().unwrap(), danmaku("first"));
+        assert!(matches!(
+            stream.next().await,
+            Some(Err(StreamError::IO(e))) if e.kind() == io::ErrorKind::InvalidData
+        ));
+    });
+}
+
+#[test]
+fn must_wrap_replay_in_heartbeat_stream() {
+    let stream = replay_stream(
+        &[
+            (
+                0,
+                Packet::new(
+                    Operation::HeartBeatResponse,
+                    Protocol::Int32BE,
+                    42i32.to_be_bytes(),
+                ),
+            ),
+            (0, danmaku("first")),
+        ],
+        Pacing::Stepped,
+    );
+    let stepper = stream.stepper();
+    let config = HeartbeatConfig::new(Duration::from_millis(50), Duration::from_millis(500));
+    let mut stream: HeartbeatStream<_, _, TestTimer> = HeartbeatStream::with_config(stream, config);
+
+    block_on(async {
+        // only heartbeat responses are yielded before stepping, and the stream never times out
+        for _ in 0..3 {
+            let pack = stream.next().await.unwrap().unwrap();
+            assert_eq!(pack.op(), Operation::HeartBeatResponse);
+        }
+        assert_eq!(stream.popularity(), Some(Popularity(1)));
+
+        stepper.step_by(2);
+        loop {
+            let pack = stream.next().await.unwrap().unwrap();
+            if pack.op() == Operation::Notification {
+                assert_eq!(pack, danmaku("first"));
+                break;
+            }
+        }
+        assert_eq!(stream.popularity(), Some(Popularity(42)));
+    });
+}
+
+/// A writer which fails after given number of bytes.
+struct FailingWriter(usize);
+
+impl Write for FailingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.0 < buf.len() {
+            return Err(io::Error::other("disk full"));
+        }
+        self.0 -= buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn must_yield_record_error() {
+    let items = vec![Ok::<_, StreamError<io::Error>>(danmaku("first"))];
+    let recorder = Recorder::new(stream::iter(items), FailingWriter(14)).unwrap();
+
+    let received = block_on(recorder.collect::<Vec<_>>());
+    assert!(matches!(received[0], Err(StreamError::IO(_))));
+    assert_eq!(received[1].as_ref().unwrap(), &danmaku("first"));
+    assert_eq!(received.len(), 2);
+}
+
+#[test]
+fn must_write_header() {
+    let buf = CaptureWriter::new(vec![]).unwrap().into_inner();
+    assert_eq!(&buf[..5], MAGIC);
+    assert_eq!(buf[5], VERSION);
+    assert_eq!(buf.len(), 14);
+}
@@ -1,5 +1,152 @@
 //! Configuration types.
 
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::time::Duration;
+
+/// Compression algorithm of the payloads requested from the danmaku server.
+///
+/// The server batches notifications and compresses them with the negotiated algorithm. Only the
+/// algorithm can be negotiated; the compression level is chosen by the server.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub enum Compression {
+    /// Zlib-compressed batches (`protover` 2).
+    #[default]
+    Zlib,
+    /// Brotli-compressed batches (`protover` 3).
+    Brotli,
+}
+
+impl Compression {
+    /// The `protover` value sent in the room enter packet.
+    #[must_use]
+    pub const fn protover(self) -> u8 {
+        match self {
+            Self::Zlib => 2,
+            Self::Brotli => 3,
+        }
+    }
+}
+
+/// Bilibili account credential, extracted from browser cookies.
+///
+/// Used to authenticate HTTP requests and the danmaku connection, so that real user identities
+/// are received instead of masked ones.
+#[derive(Clone, Eq, PartialEq, Hash)]
+pub struct Credential {
+    sessdata: String,
+    bili_jct: String,
+    buvid3: String,
+    uid: u64,
+}
+
+impl Credential {
+    /// Construct a credential from cookie values `SESSDATA`, `bili_jct`, `buvid3` and `DedeUserID`.
+    #[must_use]
+    pub fn new(sessdata: &str, bili_jct: &str, buvid3: &str, uid: u64) -> Self {
+        Self {
+            sessdata: sessdata.to_string(),
+            bili_jct: bili_jct.to_string(),
+            buvid3: buvid3.to_string(),
+            uid,
+        }
+    }
+}
+
+impl Credential {
+    /// `SESSDATA` cookie.
+    #[must_use]
+    pub fn sessdata(&self) -> &str {
+        &self.sessdata
+    }
+    /// `bili_jct` cookie, also used as csrf token.
+    #[must_use]
+    pub fn bili_jct(&self) -> &str {
+        &self.bili_jct
+    }
+    /// `buvid3` cookie.
+    #[must_use]
+    pub fn buvid3(&self) -> &str {
+        &self.buvid3
+    }
+    /// `DedeUserID` cookie, i.e. the user id of the account.
+    #[must_use]
+    pub const fn uid(&self) -> u64 {
+        self.uid
+    }
+    /// Value of the `Cookie` header carrying this credential.
+    #[must_use]
+    pub fn cookie(&self) -> String {
+        format!(
+            "SESSDATA={}; bili_jct={}; buvid3={}; DedeUserID={}",
+            self.sessdata, self.bili_jct, self.buvid3, self.uid
+        )
+    }
+}
+
+impl Debug for Credential {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("Credential")
+            .field("sessdata", &"<redacted>")
+            .field("bili_jct", &"<redacted>")
+            .field("buvid3", &self.buvid3)
+            .field("uid", &self.uid)
+            .finish()
+    }
+}
+
+/// The configuration for heartbeat mechanism.
+///
+/// A heartbeat is sent every `interval`. If no packet is received within `timeout` after an
+/// unanswered heartbeat, the connection is considered dead.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct HeartbeatConfig {
+    interval: Duration,
+    timeout: Duration,
+    payload: Vec<u8>,
+}
+
+impl HeartbeatConfig {
+    /// Construct a heartbeat config with given interval and response timeout.
+    #[must_use]
+    pub const fn new(interval: Duration, timeout: Duration) -> Self {
+        Self {
+            interval,
+            timeout,
+            payload: Vec::new(),
+        }
+    }
+    /// Set the payload carried by heartbeat packets. By default it's empty.
+    #[must_use]
+    pub fn with_payload(mut self, payload: impl Into<Vec<u8>>) -> Self {
+        self.payload = payload.into();
+        self
+    }
+}
+
+impl HeartbeatConfig {
+    /// Interval between heartbeats.
+    #[must_use]
+    pub const fn interval(&self) -> Duration {
+        self.interval
+    }
+    /// Time to wait for any packet after an unanswered heartbeat.
+    #[must_use]
+    pub const fn timeout(&self) -> Duration {
+        self.timeout
+    }
+    /// Payload carried by heartbeat packets.
+    #[must_use]
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(30), Duration::from_secs(20))
+    }
+}
+
 /// The configuration for bilibili live stream connection.
 #[derive(Debug, Clone)]
 pub struct StreamConfig(Box<StreamConfigInner>);
@@ -12,17 +159,42 @@ impl StreamConfig {
             uid,
             token,
             servers,
+            compression: Compression::default(),
+            credential: None,
+            heartbeat: HeartbeatConfig::default(),
         }))
     }
 }
 
+impl StreamConfig {
+    /// Set the payload compression algorithm requested from the server. By default it's `Zlib`.
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.0.compression = compression;
+    }
+    /// Set the credential used to enter the room. By default the connection is anonymous.
+    ///
+    /// The user id is replaced by the one of the credential, since the server only accepts the
+    /// token with the user it's issued to.
+    pub fn set_credential(&mut self, credential: Credential) {
+        self.0.uid = credential.uid();
+        self.0.credential = Some(credential);
+    }
+    /// Set the heartbeat behavior. By default a heartbeat is sent every 30 seconds, and the
+    /// connection times out if nothing is received in 20 seconds after it.
+    pub fn set_heartbeat(&mut self, heartbeat: HeartbeatConfig) {
+        self.0.heartbeat = heartbeat;
+    }
+}
+
 impl StreamConfig {
     /// Live room id (long version).
     #[must_use]
     pub const fn room_id(&self) -> u64 {
         self.0.room_id
     }
-    /// Live room user id.
+    /// User id to enter the room with.
+    ///
+    /// It's the user id of the credential if there's one.
     #[must_use]
     pub const fn uid(&self) -> u64 {
         self.0.uid
@@ -37,6 +209,21 @@ impl StreamConfig {
     pub fn servers(&self) -> &[String] {
         &self.0.servers
     }
+    /// Payload compression algorithm requested from the server.
+    #[must_use]
+    pub const fn compression(&self) -> Compression {
+        self.0.compression
+    }
+    /// Credential used to enter the room.
+    #[must_use]
+    pub const fn credential(&self) -> Option<&Credential> {
+        self.0.credential.as_ref()
+    }
+    /// Heartbeat behavior.
+    #[must_use]
+    pub const fn heartbeat(&self) -> &HeartbeatConfig {
+        &self.0.heartbeat
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -48,4 +235,10 @@ struct StreamConfigInner {
     /// Danmaku server token.
     token: String,
     servers: Vec<String>,
+    /// Payload compression algorithm requested from the server.
+    compression: Compression,
+    /// Credential used to enter the room.
+    credential: Option<Credential>,
+    /// Heartbeat behavior.
+    heartbeat: HeartbeatConfig,
 }
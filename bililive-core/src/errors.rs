@@ -8,7 +8,7 @@ use thiserror::Error;
 ///
 /// * `Ok` indicates a successful parse.
 /// * `Incomplete` means that more data is needed to complete the parsing.
-/// The `Needed` enum can contain how many additional bytes are necessary.
+///   The `Needed` enum can contain how many additional bytes are necessary.
 /// * `Err` indicates an error.
 pub enum IncompleteResult<T> {
     Ok(T),
@@ -23,12 +23,16 @@ pub enum ParseError {
     Json(#[from] serde_json::Error),
     #[error("not a valid int32 big endian")]
     Int32BE,
+    #[error("packet carries no live event")]
+    NoEvent,
     #[error("unknown websocket pack protocol")]
     UnknownProtocol,
     #[error("error when parsing packet struct")]
     PacketError(String),
     #[error("error when decompressing packet buffer: {0}")]
     ZlibError(#[from] std::io::Error),
+    #[error("error when decompressing brotli packet buffer: {0}")]
+    BrotliError(std::io::Error),
 }
 
 #[cfg(feature = "not-send")]
@@ -37,10 +41,41 @@ pub(crate) type BoxedError = Box<dyn std::error::Error>;
 #[cfg(not(feature = "not-send"))]
 pub(crate) type BoxedError = Box<dyn std::error::Error + Send + Sync>;
 
-/// Errors that may occur when making HTTP requests through builder.
+/// Errors that may occur when building config through builder.
 #[derive(Debug, Error)]
-#[error("error when making http request: {0}")]
-pub struct BuildError(#[source] pub(crate) BoxedError);
+pub enum BuildError {
+    #[error("error when making http request: {0}")]
+    Http(#[source] BoxedError),
+    #[error("error when decoding api response: {0}")]
+    Decode(#[from] serde_json::Error),
+    #[error("api error {code}: {message}")]
+    Api { code: i64, message: String },
+    #[error("user has no live room")]
+    NoLiveRoom,
+    #[error("room is locked until {until}")]
+    RoomLocked { until: u64 },
+    #[error("room is encrypted")]
+    RoomEncrypted,
+}
+
+/// Errors that may occur when performing room actions or queries.
+#[derive(Debug, Error)]
+pub enum ActionError {
+    #[error("error when making http request: {0}")]
+    Http(#[source] BoxedError),
+    #[error("error when decoding api response: {0}")]
+    Decode(#[from] serde_json::Error),
+    #[error("not logged in")]
+    NotLoggedIn,
+    #[error("rate limited: {message}")]
+    RateLimited { code: i64, message: String },
+    #[error("muted: {message}")]
+    Muted { message: String },
+    #[error("danmaku dropped by filter")]
+    Filtered,
+    #[error("api error {code}: {message}")]
+    Api { code: i64, message: String },
+}
 
 /// Errors that may occur when consuming a stream.
 ///
@@ -51,8 +86,44 @@ pub enum StreamError<E> {
     Parse(#[from] ParseError),
     #[error("ws error: {0}")]
     WebSocket(E),
+    #[error("connection closed by server: code {code}, reason: {reason}")]
+    Closed { code: u16, reason: String },
+    #[error("io error: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("room enter rejected by server: code {code}")]
+    Auth { code: i64 },
+    #[error("timed out")]
+    Timeout,
+    #[error("no server to connect to")]
+    NoServer,
+}
+
+/// Errors that may occur when reading a capture.
+#[derive(Debug, Error)]
+pub enum CaptureError {
     #[error("io error: {0}")]
     IO(#[from] std::io::Error),
+    #[error("not a capture file")]
+    InvalidMagic,
+    #[error("unsupported capture version: {0}")]
+    UnsupportedVersion(u8),
+    #[error("capture is truncated")]
+    Truncated,
+    #[error("packet too large: {0} bytes")]
+    PacketTooLarge(usize),
+    #[error("parse error: {0}")]
+    Parse(#[from] ParseError),
+}
+
+impl<E> From<CaptureError> for StreamError<E> {
+    /// Parse errors are kept, and other errors of the capture become invalid data io errors.
+    fn from(e: CaptureError) -> Self {
+        match e {
+            CaptureError::IO(e) => Self::IO(e),
+            CaptureError::Parse(e) => Self::Parse(e),
+            e => Self::IO(std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+        }
+    }
 }
 
 impl<E> StreamError<E> {
@@ -0,0 +1,430 @@
+use std::borrow::Cow;
+
+use serde::de::Error as _;
+use serde::Deserialize;
+use serde_json::value::RawValue;
+
+use crate::errors::ParseError;
+
+use super::{
+    Cmd, CmdKind, DanmakuRef, Gift, GuardBuy, InteractKind, InteractWord, LikeClick,
+    LikeInfoUpdate, Live, OnlineRankCount, Popularity, Preparing, RoomChange, SuperChat,
+    SuperChatUser, WatchedChange,
+};
+
+type Result<T, E = ParseError> = std::result::Result<T, E>;
+
+/// Live event borrowing its strings from the packet body.
+///
+/// It mirrors [`Event`](super::Event), but string fields are only allocated if they contain
+/// escape sequences. Events without string fields share their types with
+/// [`Event`](super::Event). Use [`EventRef::into_owned`](EventRef::into_owned) to detach it from
+/// the packet.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum EventRef<'a> {
+    /// Room popularity carried by heartbeat responses.
+    Popularity(Popularity),
+    /// `DANMU_MSG`
+    Danmaku(DanmakuRef<'a>),
+    /// `SEND_GIFT`
+    Gift(GiftRef<'a>),
+    /// `GUARD_BUY`
+    GuardBuy(GuardBuyRef<'a>),
+    /// `SUPER_CHAT_MESSAGE`
+    SuperChat(SuperChatRef<'a>),
+    /// `INTERACT_WORD`
+    InteractWord(InteractWordRef<'a>),
+    /// `ROOM_CHANGE`
+    RoomChange(RoomChangeRef<'a>),
+    /// `LIVE`
+    Live(Live),
+    /// `PREPARING`
+    Preparing(Preparing),
+    /// `ONLINE_RANK_COUNT`
+    OnlineRankCount(OnlineRankCount),
+    /// `WATCHED_CHANGE`
+    WatchedChange(WatchedChangeRef<'a>),
+    /// `LIKE_INFO_V3_UPDATE`
+    LikeInfoUpdate(LikeInfoUpdate),
+    /// `LIKE_INFO_V3_CLICK`
+    LikeClick(LikeClickRef<'a>),
+    /// Any other notification. Contains the whole JSON body.
+    Unknown(&'a str),
+}
+
+/// Top-level fields of a notification body. Other fields are skipped without allocation.
+#[derive(Deserialize)]
+struct Envelope<'a> {
+    #[serde(borrow, default)]
+    cmd: Option<Cmd<'a>>,
+    #[serde(borrow, default)]
+    data: Option<&'a RawValue>,
+    #[serde(borrow, default)]
+    info: Option<&'a RawValue>,
+}
+
+/// The `cmd` field of a notification body. Other fields are skipped without allocation.
+#[derive(Deserialize)]
+struct CmdOnly<'a> {
+    #[serde(borrow, default)]
+    cmd: Option<Cmd<'a>>,
+}
+
+/// Read the `cmd` of a notification body without decoding other fields.
+///
+/// Returns `None` if the body is not a json object or has no `cmd`.
+pub(crate) fn peek_cmd(body: &[u8]) -> Option<Cmd<'_>> {
+    serde_json::from_slice::<CmdOnly>(body)
+        .ok()
+        .and_then(|CmdOnly { cmd }| cmd)
+}
+
+/// A string borrowed from the input if possible.
+#[derive(Deserialize)]
+pub(crate) struct Str<'a>(#[serde(borrow)] pub(crate) Cow<'a, str>);
+
+impl<'a> EventRef<'a> {
+    /// Parse an event from the JSON body of a notification packet.
+    ///
+    /// # Errors
+    /// Returns an error if the body is not a json object, or the `cmd` is known but the body
+    /// doesn't match its model.
+    pub fn from_slice(body: &'a [u8]) -> Result<Self> {
+        Self::from_slice_if(body, |_| true)?.ok_or(ParseError::NoEvent)
+    }
+
+    /// Parse an event from the JSON body of a notification packet if its `cmd` is accepted by
+    /// `filter`.
+    ///
+    /// The `cmd` passed to `filter` is empty if the body has no `cmd`. Rejected notifications are
+    /// discarded before their payload is decoded, so nothing is allocated for them.
+    ///
+    /// # Errors
+    /// Returns an error if the body is not a json object, or the `cmd` is accepted but the body
+    /// doesn't match its model.
+    pub fn from_slice_if(
+        body: &'a [u8],
+        filter: impl FnOnce(&Cmd) -> bool,
+    ) -> Result<Option<Self>> {
+        let envelope: Envelope = serde_json::from_slice(body)?;
+        let cmd = envelope.cmd.unwrap_or_else(|| Cmd::parse(""));
+        if !filter(&cmd) {
+            return Ok(None);
+        }
+
+        let data = || {
+            envelope
+                .data
+                .ok_or_else(|| serde_json::Error::missing_field("data"))
+        };
+        Ok(Some(match cmd.kind() {
+            CmdKind::Danmaku => {
+                let info = envelope
+                    .info
+                    .ok_or_else(|| serde_json::Error::missing_field("info"))?;
+                Self::Danmaku(from_raw(info)?)
+            }
+            CmdKind::Gift => Self::Gift(from_raw(data()?)?),
+            CmdKind::GuardBuy => Self::GuardBuy(from_raw(data()?)?),
+            CmdKind::SuperChat => Self::SuperChat(from_raw(data()?)?),
+            CmdKind::InteractWord => Self::InteractWord(from_raw(data()?)?),
+            CmdKind::RoomChange => Self::RoomChange(from_raw(data()?)?),
+            CmdKind::Live => Self::Live(serde_json::from_slice(body)?),
+            CmdKind::Preparing => Self::Preparing(serde_json::from_slice(body)?),
+            CmdKind::OnlineRankCount => Self::OnlineRankCount(from_raw(data()?)?),
+            CmdKind::WatchedChange => Self::WatchedChange(from_raw(data()?)?),
+            CmdKind::LikeInfoUpdate => Self::LikeInfoUpdate(from_raw(data()?)?),
+            CmdKind::LikeClick => Self::LikeClick(from_raw(data()?)?),
+            CmdKind::Other => Self::Unknown(serde_json::from_slice::<&RawValue>(body)?.get()),
+        }))
+    }
+
+    /// Convert into an owned [`Event`](super::Event).
+    ///
+    /// # Errors
+    /// Returns an error if an unknown event isn't valid json, which never happens for events
+    /// parsed by [`EventRef::from_slice`](EventRef::from_slice).
+    pub fn into_owned(self) -> Result<super::Event> {
+        use super::Event;
+
+        Ok(match self {
+            Self::Popularity(e) => Event::Popularity(e),
+            Self::Danmaku(e) => Event::Danmaku(e.into_owned()),
+            Self::Gift(e) => Event::Gift(e.into_owned()),
+            Self::GuardBuy(e) => Event::GuardBuy(e.into_owned()),
+            Self::SuperChat(e) => Event::SuperChat(e.into_owned()),
+            Self::InteractWord(e) => Event::InteractWord(e.into_owned()),
+            Self::RoomChange(e) => Event::RoomChange(e.into_owned()),
+            Self::Live(e) => Event::Live(e),
+            Self::Preparing(e) => Event::Preparing(e),
+            Self::OnlineRankCount(e) => Event::OnlineRankCount(e),
+            Self::WatchedChange(e) => Event::WatchedChange(e.into_owned()),
+            Self::LikeInfoUpdate(e) => Event::LikeInfoUpdate(e),
+            Self::LikeClick(e) => Event::LikeClick(e.into_owned()),
+            Self::Unknown(body) => Event::Unknown(serde_json::from_str(body)?),
+        })
+    }
+}
+
+fn from_raw<'a, T: Deserialize<'a>>(raw: &'a RawValue) -> Result<T> {
+    Ok(serde_json::from_str(raw.get())?)
+}
+
+/// A borrowed [`Gift`](Gift).
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+pub struct GiftRef<'a> {
+    /// Sender user id.
+    pub uid: u64,
+    /// Sender user name.
+    #[serde(borrow)]
+    pub uname: Cow<'a, str>,
+    /// Gift id.
+    #[serde(rename = "giftId")]
+    pub gift_id: u64,
+    /// Gift name.
+    #[serde(rename = "giftName", borrow)]
+    pub gift_name: Cow<'a, str>,
+    /// Count of gifts sent.
+    pub num: u32,
+    /// Price of a single gift. 1000 gold coins equal to 1 CNY.
+    pub price: u64,
+    /// `gold` or `silver`.
+    #[serde(borrow)]
+    pub coin_type: Cow<'a, str>,
+    /// Total price of the gifts.
+    pub total_coin: u64,
+    /// Action text, e.g. `投喂`.
+    #[serde(borrow)]
+    pub action: Cow<'a, str>,
+    /// Send time in seconds since unix epoch.
+    pub timestamp: u64,
+}
+
+impl GiftRef<'_> {
+    /// Convert into an owned [`Gift`](Gift).
+    #[must_use]
+    pub fn into_owned(self) -> Gift {
+        Gift {
+            uid: self.uid,
+            uname: self.uname.into_owned(),
+            gift_id: self.gift_id,
+            gift_name: self.gift_name.into_owned(),
+            num: self.num,
+            price: self.price,
+            coin_type: self.coin_type.into_owned(),
+            total_coin: self.total_coin,
+            action: self.action.into_owned(),
+            timestamp: self.timestamp,
+        }
+    }
+}
+
+/// A borrowed [`GuardBuy`](GuardBuy).
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+pub struct GuardBuyRef<'a> {
+    /// Buyer user id.
+    pub uid: u64,
+    /// Buyer user name.
+    #[serde(borrow)]
+    pub username: Cow<'a, str>,
+    /// Guard level. 1 for 总督, 2 for 提督 and 3 for 舰长.
+    pub guard_level: u8,
+    /// Count of months purchased.
+    pub num: u32,
+    /// Price in gold coins.
+    pub price: u64,
+    /// Gift id.
+    pub gift_id: u64,
+    /// Gift name.
+    #[serde(borrow)]
+    pub gift_name: Cow<'a, str>,
+    /// Start time in seconds since unix epoch.
+    pub start_time: u64,
+    /// End time in seconds since unix epoch.
+    pub end_time: u64,
+}
+
+impl GuardBuyRef<'_> {
+    /// Convert into an owned [`GuardBuy`](GuardBuy).
+    #[must_use]
+    pub fn into_owned(self) -> GuardBuy {
+        GuardBuy {
+            uid: self.uid,
+            username: self.username.into_owned(),
+            guard_level: self.guard_level,
+            num: self.num,
+            price: self.price,
+            gift_id: self.gift_id,
+            gift_name: self.gift_name.into_owned(),
+            start_time: self.start_time,
+            end_time: self.end_time,
+        }
+    }
+}
+
+/// A borrowed [`SuperChat`](SuperChat).
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+pub struct SuperChatRef<'a> {
+    /// Super chat id.
+    pub id: u64,
+    /// Sender user id.
+    pub uid: u64,
+    /// Price in CNY.
+    pub price: u64,
+    /// Message text.
+    #[serde(borrow)]
+    pub message: Cow<'a, str>,
+    /// Sender information.
+    #[serde(borrow)]
+    pub user_info: SuperChatUserRef<'a>,
+    /// Start time in seconds since unix epoch.
+    pub start_time: u64,
+    /// End time in seconds since unix epoch.
+    pub end_time: u64,
+    /// Duration in seconds for which the message is pinned.
+    pub time: u64,
+}
+
+impl SuperChatRef<'_> {
+    /// Convert into an owned [`SuperChat`](SuperChat).
+    #[must_use]
+    pub fn into_owned(self) -> SuperChat {
+        SuperChat {
+            id: self.id,
+            uid: self.uid,
+            price: self.price,
+            message: self.message.into_owned(),
+            user_info: SuperChatUser {
+                uname: self.user_info.uname.into_owned(),
+                face: self.user_info.face.into_owned(),
+            },
+            start_time: self.start_time,
+            end_time: self.end_time,
+            time: self.time,
+        }
+    }
+}
+
+/// A borrowed [`SuperChatUser`](SuperChatUser).
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+pub struct SuperChatUserRef<'a> {
+    /// Sender user name.
+    #[serde(borrow)]
+    pub uname: Cow<'a, str>,
+    /// Sender avatar url.
+    #[serde(borrow)]
+    pub face: Cow<'a, str>,
+}
+
+/// A borrowed [`InteractWord`](InteractWord).
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+pub struct InteractWordRef<'a> {
+    /// User id.
+    pub uid: u64,
+    /// User name.
+    #[serde(borrow)]
+    pub uname: Cow<'a, str>,
+    /// Interaction type.
+    #[serde(rename = "msg_type")]
+    pub kind: InteractKind,
+    /// Room id (long version).
+    pub roomid: u64,
+    /// Interaction time in seconds since unix epoch.
+    pub timestamp: u64,
+}
+
+impl InteractWordRef<'_> {
+    /// Convert into an owned [`InteractWord`](InteractWord).
+    #[must_use]
+    pub fn into_owned(self) -> InteractWord {
+        InteractWord {
+            uid: self.uid,
+            uname: self.uname.into_owned(),
+            kind: self.kind,
+            roomid: self.roomid,
+            timestamp: self.timestamp,
+        }
+    }
+}
+
+/// A borrowed [`RoomChange`](RoomChange).
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+pub struct RoomChangeRef<'a> {
+    /// Room title.
+    #[serde(borrow)]
+    pub title: Cow<'a, str>,
+    /// Area id.
+    pub area_id: u64,
+    /// Parent area id.
+    pub parent_area_id: u64,
+    /// Area name.
+    #[serde(borrow)]
+    pub area_name: Cow<'a, str>,
+    /// Parent area name.
+    #[serde(borrow)]
+    pub parent_area_name: Cow<'a, str>,
+}
+
+impl RoomChangeRef<'_> {
+    /// Convert into an owned [`RoomChange`](RoomChange).
+    #[must_use]
+    pub fn into_owned(self) -> RoomChange {
+        RoomChange {
+            title: self.title.into_owned(),
+            area_id: self.area_id,
+            parent_area_id: self.parent_area_id,
+            area_name: self.area_name.into_owned(),
+            parent_area_name: self.parent_area_name.into_owned(),
+        }
+    }
+}
+
+/// A borrowed [`WatchedChange`](WatchedChange).
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+pub struct WatchedChangeRef<'a> {
+    /// Count of users.
+    pub num: u64,
+    /// Short text shown on the client, e.g. `1.2万`.
+    #[serde(borrow)]
+    pub text_small: Cow<'a, str>,
+    /// Long text shown on the client, e.g. `1.2万人看过`.
+    #[serde(borrow)]
+    pub text_large: Cow<'a, str>,
+}
+
+impl WatchedChangeRef<'_> {
+    /// Convert into an owned [`WatchedChange`](WatchedChange).
+    #[must_use]
+    pub fn into_owned(self) -> WatchedChange {
+        WatchedChange {
+            num: self.num,
+            text_small: self.text_small.into_owned(),
+            text_large: self.text_large.into_owned(),
+        }
+    }
+}
+
+/// A borrowed [`LikeClick`](LikeClick).
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+pub struct LikeClickRef<'a> {
+    /// User id.
+    pub uid: u64,
+    /// User name.
+    #[serde(borrow)]
+    pub uname: Cow<'a, str>,
+    /// Like text, e.g. `为主播点赞了`.
+    #[serde(borrow)]
+    pub like_text: Cow<'a, str>,
+}
+
+impl LikeClickRef<'_> {
+    /// Convert into an owned [`LikeClick`](LikeClick).
+    #[must_use]
+    pub fn into_owned(self) -> LikeClick {
+        LikeClick {
+            uid: self.uid,
+            uname: self.uname.into_owned(),
+            like_text: self.like_text.into_owned(),
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use std::borrow::Cow;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::str::Split;
+
+use serde::{Deserialize, Deserializer};
+
+use super::Str;
+
+/// Kinds of notification commands modeled by [`Event`](super::Event).
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum CmdKind {
+    /// `DANMU_MSG`
+    Danmaku,
+    /// `SEND_GIFT`
+    Gift,
+    /// `GUARD_BUY`
+    GuardBuy,
+    /// `SUPER_CHAT_MESSAGE`
+    SuperChat,
+    /// `INTERACT_WORD`
+    InteractWord,
+    /// `ROOM_CHANGE`
+    RoomChange,
+    /// `LIVE`
+    Live,
+    /// `PREPARING`
+    Preparing,
+    /// `ONLINE_RANK_COUNT`
+    OnlineRankCount,
+    /// `WATCHED_CHANGE`
+    WatchedChange,
+    /// `LIKE_INFO_V3_UPDATE`
+    LikeInfoUpdate,
+    /// `LIKE_INFO_V3_CLICK`
+    LikeClick,
+    /// Any other command.
+    Other,
+}
+
+impl CmdKind {
+    /// Get the kind of a base command name, i.e. without version parameters.
+    #[must_use]
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "DANMU_MSG" => Self::Danmaku,
+            "SEND_GIFT" => Self::Gift,
+            "GUARD_BUY" => Self::GuardBuy,
+            "SUPER_CHAT_MESSAGE" => Self::SuperChat,
+            "INTERACT_WORD" => Self::InteractWord,
+            "ROOM_CHANGE" => Self::RoomChange,
+            "LIVE" => Self::Live,
+            "PREPARING" => Self::Preparing,
+            "ONLINE_RANK_COUNT" => Self::OnlineRankCount,
+            "WATCHED_CHANGE" => Self::WatchedChange,
+            "LIKE_INFO_V3_UPDATE" => Self::LikeInfoUpdate,
+            "LIKE_INFO_V3_CLICK" => Self::LikeClick,
+            _ => Self::Other,
+        }
+    }
+    /// Get the base command name of the kind, or `None` for [`CmdKind::Other`](CmdKind::Other).
+    #[must_use]
+    pub const fn name(self) -> Option<&'static str> {
+        Some(match self {
+            Self::Danmaku => "DANMU_MSG",
+            Self::Gift => "SEND_GIFT",
+            Self::GuardBuy => "GUARD_BUY",
+            Self::SuperChat => "SUPER_CHAT_MESSAGE",
+            Self::InteractWord => "INTERACT_WORD",
+            Self::RoomChange => "ROOM_CHANGE",
+            Self::Live => "LIVE",
+            Self::Preparing => "PREPARING",
+            Self::OnlineRankCount => "ONLINE_RANK_COUNT",
+            Self::WatchedChange => "WATCHED_CHANGE",
+            Self::LikeInfoUpdate => "LIKE_INFO_V3_UPDATE",
+            Self::LikeClick => "LIKE_INFO_V3_CLICK",
+            Self::Other => return None,
+        })
+    }
+}
+
+/// The `cmd` of a notification.
+///
+/// Commands may be suffixed with colon-separated version parameters, e.g.
+/// `DANMU_MSG:4:0:2:2:2:0`. Match on [`name`](Cmd::name) or [`kind`](Cmd::kind) instead of the raw
+/// string so that such variants are handled alike.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Cmd<'a> {
+    raw: Cow<'a, str>,
+    kind: CmdKind,
+}
+
+impl<'a> Cmd<'a> {
+    /// Parse a raw command.
+    pub fn parse(raw: impl Into<Cow<'a, str>>) -> Self {
+        let raw = raw.into();
+        let kind = CmdKind::from_name(raw.split(':').next().unwrap_or_default());
+        Self { raw, kind }
+    }
+    /// Get the raw command, including version parameters.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+    /// Get the base command, e.g. `DANMU_MSG` for `DANMU_MSG:4:0:2:2:2:0`.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        self.raw.split(':').next().unwrap_or_default()
+    }
+    /// Get the kind of the command.
+    #[must_use]
+    pub const fn kind(&self) -> CmdKind {
+        self.kind
+    }
+    /// Get the version parameters, e.g. `["4", "0", "2", "2", "2", "0"]` for
+    /// `DANMU_MSG:4:0:2:2:2:0`.
+    #[must_use]
+    pub fn params(&self) -> Params<'_> {
+        let mut split = self.raw.split(':');
+        split.next();
+        Params(split)
+    }
+    /// Whether the base command equals to `name`.
+    #[must_use]
+    pub fn is(&self, name: &str) -> bool {
+        self.name() == name
+    }
+    /// Detach the command from the buffer it borrows from.
+    #[must_use]
+    pub fn into_owned(self) -> Cmd<'static> {
+        Cmd {
+            raw: Cow::Owned(self.raw.into_owned()),
+            kind: self.kind,
+        }
+    }
+}
+
+impl Display for Cmd<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str(&self.raw)
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for Cmd<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let Str(raw) = Str::deserialize(deserializer)?;
+        Ok(Self::parse(raw))
+    }
+}
+
+/// Iterator over version parameters of a [`Cmd`](Cmd).
+#[derive(Debug, Clone)]
+pub struct Params<'a>(Split<'a, char>);
+
+impl<'a> Iterator for Params<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
@@ -0,0 +1,595 @@
+use std::borrow::Cow;
+use std::fmt::{Formatter, Result as FmtResult};
+use std::marker::PhantomData;
+
+use serde::de::value::MapAccessDeserializer;
+use serde::de::{DeserializeOwned, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+
+use super::Str;
+
+/// A danmaku (chat message).
+///
+/// Decoded from the positional `info` array of `DANMU_MSG`. Only the sender, the text and the
+/// timestamp are required. Other fields fall back to their defaults if missing or `null`, since
+/// old or trimmed messages may omit the tail of the arrays.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Danmaku {
+    /// Sender user id.
+    pub uid: u64,
+    /// Sender user name.
+    pub uname: String,
+    /// Message text.
+    pub text: String,
+    /// Send time in milliseconds since unix epoch.
+    pub timestamp: u64,
+    /// Display mode. Unknown modes are treated as [`DanmakuMode::Scroll`](DanmakuMode::Scroll).
+    pub mode: DanmakuMode,
+    /// Font size.
+    pub font_size: u32,
+    /// Color in `0xRRGGBB`.
+    pub color: u32,
+    /// Type of the message.
+    pub dm_type: DanmakuType,
+    /// The sticker, if the message is one.
+    pub emoticon: Option<Emoticon>,
+    /// The user replied to, if any.
+    pub reply_to: Option<ReplyTo>,
+    /// Fan medal worn by the sender, if any.
+    pub medal: Option<Medal>,
+    /// User level of the sender.
+    pub user_level: Option<u32>,
+    /// Title id worn by the sender, e.g. `title-111-1`.
+    pub title: Option<String>,
+    /// Guard level of the sender in this room. 0 for none, 1 for 总督, 2 for 提督 and 3 for 舰长.
+    pub guard_level: u8,
+}
+
+/// Danmaku types.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+pub enum DanmakuType {
+    /// Plain text.
+    #[default]
+    Text,
+    /// A sticker. See [`Danmaku::emoticon`](Danmaku::emoticon).
+    Emoticon,
+    /// A type not recognized by this crate, with the raw `dm_type` value.
+    Unknown(u8),
+}
+
+impl From<u8> for DanmakuType {
+    fn from(i: u8) -> Self {
+        match i {
+            0 => Self::Text,
+            1 => Self::Emoticon,
+            _ => Self::Unknown(i),
+        }
+    }
+}
+
+/// Display mode of a danmaku.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub enum DanmakuMode {
+    /// Scroll across the screen.
+    #[default]
+    Scroll,
+    /// Fixed at the bottom of the screen.
+    Bottom,
+    /// Fixed at the top of the screen.
+    Top,
+}
+
+impl DanmakuMode {
+    /// The `mode` value used by bilibili.
+    #[must_use]
+    pub const fn value(self) -> u8 {
+        match self {
+            Self::Scroll => 1,
+            Self::Bottom => 4,
+            Self::Top => 5,
+        }
+    }
+    /// Get the mode of a `mode` value used by bilibili.
+    #[must_use]
+    pub const fn from_value(value: u8) -> Option<Self> {
+        match value {
+            1 => Some(Self::Scroll),
+            4 => Some(Self::Bottom),
+            5 => Some(Self::Top),
+            _ => None,
+        }
+    }
+}
+
+/// A sticker sent as a danmaku.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default, Deserialize)]
+#[serde(default)]
+pub struct Emoticon {
+    /// Sticker id, e.g. `official_147`.
+    #[serde(rename = "emoticon_unique")]
+    pub unique: String,
+    /// Image url.
+    pub url: String,
+    /// Image width.
+    pub width: u32,
+    /// Image height.
+    pub height: u32,
+}
+
+/// The user a danmaku replies to.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct ReplyTo {
+    /// User id.
+    pub uid: u64,
+    /// User name.
+    pub uname: String,
+}
+
+/// A fan medal worn by a user.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Medal {
+    /// Medal level.
+    pub level: u32,
+    /// Medal name.
+    pub name: String,
+    /// User name of the streamer the medal belongs to.
+    pub anchor_uname: String,
+    /// User id of the streamer the medal belongs to.
+    pub anchor_uid: u64,
+    /// Room id of the streamer the medal belongs to.
+    pub room_id: u64,
+    /// Medal color in `0xRRGGBB`.
+    pub color: u32,
+    /// Guard level of the user in the medal's room. 0 for none, 1 for 总督, 2 for 提督 and 3 for
+    /// 舰长.
+    pub guard_level: u8,
+    /// Whether the medal is lit, i.e. the user has interacted with the streamer recently.
+    pub is_lighted: bool,
+}
+
+/// A borrowed [`Danmaku`](Danmaku).
+///
+/// Stickers and replies are carried in an embedded JSON string, so they are always allocated.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct DanmakuRef<'a> {
+    /// Sender user id.
+    pub uid: u64,
+    /// Sender user name.
+    pub uname: Cow<'a, str>,
+    /// Message text.
+    pub text: Cow<'a, str>,
+    /// Send time in milliseconds since unix epoch.
+    pub timestamp: u64,
+    /// Display mode. Unknown modes are treated as [`DanmakuMode::Scroll`](DanmakuMode::Scroll).
+    pub mode: DanmakuMode,
+    /// Font size.
+    pub font_size: u32,
+    /// Color in `0xRRGGBB`.
+    pub color: u32,
+    /// Type of the message.
+    pub dm_type: DanmakuType,
+    /// The sticker, if the message is one.
+    pub emoticon: Option<Emoticon>,
+    /// The user replied to, if any.
+    pub reply_to: Option<ReplyTo>,
+    /// Fan medal worn by the sender, if any.
+    pub medal: Option<MedalRef<'a>>,
+    /// User level of the sender.
+    pub user_level: Option<u32>,
+    /// Title id worn by the sender, e.g. `title-111-1`.
+    pub title: Option<Cow<'a, str>>,
+    /// Guard level of the sender in this room. 0 for none, 1 for 总督, 2 for 提督 and 3 for 舰长.
+    pub guard_level: u8,
+}
+
+impl DanmakuRef<'_> {
+    /// Convert into an owned [`Danmaku`](Danmaku).
+    #[must_use]
+    pub fn into_owned(self) -> Danmaku {
+        Danmaku {
+            uid: self.uid,
+            uname: self.uname.into_owned(),
+            text: self.text.into_owned(),
+            timestamp: self.timestamp,
+            mode: self.mode,
+            font_size: self.font_size,
+            color: self.color,
+            dm_type: self.dm_type,
+            emoticon: self.emoticon,
+            reply_to: self.reply_to,
+            medal: self.medal.map(MedalRef::into_owned),
+            user_level: self.user_level,
+            title: self.title.map(Cow::into_owned),
+            guard_level: self.guard_level,
+        }
+    }
+}
+
+/// A borrowed [`Medal`](Medal).
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
+pub struct MedalRef<'a> {
+    /// Medal level.
+    pub level: u32,
+    /// Medal name.
+    pub name: Cow<'a, str>,
+    /// User name of the streamer the medal belongs to.
+    pub anchor_uname: Cow<'a, str>,
+    /// User id of the streamer the medal belongs to.
+    pub anchor_uid: u64,
+    /// Room id of the streamer the medal belongs to.
+    pub room_id: u64,
+    /// Medal color in `0xRRGGBB`.
+    pub color: u32,
+    /// Guard level of the user in the medal's room. 0 for none, 1 for 总督, 2 for 提督 and 3 for
+    /// 舰长.
+    pub guard_level: u8,
+    /// Whether the medal is lit, i.e. the user has interacted with the streamer recently.
+    pub is_lighted: bool,
+}
+
+impl MedalRef<'_> {
+    /// Convert into an owned [`Medal`](Medal).
+    #[must_use]
+    pub fn into_owned(self) -> Medal {
+        Medal {
+            level: self.level,
+            name: self.name.into_owned(),
+            anchor_uname: self.anchor_uname.into_owned(),
+            anchor_uid: self.anchor_uid,
+            room_id: self.room_id,
+            color: self.color,
+            guard_level: self.guard_level,
+            is_lighted: self.is_lighted,
+        }
+    }
+}
+
+/// Decodes the positional `info` array without collecting it.
+///
+/// The expected layout is:
+/// - `[0]`: `[_, mode, font_size, color, timestamp, .., dm_type at 12, sticker at 13, _,
+///   {extra} at 15, ..]`
+/// - `[1]`: text
+/// - `[2]`: `[uid, uname, ..]`
+/// - `[3]`: `[level, name, anchor_uname, room_id, color, .., guard_level at 10, is_lighted,
+///   anchor_uid, ..]`, or empty if no medal is worn
+/// - `[4]`: `[user_level, ..]`
+/// - `[5]`: `[old_title, title]`
+/// - `[7]`: guard level
+impl<'de: 'a, 'a> Deserialize<'de> for DanmakuRef<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct InfoVisitor;
+
+        impl<'de> Visitor<'de> for InfoVisitor {
+            type Value = DanmakuRef<'de>;
+
+            fn expecting(&self, f: &mut Formatter) -> FmtResult {
+                f.write_str("danmaku info array")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let meta: Meta = element(&mut seq, "[0]")?;
+                let Str(text) = element(&mut seq, "[1]")?;
+                let User(uid, Str(uname)) = element(&mut seq, "[2]")?;
+
+                let mut medal = Info(None);
+                let mut user_level = Info(None);
+                let mut title = Info(None);
+                let mut guard_level = 0;
+                for i in 3.. {
+                    let found = match i {
+                        3 => next_into(&mut seq, &mut medal)?,
+                        4 => next_into(&mut seq, &mut user_level)?,
+                        5 => next_into(&mut seq, &mut title)?,
+                        7 => next_into(&mut seq, &mut guard_level)?,
+                        _ => seq.next_element::<IgnoredAny>()?.is_some(),
+                    };
+                    if !found {
+                        break;
+                    }
+                }
+
+                let extra = meta.extra.unwrap_or_default();
+                let emoticon = meta.emoticon.or_else(|| {
+                    (!extra.emoticon_unique.is_empty()).then(|| Emoticon {
+                        unique: extra.emoticon_unique,
+                        ..Default::default()
+                    })
+                });
+                let reply_to = (extra.reply_mid != 0).then_some(ReplyTo {
+                    uid: extra.reply_mid,
+                    uname: extra.reply_uname,
+                });
+
+                Ok(DanmakuRef {
+                    uid,
+                    uname,
+                    text,
+                    timestamp: meta.timestamp,
+                    mode: DanmakuMode::from_value(meta.mode).unwrap_or_default(),
+                    font_size: meta.font_size,
+                    color: meta.color,
+                    dm_type: meta.dm_type.into(),
+                    emoticon,
+                    reply_to,
+                    medal: medal.0,
+                    user_level: user_level.0,
+                    title: title.0,
+                    guard_level,
+                })
+            }
+        }
+
+        deserializer.deserialize_seq(InfoVisitor)
+    }
+}
+
+/// `info[0]`.
+struct Meta {
+    mode: u8,
+    font_size: u32,
+    color: u32,
+    timestamp: u64,
+    dm_type: u8,
+    emoticon: Option<Emoticon>,
+    extra: Option<Extra>,
+}
+
+impl<'de> Deserialize<'de> for Meta {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct MetaVisitor;
+
+        impl<'de> Visitor<'de> for MetaVisitor {
+            type Value = Meta;
+
+            fn expecting(&self, f: &mut Formatter) -> FmtResult {
+                f.write_str("danmaku meta array")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut meta = Meta {
+                    mode: DanmakuMode::Scroll.value(),
+                    font_size: 25,
+                    color: 0x00ff_ffff,
+                    timestamp: 0,
+                    dm_type: 0,
+                    emoticon: None,
+                    extra: None,
+                };
+                let mut timestamp = None;
+                let mut emoticon = Json(None);
+                let mut extra = Json(None);
+                for i in 0.. {
+                    let found = match i {
+                        1 => next_into(&mut seq, &mut meta.mode)?,
+                        2 => next_into(&mut seq, &mut meta.font_size)?,
+                        3 => next_into(&mut seq, &mut meta.color)?,
+                        4 => next_into(&mut seq, &mut timestamp)?,
+                        12 => next_into(&mut seq, &mut meta.dm_type)?,
+                        13 => next_into(&mut seq, &mut emoticon)?,
+                        15 => next_into(&mut seq, &mut extra)?,
+                        _ => seq.next_element::<IgnoredAny>()?.is_some(),
+                    };
+                    if !found {
+                        break;
+                    }
+                }
+                meta.timestamp = timestamp.ok_or_else(|| missing("[0, 4]"))?;
+                meta.emoticon = emoticon.0.filter(|emoticon: &Emoticon| {
+                    !emoticon.unique.is_empty() || !emoticon.url.is_empty()
+                });
+                meta.extra = extra.0.and_then(|ExtraHolder { extra }| extra.0);
+                Ok(meta)
+            }
+        }
+
+        deserializer.deserialize_seq(MetaVisitor)
+    }
+}
+
+/// `info[0][15]`, carrying the `extra` JSON string.
+#[derive(Deserialize)]
+struct ExtraHolder {
+    #[serde(default)]
+    extra: Json<Extra>,
+}
+
+/// Fields of the `extra` JSON string.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct Extra {
+    emoticon_unique: String,
+    reply_mid: u64,
+    reply_uname: String,
+}
+
+/// `info[2]`, from which only the user id and name are taken.
+struct User<'a>(u64, Str<'a>);
+
+impl<'de: 'a, 'a> Deserialize<'de> for User<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct UserVisitor;
+
+        impl<'de> Visitor<'de> for UserVisitor {
+            type Value = User<'de>;
+
+            fn expecting(&self, f: &mut Formatter) -> FmtResult {
+                f.write_str("danmaku user array")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let uid = element(&mut seq, "[2, 0]")?;
+                let uname = element(&mut seq, "[2, 1]")?;
+                while seq.next_element::<IgnoredAny>()?.is_some() {}
+                Ok(User(uid, uname))
+            }
+        }
+
+        deserializer.deserialize_seq(UserVisitor)
+    }
+}
+
+/// An optional positional array in `info`, decoded by [`InfoVisitor`](InfoVisitor).
+struct Info<T>(Option<T>);
+
+/// Decoder of an optional positional array in `info`. Returns `None` for empty arrays.
+trait InfoVisitor<'de>: Sized {
+    const EXPECTING: &'static str;
+
+    fn visit<A: SeqAccess<'de>>(seq: A) -> Result<Option<Self>, A::Error>;
+}
+
+impl<'de, T: InfoVisitor<'de>> Deserialize<'de> for Info<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct SeqVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: InfoVisitor<'de>> Visitor<'de> for SeqVisitor<T> {
+            type Value = Info<T>;
+
+            fn expecting(&self, f: &mut Formatter) -> FmtResult {
+                f.write_str(T::EXPECTING)
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+                T::visit(seq).map(Info)
+            }
+        }
+
+        deserializer.deserialize_seq(SeqVisitor(PhantomData))
+    }
+}
+
+/// `info[3]`.
+impl<'de> InfoVisitor<'de> for MedalRef<'de> {
+    const EXPECTING: &'static str = "danmaku medal array";
+
+    fn visit<A: SeqAccess<'de>>(mut seq: A) -> Result<Option<Self>, A::Error> {
+        let mut medal = MedalRef::default();
+        let mut name = Str(Cow::Borrowed(""));
+        let mut anchor_uname = Str(Cow::Borrowed(""));
+        let mut lighted = 0u8;
+        let mut len = 0;
+        loop {
+            let found = match len {
+                0 => next_into(&mut seq, &mut medal.level)?,
+                1 => next_into(&mut seq, &mut name)?,
+                2 => next_into(&mut seq, &mut anchor_uname)?,
+                3 => next_into(&mut seq, &mut medal.room_id)?,
+                4 => next_into(&mut seq, &mut medal.color)?,
+                10 => next_into(&mut seq, &mut medal.guard_level)?,
+                11 => next_into(&mut seq, &mut lighted)?,
+                12 => next_into(&mut seq, &mut medal.anchor_uid)?,
+                _ => seq.next_element::<IgnoredAny>()?.is_some(),
+            };
+            if !found {
+                break;
+            }
+            len += 1;
+        }
+        medal.name = name.0;
+        medal.anchor_uname = anchor_uname.0;
+        medal.is_lighted = lighted != 0;
+        Ok((len > 0).then_some(medal))
+    }
+}
+
+/// `info[4]`, from which only the user level is taken.
+impl<'de> InfoVisitor<'de> for u32 {
+    const EXPECTING: &'static str = "danmaku user level array";
+
+    fn visit<A: SeqAccess<'de>>(mut seq: A) -> Result<Option<Self>, A::Error> {
+        let level = seq.next_element()?;
+        while seq.next_element::<IgnoredAny>()?.is_some() {}
+        Ok(level)
+    }
+}
+
+/// `info[5]`, from which the non-empty one of the titles is taken.
+impl<'de> InfoVisitor<'de> for Cow<'de, str> {
+    const EXPECTING: &'static str = "danmaku title array";
+
+    fn visit<A: SeqAccess<'de>>(mut seq: A) -> Result<Option<Self>, A::Error> {
+        let mut title = None;
+        while let Some(Str(s)) = seq.next_element::<Str>()? {
+            if !s.is_empty() {
+                title = Some(s);
+            }
+        }
+        Ok(title)
+    }
+}
+
+/// A JSON object, possibly embedded as a string. `null` and empty strings are treated as missing.
+struct Json<T>(Option<T>);
+
+impl<T> Default for Json<T> {
+    fn default() -> Self {
+        Self(None)
+    }
+}
+
+impl<'de, T: DeserializeOwned> Deserialize<'de> for Json<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct JsonVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: DeserializeOwned> Visitor<'de> for JsonVisitor<T> {
+            type Value = Json<T>;
+
+            fn expecting(&self, f: &mut Formatter) -> FmtResult {
+                f.write_str("json object or string")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                if v.trim().is_empty() {
+                    return Ok(Json(None));
+                }
+                serde_json::from_str(v)
+                    .map(|v| Json(Some(v)))
+                    .map_err(E::custom)
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+                T::deserialize(MapAccessDeserializer::new(map)).map(|v| Json(Some(v)))
+            }
+
+            fn visit_unit<E: serde::de::Error>(self) -> Result<Self::Value, E> {
+                Ok(Json(None))
+            }
+
+            fn visit_none<E: serde::de::Error>(self) -> Result<Self::Value, E> {
+                Ok(Json(None))
+            }
+        }
+
+        deserializer.deserialize_any(JsonVisitor(PhantomData))
+    }
+}
+
+/// Store the next element of a positional array into `slot`, returning whether there's one.
+///
+/// `slot` is left untouched if the element is `null`.
+fn next_into<'de, T: Deserialize<'de>, A: SeqAccess<'de>>(
+    seq: &mut A,
+    slot: &mut T,
+) -> Result<bool, A::Error> {
+    Ok(match seq.next_element::<Option<T>>()? {
+        Some(Some(value)) => {
+            *slot = value;
+            true
+        }
+        Some(None) => true,
+        None => false,
+    })
+}
+
+/// Get the next required element of a positional array.
+fn element<'de, T: Deserialize<'de>, A: SeqAccess<'de>>(
+    seq: &mut A,
+    path: &str,
+) -> Result<T, A::Error> {
+    seq.next_element()?.ok_or_else(|| missing(path))
+}
+
+fn missing<E: serde::de::Error>(path: &str) -> E {
+    E::custom(format!("invalid or missing danmaku field at {}", path))
+}
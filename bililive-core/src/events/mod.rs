@@ -0,0 +1,110 @@
+//! Live event types.
+//!
+//! Notification packets carry a JSON body whose `cmd` field indicates the kind of the event.
+//! [`Event`](Event) models the common ones, and falls back to the raw JSON value for the rest.
+//!
+//! Heartbeat response packets carry the room popularity, which is modeled as
+//! [`Event::Popularity`](Event::Popularity).
+//!
+//! [`EventRef`](EventRef) is the borrowed counterpart of [`Event`](Event) for high-throughput
+//! consumers. It borrows strings from the packet body, and can skip unwanted notifications by
+//! their `cmd` before decoding them.
+
+use serde::de::{DeserializeOwned, Error as _};
+use serde::Deserialize;
+use serde_json::Value;
+
+pub use borrowed::*;
+pub use cmd::*;
+pub use danmaku::*;
+pub use types::*;
+
+use crate::errors::ParseError;
+
+mod borrowed;
+mod cmd;
+mod danmaku;
+mod types;
+
+#[cfg(test)]
+mod tests;
+
+type Result<T, E = ParseError> = std::result::Result<T, E>;
+
+/// Live event carried by a notification or heartbeat response packet.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Event {
+    /// Room popularity carried by heartbeat responses.
+    Popularity(Popularity),
+    /// `DANMU_MSG`
+    Danmaku(Danmaku),
+    /// `SEND_GIFT`
+    Gift(Gift),
+    /// `GUARD_BUY`
+    GuardBuy(GuardBuy),
+    /// `SUPER_CHAT_MESSAGE`
+    SuperChat(SuperChat),
+    /// `INTERACT_WORD`
+    InteractWord(InteractWord),
+    /// `ROOM_CHANGE`
+    RoomChange(RoomChange),
+    /// `LIVE`
+    Live(Live),
+    /// `PREPARING`
+    Preparing(Preparing),
+    /// `ONLINE_RANK_COUNT`
+    OnlineRankCount(OnlineRankCount),
+    /// `WATCHED_CHANGE`
+    WatchedChange(WatchedChange),
+    /// `LIKE_INFO_V3_UPDATE`
+    LikeInfoUpdate(LikeInfoUpdate),
+    /// `LIKE_INFO_V3_CLICK`
+    LikeClick(LikeClick),
+    /// Any other notification. Contains the whole JSON body.
+    Unknown(Value),
+}
+
+impl Event {
+    /// Parse an event from the JSON body of a notification packet.
+    ///
+    /// # Errors
+    /// Returns an error if the `cmd` is known but the body doesn't match its model.
+    pub fn from_value(mut value: Value) -> Result<Self> {
+        let kind = match value.get("cmd").and_then(Value::as_str) {
+            Some(cmd) => Cmd::parse(cmd).kind(),
+            None => return Ok(Self::Unknown(value)),
+        };
+
+        Ok(match kind {
+            CmdKind::Danmaku => Self::Danmaku(DanmakuRef::deserialize(info(&value)?)?.into_owned()),
+            CmdKind::Gift => Self::Gift(data(&mut value)?),
+            CmdKind::GuardBuy => Self::GuardBuy(data(&mut value)?),
+            CmdKind::SuperChat => Self::SuperChat(data(&mut value)?),
+            CmdKind::InteractWord => Self::InteractWord(data(&mut value)?),
+            CmdKind::RoomChange => Self::RoomChange(data(&mut value)?),
+            CmdKind::Live => Self::Live(serde_json::from_value(value)?),
+            CmdKind::Preparing => Self::Preparing(serde_json::from_value(value)?),
+            CmdKind::OnlineRankCount => Self::OnlineRankCount(data(&mut value)?),
+            CmdKind::WatchedChange => Self::WatchedChange(data(&mut value)?),
+            CmdKind::LikeInfoUpdate => Self::LikeInfoUpdate(data(&mut value)?),
+            CmdKind::LikeClick => Self::LikeClick(data(&mut value)?),
+            CmdKind::Other => Self::Unknown(value),
+        })
+    }
+}
+
+/// Take the `data` field out of the body and deserialize it.
+fn data<T: DeserializeOwned>(value: &mut Value) -> Result<T> {
+    let data = value
+        .get_mut("data")
+        .map(Value::take)
+        .ok_or_else(|| serde_json::Error::missing_field("data"))?;
+    Ok(serde_json::from_value(data)?)
+}
+
+/// Get the positional `info` array of the body.
+fn info(value: &Value) -> Result<&Value> {
+    Ok(value
+        .get("info")
+        .ok_or_else(|| serde_json::Error::missing_field("info"))?)
+}
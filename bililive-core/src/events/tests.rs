@@ -0,0 +1,464 @@
+use std::borrow::Cow;
+use std::fs::read;
+
+use serde_json::{json, Value};
+
+use crate::errors::{IncompleteResult, ParseError};
+use crate::packet::{Operation, Packet, Protocol};
+
+use super::*;
+
+/// Parse a notification fixture in `tests/events`.
+///
+/// The `danmu_msg*` fixtures are hand-written after the layout expected by the decoder, not
+/// captured from the server. They test the decoding, but don't verify the layout itself.
+fn parse_fixture(name: &str) -> Event {
+    let content = read(format!("tests/events/{}.json", name)).unwrap();
+    Packet::new(Operation::Notification, Protocol::Json, content)
+        .event()
+        .expect("unable to parse event")
+}
+
+fn parse_packet(path: &str) -> Vec<Event> {
+    let content = read(path).unwrap();
+    if let IncompleteResult::Ok((_, packets)) = Packet::parse_all(&content) {
+        packets
+            .iter()
+            .map(|packet| packet.event().expect("unable to parse event"))
+            .collect()
+    } else {
+        panic!("error while parsing");
+    }
+}
+
+#[test]
+fn must_parse_popularity() {
+    let events = parse_packet("tests/raw/int32be.packet");
+    assert_eq!(events, [Event::Popularity(Popularity(358_069))]);
+}
+
+#[test]
+fn must_parse_danmaku() {
+    let events = parse_packet("tests/raw/zlib_batch.packet");
+    match &events[0] {
+        Event::Danmaku(danmaku) => {
+            assert_eq!(danmaku.uid, 174_102_117);
+            assert_eq!(danmaku.uname, "user-a");
+            assert_eq!(danmaku.text, "first");
+            assert_eq!(danmaku.timestamp, 1_626_324_625_000);
+            assert_eq!(danmaku.medal, None);
+            assert_eq!(danmaku.user_level, Some(0));
+            assert_eq!(danmaku.title, None);
+        }
+        e => panic!("unexpected event: {:?}", e),
+    }
+    assert!(matches!(&events[1], Event::Danmaku(d) if d.text == "second"));
+    assert_eq!(
+        events[2],
+        Event::OnlineRankCount(OnlineRankCount {
+            count: 42,
+            online_count: None
+        })
+    );
+}
+
+#[test]
+fn must_parse_danmaku_details() {
+    let danmaku = match parse_fixture("danmu_msg") {
+        Event::Danmaku(danmaku) => danmaku,
+        e => panic!("unexpected event: {:?}", e),
+    };
+    assert_eq!(danmaku.text, "@viewer-2 晚上好");
+    assert_eq!(danmaku.mode, DanmakuMode::Scroll);
+    assert_eq!(danmaku.font_size, 25);
+    assert_eq!(danmaku.color, 0x00e3_3fff);
+    assert_eq!(danmaku.timestamp, 1_689_324_123_456);
+    assert_eq!(danmaku.dm_type, DanmakuType::Text);
+    assert_eq!(danmaku.emoticon, None);
+    assert_eq!(
+        danmaku.reply_to,
+        Some(ReplyTo {
+            uid: 8_123_456,
+            uname: String::from("viewer-2")
+        })
+    );
+    assert_eq!(
+        danmaku.medal,
+        Some(Medal {
+            level: 21,
+            name: String::from("小花花"),
+            anchor_uname: String::from("anchor-1"),
+            anchor_uid: 15_474_562,
+            room_id: 21_452_505,
+            color: 1_725_515,
+            guard_level: 3,
+            is_lighted: true,
+        })
+    );
+    assert_eq!(danmaku.user_level, Some(31));
+    assert_eq!(danmaku.title.as_deref(), Some("title-111-1"));
+    assert_eq!(danmaku.guard_level, 3);
+}
+
+#[test]
+fn must_parse_emoticon_danmaku() {
+    let danmaku = match parse_fixture("danmu_msg_emoticon") {
+        Event::Danmaku(danmaku) => danmaku,
+        e => panic!("unexpected event: {:?}", e),
+    };
+    assert_eq!(danmaku.dm_type, DanmakuType::Emoticon);
+    let emoticon = danmaku.emoticon.unwrap();
+    assert_eq!(emoticon.unique, "official_147");
+    assert!(emoticon.url.ends_with(".png"));
+    assert_eq!((emoticon.width, emoticon.height), (183, 60));
+    assert_eq!(danmaku.reply_to, None);
+    assert_eq!(danmaku.medal, None);
+}
+
+#[test]
+fn must_parse_legacy_danmaku() {
+    let danmaku = match parse_fixture("danmu_msg_legacy") {
+        Event::Danmaku(danmaku) => danmaku,
+        e => panic!("unexpected event: {:?}", e),
+    };
+    assert_eq!(danmaku.uname, "viewer-3");
+    assert_eq!(danmaku.mode, DanmakuMode::Top);
+    assert_eq!(danmaku.font_size, 18);
+    assert_eq!(danmaku.dm_type, DanmakuType::Text);
+    assert_eq!(danmaku.emoticon, None);
+    assert_eq!(danmaku.reply_to, None);
+    let medal = danmaku.medal.unwrap();
+    assert_eq!((medal.level, medal.name.as_str()), (8, "旧牌子"));
+    assert_eq!(medal.anchor_uid, 0);
+    assert!(!medal.is_lighted);
+    assert_eq!(danmaku.user_level, None);
+    assert_eq!(danmaku.title, None);
+    assert_eq!(danmaku.guard_level, 0);
+}
+
+#[test]
+fn must_tolerate_null_danmaku_fields() {
+    let value = json!({
+        "cmd": "DANMU_MSG",
+        "info": [[0, 9, 25, 0, 42, 0, 0, "", 0, 0, 0, "", null, null, null, null], "text", [1, "user"], null, null, null, 0, null]
+    });
+    match Event::from_value(value).unwrap() {
+        Event::Danmaku(danmaku) => {
+            assert_eq!(danmaku.mode, DanmakuMode::Scroll);
+            assert_eq!(danmaku.dm_type, DanmakuType::Text);
+            assert_eq!(danmaku.medal, None);
+            assert_eq!(danmaku.guard_level, 0);
+        }
+        e => panic!("unexpected event: {:?}", e),
+    }
+}
+
+#[test]
+fn must_parse_interact_word() {
+    let events = parse_packet("tests/raw/buffer.packet");
+    assert_eq!(
+        events[0],
+        Event::InteractWord(InteractWord {
+            uid: 174_102_117,
+            uname: String::from("vioIet・伊芙加登"),
+            kind: InteractKind::Enter,
+            roomid: 23_090_051,
+            timestamp: 1_626_324_624,
+        })
+    );
+}
+
+#[test]
+fn must_parse_gift() {
+    match parse_fixture("send_gift") {
+        Event::Gift(gift) => {
+            assert_eq!(gift.uid, 8_123_456);
+            assert_eq!(gift.gift_id, 31036);
+            assert_eq!(gift.gift_name, "小花花");
+            assert_eq!(gift.num, 1);
+            assert_eq!(gift.coin_type, "gold");
+            assert_eq!(gift.total_coin, 100);
+        }
+        e => panic!("unexpected event: {:?}", e),
+    }
+}
+
+#[test]
+fn must_parse_guard_buy() {
+    match parse_fixture("guard_buy") {
+        Event::GuardBuy(guard) => {
+            assert_eq!(guard.username, "某位观众");
+            assert_eq!(guard.guard_level, 3);
+            assert_eq!(guard.price, 198_000);
+        }
+        e => panic!("unexpected event: {:?}", e),
+    }
+}
+
+#[test]
+fn must_parse_super_chat() {
+    match parse_fixture("super_chat_message") {
+        Event::SuperChat(sc) => {
+            assert_eq!(sc.id, 7_654_321);
+            assert_eq!(sc.price, 30);
+            assert_eq!(sc.message, "晚上好！");
+            assert_eq!(sc.user_info.uname, "某位观众");
+            assert_eq!(sc.time, 60);
+        }
+        e => panic!("unexpected event: {:?}", e),
+    }
+}
+
+#[test]
+fn must_parse_room_events() {
+    assert!(matches!(
+        parse_fixture("room_change"),
+        Event::RoomChange(RoomChange {
+            area_id: 371,
+            parent_area_id: 9,
+            ..
+        })
+    ));
+    assert_eq!(
+        parse_fixture("live"),
+        Event::Live(Live {
+            roomid: 21_452_505,
+            live_time: Some(1_689_324_000)
+        })
+    );
+    assert_eq!(
+        parse_fixture("preparing"),
+        Event::Preparing(Preparing { roomid: 21_452_505 })
+    );
+}
+
+#[test]
+fn must_parse_counters() {
+    assert_eq!(
+        parse_fixture("online_rank_count"),
+        Event::OnlineRankCount(OnlineRankCount {
+            count: 1207,
+            online_count: Some(3045)
+        })
+    );
+    assert!(matches!(
+        parse_fixture("watched_change"),
+        Event::WatchedChange(WatchedChange { num: 12345, .. })
+    ));
+    assert_eq!(
+        parse_fixture("like_info_v3_update"),
+        Event::LikeInfoUpdate(LikeInfoUpdate { click_count: 5678 })
+    );
+    assert!(matches!(
+        parse_fixture("like_info_v3_click"),
+        Event::LikeClick(LikeClick { uid: 8_123_456, .. })
+    ));
+}
+
+#[test]
+fn must_fallback_unknown() {
+    let value = json!({"cmd": "STOP_LIVE_ROOM_LIST", "data": {"room_id_list": [1, 2]}});
+    assert_eq!(
+        Event::from_value(value.clone()).unwrap(),
+        Event::Unknown(value)
+    );
+}
+
+#[test]
+fn must_reject_malformed() {
+    assert!(matches!(
+        Event::from_value(json!({"cmd": "DANMU_MSG:4:0:2:2:2:0", "info": [[], "text"]})),
+        Err(ParseError::Json(_))
+    ));
+    assert!(matches!(
+        Packet::new(Operation::RoomEnterResponse, Protocol::Json, "{}").event(),
+        Err(ParseError::NoEvent)
+    ));
+    assert!(matches!(
+        Packet::new(Operation::HeartBeatResponse, Protocol::Int32BE, vec![0, 1]).event(),
+        Err(ParseError::Int32BE)
+    ));
+    assert!(matches!(
+        Event::from_value(Value::Null),
+        Ok(Event::Unknown(Value::Null))
+    ));
+}
+
+#[test]
+fn must_parse_borrowed_like_owned() {
+    let fixtures = [
+        "danmu_msg",
+        "danmu_msg_emoticon",
+        "danmu_msg_legacy",
+        "send_gift",
+        "guard_buy",
+        "super_chat_message",
+        "room_change",
+        "live",
+        "preparing",
+        "online_rank_count",
+        "watched_change",
+        "like_info_v3_update",
+        "like_info_v3_click",
+    ];
+    let mut packets: Vec<_> = fixtures
+        .iter()
+        .map(|name| {
+            let content = read(format!("tests/events/{}.json", name)).unwrap();
+            Packet::new(Operation::Notification, Protocol::Json, content)
+        })
+        .collect();
+    for path in [
+        "tests/raw/int32be.packet",
+        "tests/raw/zlib_batch.packet",
+        "tests/raw/buffer.packet",
+    ] {
+        if let IncompleteResult::Ok((_, parsed)) = Packet::parse_all(&read(path).unwrap()) {
+            packets.extend(parsed);
+        }
+    }
+    packets.push(Packet::new(
+        Operation::Notification,
+        Protocol::Json,
+        r#"{"cmd": "STOP_LIVE_ROOM_LIST", "data": {"room_id_list": [1, 2]}}"#,
+    ));
+
+    for packet in packets {
+        let event = packet.event_ref().unwrap().into_owned().unwrap();
+        assert_eq!(event, packet.event().unwrap());
+    }
+}
+
+#[test]
+fn must_borrow_strings() {
+    let content = read("tests/raw/zlib_batch.packet").unwrap();
+    let packets = match Packet::parse_all(&content) {
+        IncompleteResult::Ok((_, packets)) => packets,
+        _ => panic!("error while parsing"),
+    };
+    match packets[0].event_ref().unwrap() {
+        EventRef::Danmaku(danmaku) => {
+            assert!(matches!(danmaku.text, Cow::Borrowed("first")));
+            assert!(matches!(danmaku.uname, Cow::Borrowed("user-a")));
+        }
+        e => panic!("unexpected event: {:?}", e),
+    }
+
+    let content = read("tests/events/danmu_msg.json").unwrap();
+    match EventRef::from_slice(&content).unwrap() {
+        EventRef::Danmaku(danmaku) => {
+            let medal = danmaku.medal.unwrap();
+            assert!(matches!(medal.name, Cow::Borrowed("小花花")));
+            assert!(matches!(danmaku.title, Some(Cow::Borrowed("title-111-1"))));
+        }
+        e => panic!("unexpected event: {:?}", e),
+    }
+
+    // escaped strings can't be borrowed
+    let body = br#"{"cmd": "DANMU_MSG", "info": [[0, 1, 25, 0, 42], "a\"b", [1, "user"]]}"#;
+    match EventRef::from_slice(body).unwrap() {
+        EventRef::Danmaku(danmaku) => {
+            assert!(matches!(danmaku.text, Cow::Owned(ref text) if text == "a\"b"));
+            assert_eq!(danmaku.timestamp, 42);
+        }
+        e => panic!("unexpected event: {:?}", e),
+    }
+}
+
+#[test]
+fn must_filter_by_cmd() {
+    let danmaku = Packet::new(
+        Operation::Notification,
+        Protocol::Json,
+        r#"{"cmd": "DANMU_MSG:4:0:2:2:2:0", "info": [[0, 1, 25, 0, 42], "text", [1, "user"]]}"#,
+    );
+    let is_danmaku = |cmd: &Cmd| cmd.kind() == CmdKind::Danmaku;
+    assert!(matches!(
+        danmaku.event_ref_if(is_danmaku),
+        Ok(Some(EventRef::Danmaku(_)))
+    ));
+    assert!(matches!(danmaku.event_ref_if(|_| false), Ok(None)));
+
+    // rejected payloads are not decoded
+    let malformed = Packet::new(
+        Operation::Notification,
+        Protocol::Json,
+        r#"{"cmd": "SEND_GIFT", "data": {"uid": "not a number"}}"#,
+    );
+    assert!(matches!(malformed.event_ref_if(is_danmaku), Ok(None)));
+    assert!(matches!(malformed.event_ref(), Err(ParseError::Json(_))));
+
+    // heartbeat responses are not filtered
+    let heartbeat = Packet::new(
+        Operation::HeartBeatResponse,
+        Protocol::Int32BE,
+        1u32.to_be_bytes(),
+    );
+    assert!(matches!(
+        heartbeat.event_ref_if(|_| false),
+        Ok(Some(EventRef::Popularity(Popularity(1))))
+    ));
+}
+
+#[test]
+fn must_reject_malformed_borrowed() {
+    assert!(matches!(
+        EventRef::from_slice(br#"{"cmd": "DANMU_MSG", "info": [[], "text"]}"#),
+        Err(ParseError::Json(_))
+    ));
+    assert!(matches!(
+        EventRef::from_slice(b"null"),
+        Err(ParseError::Json(_))
+    ));
+    assert!(matches!(
+        EventRef::from_slice(br#"{"info": []}"#),
+        Ok(EventRef::Unknown(r#"{"info": []}"#))
+    ));
+}
+
+#[test]
+fn must_parse_cmd() {
+    let cmd = Cmd::parse("DANMU_MSG:4:0:2:2:2:0");
+    assert_eq!(cmd.name(), "DANMU_MSG");
+    assert_eq!(cmd.kind(), CmdKind::Danmaku);
+    assert_eq!(
+        cmd.params().collect::<Vec<_>>(),
+        ["4", "0", "2", "2", "2", "0"]
+    );
+    assert!(cmd.is("DANMU_MSG"));
+    assert_eq!(cmd.to_string(), "DANMU_MSG:4:0:2:2:2:0");
+
+    let cmd = Cmd::parse(String::from("SEND_GIFT")).into_owned();
+    assert_eq!(cmd.kind(), CmdKind::Gift);
+    assert_eq!(cmd.params().count(), 0);
+
+    let cmd = Cmd::parse("DANMU_MSG_MIRROR");
+    assert_eq!(cmd.kind(), CmdKind::Other);
+    assert!(!cmd.is("DANMU_MSG"));
+
+    for kind in [CmdKind::Danmaku, CmdKind::LikeClick, CmdKind::Other] {
+        assert_eq!(kind.name().map_or(CmdKind::Other, CmdKind::from_name), kind);
+    }
+}
+
+#[test]
+fn must_peek_packet_cmd() {
+    let packet = Packet::new(
+        Operation::Notification,
+        Protocol::Json,
+        r#"{"data": {"cmd": "nested"}, "cmd": "LIKE_INFO_V3_CLICK"}"#,
+    );
+    let cmd = packet.cmd().unwrap();
+    assert_eq!(cmd.kind(), CmdKind::LikeClick);
+
+    assert!(Packet::new(Operation::Notification, Protocol::Json, "{}")
+        .cmd()
+        .is_none());
+    assert!(Packet::new(
+        Operation::HeartBeatResponse,
+        Protocol::Int32BE,
+        1u32.to_be_bytes()
+    )
+    .cmd()
+    .is_none());
+}
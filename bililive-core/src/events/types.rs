@@ -0,0 +1,221 @@
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer};
+
+/// Room popularity, carried by heartbeat responses.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Popularity(pub u32);
+
+/// A gift sent to the streamer.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+pub struct Gift {
+    /// Sender user id.
+    pub uid: u64,
+    /// Sender user name.
+    pub uname: String,
+    /// Gift id.
+    #[serde(rename = "giftId")]
+    pub gift_id: u64,
+    /// Gift name.
+    #[serde(rename = "giftName")]
+    pub gift_name: String,
+    /// Count of gifts sent.
+    pub num: u32,
+    /// Price of a single gift. 1000 gold coins equal to 1 CNY.
+    pub price: u64,
+    /// `gold` or `silver`.
+    pub coin_type: String,
+    /// Total price of the gifts.
+    pub total_coin: u64,
+    /// Action text, e.g. `投喂`.
+    pub action: String,
+    /// Send time in seconds since unix epoch.
+    pub timestamp: u64,
+}
+
+/// A guard (舰长/提督/总督) purchase.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+pub struct GuardBuy {
+    /// Buyer user id.
+    pub uid: u64,
+    /// Buyer user name.
+    pub username: String,
+    /// Guard level. 1 for 总督, 2 for 提督 and 3 for 舰长.
+    pub guard_level: u8,
+    /// Count of months purchased.
+    pub num: u32,
+    /// Price in gold coins.
+    pub price: u64,
+    /// Gift id.
+    pub gift_id: u64,
+    /// Gift name.
+    pub gift_name: String,
+    /// Start time in seconds since unix epoch.
+    pub start_time: u64,
+    /// End time in seconds since unix epoch.
+    pub end_time: u64,
+}
+
+/// A super chat (paid pinned message).
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+pub struct SuperChat {
+    /// Super chat id.
+    pub id: u64,
+    /// Sender user id.
+    pub uid: u64,
+    /// Price in CNY.
+    pub price: u64,
+    /// Message text.
+    pub message: String,
+    /// Sender information.
+    pub user_info: SuperChatUser,
+    /// Start time in seconds since unix epoch.
+    pub start_time: u64,
+    /// End time in seconds since unix epoch.
+    pub end_time: u64,
+    /// Duration in seconds for which the message is pinned.
+    pub time: u64,
+}
+
+/// Sender information of a super chat.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+pub struct SuperChatUser {
+    /// Sender user name.
+    pub uname: String,
+    /// Sender avatar url.
+    pub face: String,
+}
+
+/// A user interaction with the room, e.g. entering or following.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+pub struct InteractWord {
+    /// User id.
+    pub uid: u64,
+    /// User name.
+    pub uname: String,
+    /// Interaction type.
+    #[serde(rename = "msg_type")]
+    pub kind: InteractKind,
+    /// Room id (long version).
+    pub roomid: u64,
+    /// Interaction time in seconds since unix epoch.
+    pub timestamp: u64,
+}
+
+/// Interaction types.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Deserialize)]
+#[serde(from = "u8")]
+pub enum InteractKind {
+    /// Entered the room. `msg_type` 1.
+    Enter,
+    /// Followed the streamer. `msg_type` 2.
+    Follow,
+    /// Shared the room. `msg_type` 3.
+    Share,
+    /// Specially followed the streamer. `msg_type` 4.
+    SpecialFollow,
+    /// Followed the streamer mutually. `msg_type` 5.
+    MutualFollow,
+    /// An interaction type not recognized by this crate, with the raw `msg_type` value.
+    Unknown(u8),
+}
+
+impl From<u8> for InteractKind {
+    fn from(i: u8) -> Self {
+        match i {
+            1 => Self::Enter,
+            2 => Self::Follow,
+            3 => Self::Share,
+            4 => Self::SpecialFollow,
+            5 => Self::MutualFollow,
+            _ => Self::Unknown(i),
+        }
+    }
+}
+
+/// Room information change, e.g. title or area.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+pub struct RoomChange {
+    /// Room title.
+    pub title: String,
+    /// Area id.
+    pub area_id: u64,
+    /// Parent area id.
+    pub parent_area_id: u64,
+    /// Area name.
+    pub area_name: String,
+    /// Parent area name.
+    pub parent_area_name: String,
+}
+
+/// The stream goes live.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+pub struct Live {
+    /// Room id (long version).
+    #[serde(deserialize_with = "u64_or_string")]
+    pub roomid: u64,
+    /// Start time in seconds since unix epoch, if provided.
+    #[serde(default)]
+    pub live_time: Option<u64>,
+}
+
+/// The stream goes offline.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+pub struct Preparing {
+    /// Room id (long version).
+    #[serde(deserialize_with = "u64_or_string")]
+    pub roomid: u64,
+}
+
+/// Count of users on the online rank.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+pub struct OnlineRankCount {
+    /// Count of users on the rank.
+    pub count: u64,
+    /// Count of online users, if provided.
+    #[serde(default)]
+    pub online_count: Option<u64>,
+}
+
+/// Count of users who have watched the stream.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+pub struct WatchedChange {
+    /// Count of users.
+    pub num: u64,
+    /// Short text shown on the client, e.g. `1.2万`.
+    pub text_small: String,
+    /// Long text shown on the client, e.g. `1.2万人看过`.
+    pub text_large: String,
+}
+
+/// Total like count update.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+pub struct LikeInfoUpdate {
+    /// Total count of likes.
+    pub click_count: u64,
+}
+
+/// A user liked the stream.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+pub struct LikeClick {
+    /// User id.
+    pub uid: u64,
+    /// User name.
+    pub uname: String,
+    /// Like text, e.g. `为主播点赞了`.
+    pub like_text: String,
+}
+
+/// Room ids are sent as either numbers or strings.
+fn u64_or_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        Int(u64),
+        Str(String),
+    }
+
+    match Repr::deserialize(deserializer)? {
+        Repr::Int(i) => Ok(i),
+        Repr::Str(s) => s.parse().map_err(D::Error::custom),
+    }
+}
@@ -18,8 +18,13 @@
 )]
 
 pub mod builder;
+pub mod capture;
 pub mod config;
 pub mod errors;
+pub mod events;
 pub mod packet;
 pub mod retry;
+pub mod room;
 pub mod stream;
+#[cfg(all(test, any(feature = "tokio", feature = "async-std")))]
+mod test_utils;
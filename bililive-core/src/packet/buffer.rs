@@ -0,0 +1,64 @@
+use std::collections::VecDeque;
+
+use bytes::{Bytes, BytesMut};
+use log::debug;
+
+use crate::errors::ParseError;
+
+use super::Packet;
+
+/// Buffer reassembling packets from binary websocket frames.
+///
+/// A frame may carry multiple packets, and a packet may be split across frames. Complete packets
+/// are sliced out of the frames without copying, and only an incomplete packet at the end of a
+/// frame is kept until the rest of it arrives.
+#[derive(Debug, Default)]
+pub struct PacketBuffer {
+    /// incomplete packet at the end of previous frames
+    partial: Bytes,
+    /// packets parsed but not yet taken
+    pending: VecDeque<Packet>,
+}
+
+impl PacketBuffer {
+    /// Construct an empty buffer.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            partial: Bytes::new(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Parse all complete packets in a binary frame. Compressed packets are unpacked.
+    ///
+    /// # Errors
+    /// Returns an error if a packet is malformed, in which case the rest of the frame is dropped.
+    /// Packets parsed before the malformed one are kept.
+    pub fn feed(&mut self, frame: Bytes) -> Result<(), ParseError> {
+        let mut buf = if self.partial.is_empty() {
+            frame
+        } else {
+            let mut buf = BytesMut::with_capacity(self.partial.len() + frame.len());
+            buf.extend_from_slice(&self.partial);
+            buf.extend_from_slice(&frame);
+            buf.freeze()
+        };
+        self.partial = Bytes::new();
+
+        while let Some(packs) = Packet::parse_bytes(&mut buf)? {
+            debug!("{} packet(s) parsed", packs.len());
+            self.pending.extend(packs);
+        }
+        if !buf.is_empty() {
+            debug!("incomplete packet, {} bytes buffered", buf.len());
+        }
+        self.partial = buf;
+        Ok(())
+    }
+
+    /// Take the next parsed packet.
+    pub fn pop(&mut self) -> Option<Packet> {
+        self.pending.pop_front()
+    }
+}
@@ -1,8 +1,10 @@
 //! Packet types.
 
 use std::convert::TryInto;
-use std::io::{Cursor, Read, Write};
+use std::io::{Read, Write};
 
+use brotli::{CompressorWriter, Decompressor};
+use bytes::{Bytes, BytesMut};
 use flate2::read::ZlibDecoder;
 use flate2::write::ZlibEncoder;
 use flate2::Compression;
@@ -10,11 +12,14 @@ use nom::Err;
 use serde::Deserialize;
 use serde_json::json;
 
+pub use buffer::PacketBuffer;
 pub use types::*;
 
-use crate::config::StreamConfig;
+use crate::config::{self, StreamConfig};
 use crate::errors::{IncompleteResult, ParseError};
+use crate::events::{peek_cmd, Cmd, Event, EventRef, Popularity};
 
+mod buffer;
 mod parser;
 mod types;
 
@@ -23,9 +28,16 @@ mod tests;
 
 type Result<T, E = ParseError> = std::result::Result<T, E>;
 
+const BROTLI_BUFFER_SIZE: usize = 4096;
+const BROTLI_QUALITY: u32 = 5;
+const BROTLI_LG_WINDOW_SIZE: u32 = 22;
+
 /// Bililive packet.
 ///
 /// Packet can be used to encode/parse raw bilibili live packets, and extract information from it.
+///
+/// The body is held in a [`Bytes`](Bytes), so packets parsed from a buffer by
+/// [`Packet::parse_buf`](Packet::parse_buf) share its memory, and cloning a packet is cheap.
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct Packet {
     packet_length: u32,
@@ -33,7 +45,7 @@ pub struct Packet {
     protocol_version: Protocol,
     op: Operation,
     seq_id: u32,
-    data: Vec<u8>,
+    data: Bytes,
 }
 
 impl Packet {
@@ -52,7 +64,7 @@ impl Packet {
     /// Set the packet body.
     /// Packet length will be updated automatically.
     pub fn set_data<T: Into<Vec<u8>>>(&mut self, data: T) {
-        self.data = data.into();
+        self.data = data.into().into();
         self.packet_length = self.header_length as u32 + self.data.len() as u32;
     }
 }
@@ -60,11 +72,16 @@ impl Packet {
 impl Packet {
     /// Construct a new packet.
     ///
-    /// To construct a zlib-compressed packet, you should create a JSON/Int32BE packet first,
-    /// then call [`Packet::compress`](Packet::compress) to convert it to a zlib one.
+    /// To construct a compressed packet, you should create a JSON/Int32BE packet first,
+    /// then call [`Packet::compress`](Packet::compress) or [`Packet::compress_brotli`](Packet::compress_brotli)
+    /// to convert it to a zlib/brotli one.
     pub fn new<T: Into<Vec<u8>>>(op: Operation, protocol_version: Protocol, data: T) -> Self {
-        let data = data.into();
+        Self::new_with_bytes(op, protocol_version, data.into().into())
+    }
 
+    /// Construct a new packet with given body without copying it.
+    #[must_use]
+    pub fn new_with_bytes(op: Operation, protocol_version: Protocol, data: Bytes) -> Self {
         Self {
             packet_length: data.len() as u32 + 16,
             header_length: 16,
@@ -80,33 +97,73 @@ impl Packet {
     /// # Errors
     /// Return errors if compression fails.
     pub fn compress(self) -> Result<Self> {
-        let raw = self.encode();
+        Self::compress_batch(&[self], config::Compression::Zlib)
+    }
 
-        let mut z = ZlibEncoder::new(Vec::new(), Compression::default());
-        z.write_all(&raw)?;
-        let data = z.finish()?;
+    /// Convert a JSON/Int32BE packet to a brotli-compressed one.
+    ///
+    /// # Errors
+    /// Return errors if compression fails.
+    pub fn compress_brotli(self) -> Result<Self> {
+        Self::compress_batch(&[self], config::Compression::Brotli)
+    }
+
+    /// Compress a batch of JSON/Int32BE packets into one, as the server does.
+    ///
+    /// The operation of the first packet is used, or `Notification` if the batch is empty.
+    ///
+    /// # Errors
+    /// Return errors if compression fails.
+    pub fn compress_batch(packs: &[Self], compression: config::Compression) -> Result<Self> {
+        let op = packs
+            .first()
+            .map_or(Operation::Notification, |pack| pack.op);
+        let raw: Vec<u8> = packs.iter().flat_map(Self::encode).collect();
 
-        Ok(Self::new(self.op, Protocol::Zlib, data))
+        Ok(match compression {
+            config::Compression::Zlib => {
+                let mut z = ZlibEncoder::new(Vec::new(), Compression::default());
+                z.write_all(&raw)?;
+                Self::new(op, Protocol::Zlib, z.finish()?)
+            }
+            config::Compression::Brotli => {
+                let mut w = CompressorWriter::new(
+                    Vec::new(),
+                    BROTLI_BUFFER_SIZE,
+                    BROTLI_QUALITY,
+                    BROTLI_LG_WINDOW_SIZE,
+                );
+                w.write_all(&raw).map_err(ParseError::BrotliError)?;
+                w.flush().map_err(ParseError::BrotliError)?;
+                Self::new(op, Protocol::Brotli, w.into_inner())
+            }
+        })
     }
 }
 
 impl Packet {
+    /// Construct a room enter packet.
+    ///
+    /// If a credential is set in the config, its `buvid3` is sent too.
     #[allow(clippy::missing_panics_doc)]
     #[must_use]
     pub fn new_room_enter(config: &StreamConfig) -> Self {
+        let mut payload = json!({
+            "uid": config.uid(),
+            "roomid": config.room_id(),
+            "protover": config.compression().protover(),
+            "platform": "web",
+            "clientver": "1.8.2",
+            "type": 2,
+            "key": config.token()
+        });
+        if let Some(credential) = config.credential() {
+            payload["buvid"] = json!(credential.buvid3());
+        }
         Self::new(
             Operation::RoomEnter,
             Protocol::Json,
-            serde_json::to_vec(&json!({
-                "uid": config.uid(),
-                "roomid": config.room_id(),
-                "protover": 2,
-                "platform": "web",
-                "clientver": "1.8.2",
-                "type": 2,
-                "key": config.token()
-            }))
-            .unwrap(),
+            serde_json::to_vec(&payload).unwrap(),
         )
     }
 }
@@ -142,6 +199,11 @@ impl Packet {
     pub fn bytes(&self) -> &[u8] {
         &self.data
     }
+    /// Get the body. It's a cheap reference-counted clone.
+    #[must_use]
+    pub fn body(&self) -> Bytes {
+        self.data.clone()
+    }
     /// Try to parse the body by json.
     ///
     /// # Errors
@@ -158,11 +220,68 @@ impl Packet {
     pub fn int32_be(&self) -> Result<i32> {
         Ok(i32::from_be_bytes(
             self.data
-                .as_slice()
+                .as_ref()
                 .try_into()
                 .map_err(|_| ParseError::Int32BE)?,
         ))
     }
+    /// Try to parse the body as a live event.
+    ///
+    /// Heartbeat responses are parsed as [`Event::Popularity`](Event::Popularity).
+    ///
+    /// # Errors
+    /// It may fail if it's neither a notification nor a heartbeat response packet, or the body
+    /// doesn't match the event model.
+    /// Notifications with unknown `cmd`s are returned as [`Event::Unknown`](Event::Unknown).
+    pub fn event(&self) -> Result<Event> {
+        match self.op {
+            Operation::Notification => Event::from_value(self.json()?),
+            Operation::HeartBeatResponse => Ok(Event::Popularity(self.popularity()?)),
+            _ => Err(ParseError::NoEvent),
+        }
+    }
+    /// Get the `cmd` of a notification without decoding the rest of the body.
+    ///
+    /// Returns `None` if it's not a notification, or the body has no `cmd`.
+    #[must_use]
+    pub fn cmd(&self) -> Option<Cmd<'_>> {
+        if self.op == Operation::Notification {
+            peek_cmd(&self.data)
+        } else {
+            None
+        }
+    }
+    /// Try to parse the body as a live event borrowing from the packet.
+    ///
+    /// # Errors
+    /// Same as [`Packet::event`](Packet::event).
+    pub fn event_ref(&self) -> Result<EventRef<'_>> {
+        self.event_ref_if(|_| true)?.ok_or(ParseError::NoEvent)
+    }
+    /// Try to parse the body as a live event borrowing from the packet, if it's a heartbeat
+    /// response or its `cmd` is accepted by `filter`.
+    ///
+    /// Notifications rejected by `filter` are discarded without allocation, which suits consumers
+    /// only interested in a few kinds of events. See
+    /// [`EventRef::from_slice_if`](EventRef::from_slice_if) for details.
+    ///
+    /// # Errors
+    /// Same as [`Packet::event`](Packet::event).
+    pub fn event_ref_if(&self, filter: impl FnOnce(&Cmd) -> bool) -> Result<Option<EventRef<'_>>> {
+        match self.op {
+            Operation::Notification => EventRef::from_slice_if(&self.data, filter),
+            Operation::HeartBeatResponse => Ok(Some(EventRef::Popularity(self.popularity()?))),
+            _ => Err(ParseError::NoEvent),
+        }
+    }
+    fn popularity(&self) -> Result<Popularity> {
+        Ok(Popularity(u32::from_be_bytes(
+            self.data
+                .as_ref()
+                .try_into()
+                .map_err(|_| ParseError::Int32BE)?,
+        )))
+    }
 }
 
 impl Packet {
@@ -175,36 +294,42 @@ impl Packet {
         buf.extend((self.protocol_version as u16).to_be_bytes());
         buf.extend((self.op as u32).to_be_bytes());
         buf.extend(self.seq_id.to_be_bytes());
-        buf.extend(&self.data);
+        buf.extend_from_slice(&self.data);
         buf
     }
 
     /// Parse the packet received from Bilibili server.
+    ///
+    /// Zlib and brotli payloads are decompressed automatically.
+    ///
+    /// A compressed packet may carry a batch of packets, in which case only the first one is
+    /// returned. Use [`Packet::parse_all`](Packet::parse_all) to get all of them.
     #[must_use]
     pub fn parse(input: &[u8]) -> IncompleteResult<(&[u8], Self)> {
+        match Self::parse_all(input) {
+            IncompleteResult::Ok((input, packets)) => packets.into_iter().next().map_or_else(
+                || IncompleteResult::Err(ParseError::PacketError(String::from("empty batch"))),
+                |packet| IncompleteResult::Ok((input, packet)),
+            ),
+            IncompleteResult::Incomplete(needed) => IncompleteResult::Incomplete(needed),
+            IncompleteResult::Err(e) => IncompleteResult::Err(e),
+        }
+    }
+
+    /// Parse the packet received from Bilibili server, returning all packets contained in it.
+    ///
+    /// Zlib and brotli payloads are decompressed automatically. The server batches notifications
+    /// into compressed packets, so a single packet on the wire may yield multiple packets.
+    ///
+    /// The body is copied out of `input`. Use [`Packet::parse_buf`](Packet::parse_buf) or
+    /// [`Packet::parse_bytes`](Packet::parse_bytes) to avoid copying.
+    #[must_use]
+    pub fn parse_all(input: &[u8]) -> IncompleteResult<(&[u8], Vec<Self>)> {
         match parser::parse(input) {
-            Ok((input, packet)) => {
-                if packet.protocol_version == Protocol::Zlib {
-                    let mut z = ZlibDecoder::new(Cursor::new(packet.data));
-                    let mut buf = Vec::new();
-                    if let Err(e) = z.read_to_end(&mut buf) {
-                        return IncompleteResult::Err(ParseError::ZlibError(e));
-                    }
-
-                    match parser::parse(&buf) {
-                        Ok((_, packet)) => IncompleteResult::Ok((input, packet)),
-                        Err(Err::Incomplete(needed)) => {
-                            IncompleteResult::Err(ParseError::PacketError(format!(
-                                "incomplete buffer: {:?} needed",
-                                needed
-                            )))
-                        }
-                        Err(Err::Error(e) | Err::Failure(e)) => {
-                            IncompleteResult::Err(ParseError::PacketError(format!("{:?}", e)))
-                        }
-                    }
-                } else {
-                    IncompleteResult::Ok((input, packet))
+            Ok((input, (header, body))) => {
+                match Self::from_parts(header, Bytes::copy_from_slice(body)).unpack() {
+                    Ok(packets) => IncompleteResult::Ok((input, packets)),
+                    Err(e) => IncompleteResult::Err(e),
                 }
             }
             Err(Err::Incomplete(needed)) => IncompleteResult::Incomplete(needed),
@@ -213,4 +338,109 @@ impl Packet {
             }
         }
     }
+
+    /// Parse the first packet in the buffer, returning all packets contained in it.
+    ///
+    /// The parsed bytes are removed from the buffer, and bodies of the packets share its memory
+    /// without copying. Returns `None` if the buffer doesn't hold a complete packet yet.
+    ///
+    /// # Errors
+    /// Returns an error if the packet is malformed.
+    pub fn parse_buf(buf: &mut BytesMut) -> Result<Option<Vec<Self>>> {
+        match Self::parse_header(buf)? {
+            Some((header, len, body_len)) => {
+                let frame = buf.split_to(len).freeze();
+                Self::from_parts(header, frame.slice(len - body_len..))
+                    .unpack()
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Parse the first packet in the bytes, returning all packets contained in it.
+    ///
+    /// Works like [`Packet::parse_buf`](Packet::parse_buf), for bytes which are already frozen,
+    /// e.g. a websocket frame.
+    ///
+    /// # Errors
+    /// Returns an error if the packet is malformed.
+    pub fn parse_bytes(buf: &mut Bytes) -> Result<Option<Vec<Self>>> {
+        match Self::parse_header(buf)? {
+            Some((header, len, body_len)) => {
+                let frame = buf.split_to(len);
+                Self::from_parts(header, frame.slice(len - body_len..))
+                    .unpack()
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Parse the first packet in the input, returning its header, its length and the length of
+    /// its body, which ends the packet. Returns `None` if it's incomplete.
+    fn parse_header(input: &[u8]) -> Result<Option<(parser::Header, usize, usize)>> {
+        match parser::parse(input) {
+            Ok((remaining, (header, body))) => {
+                Ok(Some((header, input.len() - remaining.len(), body.len())))
+            }
+            Err(Err::Incomplete(_)) => Ok(None),
+            Err(Err::Error(e) | Err::Failure(e)) => {
+                Err(ParseError::PacketError(format!("{:?}", e)))
+            }
+        }
+    }
+
+    fn from_parts(header: parser::Header, data: Bytes) -> Self {
+        Self {
+            packet_length: header.packet_length,
+            header_length: header.header_length,
+            protocol_version: header.protocol_version,
+            op: header.op,
+            seq_id: header.seq_id,
+            data,
+        }
+    }
+
+    /// Decompress the packet if needed, and split it into the packets it contains.
+    fn unpack(self) -> Result<Vec<Self>> {
+        let buf = match self.protocol_version {
+            Protocol::Zlib => {
+                let mut z = ZlibDecoder::new(&*self.data);
+                let mut buf = Vec::new();
+                z.read_to_end(&mut buf).map_err(ParseError::ZlibError)?;
+                buf
+            }
+            Protocol::Brotli => {
+                let mut d = Decompressor::new(&*self.data, BROTLI_BUFFER_SIZE);
+                let mut buf = Vec::new();
+                d.read_to_end(&mut buf).map_err(ParseError::BrotliError)?;
+                buf
+            }
+            Protocol::Json | Protocol::Int32BE => return Ok(vec![self]),
+        };
+
+        // packets share the decompressed buffer
+        let buf = Bytes::from(buf);
+        let mut input = &*buf;
+        let mut packets = Vec::new();
+        while !input.is_empty() {
+            match parser::parse(input) {
+                Ok((remaining, (header, body))) => {
+                    packets.push(Self::from_parts(header, buf.slice_ref(body)));
+                    input = remaining;
+                }
+                Err(Err::Incomplete(needed)) => {
+                    return Err(ParseError::PacketError(format!(
+                        "incomplete buffer: {:?} needed",
+                        needed
+                    )));
+                }
+                Err(Err::Error(e) | Err::Failure(e)) => {
+                    return Err(ParseError::PacketError(format!("{:?}", e)));
+                }
+            }
+        }
+        Ok(packets)
+    }
 }
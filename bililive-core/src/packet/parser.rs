@@ -2,12 +2,21 @@ use std::convert::TryFrom;
 
 use nom::bytes::streaming::take;
 use nom::combinator::{map, map_res};
+use nom::error::{Error, ErrorKind};
 use nom::number::streaming::{be_u16, be_u32};
 use nom::sequence::tuple;
-use nom::IResult;
+use nom::{Err, IResult};
 
 use super::types::{Operation, Protocol};
-use super::Packet;
+
+/// Header fields of a packet.
+pub struct Header {
+    pub packet_length: u32,
+    pub header_length: u16,
+    pub protocol_version: Protocol,
+    pub op: Operation,
+    pub seq_id: u32,
+}
 
 fn parse_proto(input: &[u8]) -> IResult<&[u8], Protocol> {
     map_res(be_u16, Protocol::try_from)(input)
@@ -17,19 +26,25 @@ fn parse_op(input: &[u8]) -> IResult<&[u8], Operation> {
     map(be_u32, Operation::from)(input)
 }
 
-pub fn parse(input: &[u8]) -> IResult<&[u8], Packet> {
+/// Parse a packet, returning its header and body.
+pub fn parse(input: &[u8]) -> IResult<&[u8], (Header, &[u8])> {
     let (input, (packet_length, header_length, protocol_version, op, seq_id)) =
         tuple((be_u32, be_u16, parse_proto, parse_op, be_u32))(input)?;
-    let (input, data) = take(packet_length - u32::from(header_length))(input)?;
+    let body_length = packet_length
+        .checked_sub(u32::from(header_length))
+        .ok_or_else(|| Err::Error(Error::new(input, ErrorKind::Verify)))?;
+    let (input, data) = take(body_length)(input)?;
     Ok((
         input,
-        Packet {
-            packet_length,
-            header_length,
-            protocol_version,
-            op,
-            seq_id,
-            data: data.to_vec(),
-        },
+        (
+            Header {
+                packet_length,
+                header_length,
+                protocol_version,
+                op,
+                seq_id,
+            },
+            data,
+        ),
     ))
 }
@@ -1,11 +1,14 @@
 use std::fs::read;
 
-use serde_json::json;
+use bytes::{Bytes, BytesMut};
+use serde_json::{json, Value};
 
+use crate::config::{Compression, Credential, StreamConfig};
 use crate::errors::IncompleteResult;
+use crate::test_utils::danmaku;
 
 use super::types::{Operation, Protocol};
-use super::Packet;
+use super::{Packet, PacketBuffer};
 
 fn test_packet(path: &str, expect: Packet, skip_encode: bool) {
     let content = read(path).unwrap();
@@ -92,3 +95,180 @@ fn must_parse_buffer() {
     expected.set_seq_id(0);
     test_packet("tests/raw/buffer.packet", expected, true);
 }
+
+fn test_compressed_round_trip(compress: impl FnOnce(Packet) -> Packet, proto: Protocol) {
+    let expected = danmaku("test");
+    let compressed = compress(expected.clone());
+    assert_eq!(compressed.proto(), proto);
+
+    let content = compressed.encode();
+    if let IncompleteResult::Ok((remaining, packet)) = Packet::parse(&content) {
+        assert!(remaining.is_empty());
+        assert_eq!(packet, expected);
+    } else {
+        panic!("error while parsing");
+    }
+}
+
+#[test]
+fn must_round_trip_zlib() {
+    test_compressed_round_trip(|p| p.compress().unwrap(), Protocol::Zlib);
+}
+
+#[test]
+fn must_round_trip_brotli() {
+    test_compressed_round_trip(|p| p.compress_brotli().unwrap(), Protocol::Brotli);
+}
+
+#[test]
+fn must_negotiate_compression() {
+    let mut config = StreamConfig::new(1016, 0, String::from("token"), vec![]);
+    let protover = |config: &StreamConfig| {
+        Packet::new_room_enter(config).json::<Value>().unwrap()["protover"].clone()
+    };
+    assert_eq!(protover(&config), json!(2));
+
+    config.set_compression(Compression::Brotli);
+    assert_eq!(protover(&config), json!(3));
+}
+
+#[test]
+fn must_enter_with_credential() {
+    let mut config = StreamConfig::new(1016, 0, String::from("token"), vec![]);
+    let enter = Packet::new_room_enter(&config).json::<Value>().unwrap();
+    assert_eq!(enter["uid"], json!(0));
+    assert!(enter.get("buvid").is_none());
+
+    config.set_credential(Credential::new("sess", "jct", "buvid-xyz", 12345));
+    let enter = Packet::new_room_enter(&config).json::<Value>().unwrap();
+    assert_eq!(enter["uid"], json!(12345));
+    assert_eq!(enter["buvid"], json!("buvid-xyz"));
+    assert_eq!(enter["key"], json!("token"));
+}
+
+fn test_batch(path: &str) {
+    let content = read(path).unwrap();
+    if let IncompleteResult::Ok((remaining, packets)) = Packet::parse_all(&content) {
+        assert!(remaining.is_empty());
+        let cmds: Vec<_> = packets
+            .iter()
+            .map(|packet| {
+                assert_eq!(packet.op(), Operation::Notification);
+                assert_eq!(packet.proto(), Protocol::Json);
+                packet.json::<Value>().unwrap()["cmd"].clone()
+            })
+            .collect();
+        assert_eq!(
+            cmds,
+            [
+                json!("DANMU_MSG"),
+                json!("DANMU_MSG"),
+                json!("ONLINE_RANK_COUNT")
+            ]
+        );
+    } else {
+        panic!("error while parsing");
+    }
+}
+
+#[test]
+fn must_parse_zlib_batch() {
+    test_batch("tests/raw/zlib_batch.packet");
+}
+
+#[test]
+fn must_parse_brotli_batch() {
+    test_batch("tests/raw/brotli_batch.packet");
+}
+
+#[test]
+fn must_round_trip_batch() {
+    let packs = vec![danmaku("first"), danmaku("second")];
+    for compression in [Compression::Zlib, Compression::Brotli] {
+        let content = Packet::compress_batch(&packs, compression)
+            .unwrap()
+            .encode();
+        if let IncompleteResult::Ok((remaining, parsed)) = Packet::parse_all(&content) {
+            assert!(remaining.is_empty());
+            assert_eq!(parsed, packs);
+        } else {
+            panic!("error while parsing");
+        }
+    }
+}
+
+#[test]
+fn must_parse_first_of_batch() {
+    let content = read("tests/raw/zlib_batch.packet").unwrap();
+    if let IncompleteResult::Ok((_, packet)) = Packet::parse(&content) {
+        assert_eq!(packet.json::<Value>().unwrap()["info"][1], json!("first"));
+    } else {
+        panic!("error while parsing");
+    }
+}
+
+#[test]
+fn must_parse_buf_incrementally() {
+    let content = read("tests/raw/zlib_batch.packet").unwrap();
+    let json = read("tests/raw/json.packet").unwrap();
+
+    let mut buf = BytesMut::from(&content[..content.len() - 1]);
+    assert!(Packet::parse_buf(&mut buf).unwrap().is_none());
+    assert_eq!(buf.len(), content.len() - 1);
+
+    buf.extend_from_slice(&content[content.len() - 1..]);
+    buf.extend_from_slice(&json);
+    let packets = Packet::parse_buf(&mut buf).unwrap().unwrap();
+    assert_eq!(packets.len(), 3);
+    assert_eq!(buf.len(), json.len());
+
+    let packets = Packet::parse_buf(&mut buf).unwrap().unwrap();
+    assert_eq!(packets[0].op(), Operation::RoomEnterResponse);
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn must_parse_bytes_without_copy() {
+    let content = Bytes::from(read("tests/raw/json.packet").unwrap());
+    let mut buf = content.clone();
+    let packets = Packet::parse_bytes(&mut buf).unwrap().unwrap();
+    assert!(buf.is_empty());
+    assert_eq!(packets[0].body(), content.slice(16..));
+    assert_eq!(packets[0].body().as_ptr(), content[16..].as_ptr());
+}
+
+#[test]
+fn must_reassemble_packets_across_frames() {
+    let content = read("tests/raw/zlib_batch.packet").unwrap();
+    let json = read("tests/raw/json.packet").unwrap();
+    let mut buffer = PacketBuffer::new();
+
+    // a packet split across frames is kept until completed
+    buffer.feed(Bytes::copy_from_slice(&content[..10])).unwrap();
+    assert!(buffer.pop().is_none());
+    let mut rest = content[10..].to_vec();
+    rest.extend_from_slice(&json);
+    rest.extend_from_slice(&json[..1]);
+    buffer.feed(Bytes::from(rest)).unwrap();
+    let packets: Vec<_> = std::iter::from_fn(|| buffer.pop()).collect();
+    assert_eq!(packets.len(), 4);
+    assert_eq!(packets[3].op(), Operation::RoomEnterResponse);
+
+    buffer.feed(Bytes::copy_from_slice(&json[1..])).unwrap();
+    assert_eq!(buffer.pop().unwrap().op(), Operation::RoomEnterResponse);
+
+    // packets in a whole frame share its memory
+    let frame = Bytes::from(json);
+    buffer.feed(frame.clone()).unwrap();
+    assert_eq!(buffer.pop().unwrap().body().as_ptr(), frame[16..].as_ptr());
+    assert!(buffer.pop().is_none());
+}
+
+#[test]
+fn must_reject_bad_header_length() {
+    let mut content = read("tests/raw/json.packet").unwrap();
+    // header length larger than the packet length
+    content[4..6].copy_from_slice(&u16::MAX.to_be_bytes());
+    let mut buf = BytesMut::from(&content[..]);
+    assert!(Packet::parse_buf(&mut buf).is_err());
+}
@@ -36,6 +36,7 @@ pub enum Protocol {
     Json = 0,
     Int32BE = 1,
     Zlib = 2,
+    Brotli = 3,
 }
 
 impl TryFrom<u16> for Protocol {
@@ -46,6 +47,7 @@ impl TryFrom<u16> for Protocol {
             0 => Ok(Self::Json),
             1 => Ok(Self::Int32BE),
             2 => Ok(Self::Zlib),
+            3 => Ok(Self::Brotli),
             _ => Err(ParseError::UnknownProtocol),
         }
     }
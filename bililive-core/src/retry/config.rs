@@ -1,13 +1,19 @@
 use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::sync::Arc;
 use std::time::Duration;
 
 use stream_reconnect::ReconnectOptions;
 
+use super::events::Scheduled;
 use super::policy::BEBIterator;
+use super::RetryContext;
+
+type Delays = Box<dyn Iterator<Item = Duration> + Send + Sync>;
+type DelaysGenerator = Arc<dyn Fn() -> Delays + Send + Sync>;
 
 /// The configuration for retry behavior.
 #[derive(Clone)]
-pub struct RetryConfig(ReconnectOptions);
+pub struct RetryConfig(DelaysGenerator);
 
 impl RetryConfig {
     /// Create a retry configuration with given `duration_generator`.
@@ -23,13 +29,30 @@ impl RetryConfig {
         I: 'static + Send + Sync + Iterator<Item = Duration>,
         IN: IntoIterator<IntoIter = I, Item = Duration>,
     {
-        Self(ReconnectOptions::new().with_retries_generator(duration_generator))
+        Self(Arc::new(move || Box::new(duration_generator().into_iter())))
+    }
+
+    /// Convert into reconnect options reporting connections, failures, disconnections, scheduled
+    /// retries and exhaustion as [`RetryEvent`](super::RetryEvent)s of given context.
+    #[must_use]
+    pub fn into_options(self, ctx: &RetryContext) -> ReconnectOptions {
+        // a failed initial connection of a previous stream leaves no retry to schedule
+        ctx.take_retry_pending();
+        let (on_connect, on_disconnect, on_connect_fail, scheduled) =
+            (ctx.clone(), ctx.clone(), ctx.clone(), ctx.clone());
+        ReconnectOptions::new()
+            .with_retries_generator(move || -> Delays {
+                Box::new(Scheduled::new((self.0)(), scheduled.clone()))
+            })
+            .with_on_connect_callback(move || on_connect.on_connect())
+            .with_on_disconnect_callback(move || on_disconnect.on_disconnect())
+            .with_on_connect_fail_callback(move || on_connect_fail.on_connect_fail())
     }
 }
 
 impl From<RetryConfig> for ReconnectOptions {
     fn from(o: RetryConfig) -> Self {
-        o.0
+        Self::new().with_retries_generator(move || (o.0)())
     }
 }
 
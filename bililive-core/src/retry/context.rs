@@ -1,42 +1,300 @@
-use std::sync::atomic::AtomicUsize;
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter, Result as FmtResult};
 use std::sync::atomic::Ordering::SeqCst;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
 
-use crate::config::StreamConfig;
+use futures::channel::mpsc::{channel, Receiver, Sender};
+use log::{debug, warn};
+
+use crate::config::{Compression, HeartbeatConfig, StreamConfig};
+use crate::events::Popularity;
+
+use super::{ConfigProvider, ConnectLimiter, RetryEvent, RoundRobin, ServerSelector, ServerStats};
+
+/// Number of events buffered for each subscriber.
+pub const EVENT_BUFFER: usize = 64;
 
 /// Internal context for server picking during (re)connection.
 ///
-/// Implements a round-robin policy for server selection.
-#[derive(Debug, Clone)]
+/// Servers are selected by a [`ServerSelector`](ServerSelector), which is
+/// [`RoundRobin`](super::RoundRobin) by default. Connection statistics of each server are recorded
+/// and shared between clones of the context, so they can be inspected by keeping a clone.
+/// So are lifecycle events, see [`subscribe`](RetryContext::subscribe), and the room popularity,
+/// see [`popularity`](RetryContext::popularity).
+#[derive(Clone)]
 pub struct RetryContext {
     config: StreamConfig,
-    cursor: Arc<AtomicUsize>,
+    latest: Arc<Mutex<StreamConfig>>,
+    selector: Arc<dyn ServerSelector>,
+    stats: Arc<Mutex<HashMap<String, ServerStats>>>,
+    attempts: Arc<AtomicUsize>,
+    config_stale: Arc<AtomicBool>,
+    limiter: Option<ConnectLimiter>,
+    provider: Option<Arc<dyn ConfigProvider>>,
+    subscribers: Arc<Mutex<Vec<Sender<RetryEvent>>>>,
+    outcome: Arc<Mutex<Outcome>>,
+    popularity: Arc<Mutex<Option<Popularity>>>,
+}
+
+/// Outcome of the latest connection, reported as events by the callbacks of reconnect options.
+#[derive(Debug, Default)]
+struct Outcome {
+    /// server of the latest connection attempt
+    server: String,
+    /// reason of the latest connection failure or disconnection
+    reason: String,
+    /// whether a failure or disconnection is reported, and the next retry is yet to be scheduled
+    retry_pending: bool,
 }
 
 impl RetryContext {
-    /// Get the stream config.
-    #[must_use]
-    pub const fn config(&self) -> &StreamConfig {
-        &self.config
-    }
-    /// Get the next server.
-    #[allow(clippy::missing_panics_doc)]
-    pub fn get(&mut self) -> &str {
-        let cursor: usize = self
-            .cursor
-            .fetch_update(SeqCst, SeqCst, |i| {
-                Some((i + 1) % self.config.servers().len())
-            })
-            .unwrap();
-        &*self.config.servers()[cursor]
+    /// Share a limit on concurrent connection attempts with other streams.
+    #[must_use]
+    pub fn with_limiter(mut self, limiter: ConnectLimiter) -> Self {
+        self.limiter = Some(limiter);
+        self
+    }
+    /// Refresh the stream config by given provider on each reconnection.
+    ///
+    /// The initial connection uses the config this context is constructed with.
+    /// If the provider fails, the previous config is used.
+    ///
+    /// Without a provider, retries are given up once the config is rejected by the server.
+    ///
+    /// The credential, heartbeat config and compression are carried over from the previous config
+    /// unless the refreshed one sets them. The heartbeat config and compression are considered
+    /// unset if they are the defaults, so the provider can't reset them to the defaults.
+    #[must_use]
+    pub fn with_config_provider(mut self, provider: impl ConfigProvider + 'static) -> Self {
+        self.provider = Some(Arc::new(provider));
+        self
+    }
+    /// Select servers by given policy.
+    #[must_use]
+    pub fn with_selector(mut self, selector: impl ServerSelector + 'static) -> Self {
+        self.selector = Arc::new(selector);
+        self
+    }
+    /// Get the latest stream config, including refreshes made by streams using this context.
+    #[must_use]
+    pub fn config(&self) -> StreamConfig {
+        lock(&self.latest).clone()
+    }
+    /// Get the next server. Returns `None` if the config has no server.
+    pub fn get(&mut self) -> Option<&str> {
+        let servers = self.config.servers();
+        if servers.is_empty() {
+            return None;
+        }
+        let idx = self.selector.select(servers, &lock(&self.stats));
+        Some(&servers[idx.min(servers.len() - 1)])
+    }
+    /// Get connection statistics of servers tried.
+    #[must_use]
+    pub fn server_stats(&self) -> HashMap<String, ServerStats> {
+        lock(&self.stats).clone()
+    }
+    /// Whether the stream config has been rejected by the server, e.g. the token expired.
+    ///
+    /// A stale config should be refetched instead of being retried. It's cleared once the config
+    /// is refreshed by the [`ConfigProvider`](ConfigProvider), and retries are given up if there's
+    /// none.
+    #[must_use]
+    pub fn is_config_stale(&self) -> bool {
+        self.config_stale.load(SeqCst)
+    }
+    /// Get the latest room popularity received by streams using this context, if any.
+    ///
+    /// It's kept across reconnections.
+    #[must_use]
+    pub fn popularity(&self) -> Option<Popularity> {
+        *lock(&self.popularity)
+    }
+    /// Get the limit on concurrent connection attempts, if any.
+    #[must_use]
+    pub const fn limiter(&self) -> Option<&ConnectLimiter> {
+        self.limiter.as_ref()
+    }
+    /// Mark the stream config as rejected by the server.
+    pub fn mark_config_stale(&self) {
+        self.config_stale.store(true, SeqCst);
+    }
+
+    /// Record a successful connection attempt to the server.
+    pub(crate) fn record_success(&self, server: &str, latency: Duration) {
+        lock(&self.stats)
+            .entry(server.to_string())
+            .or_default()
+            .record_success(latency);
+    }
+    /// Record a failed connection attempt to the server.
+    pub(crate) fn record_failure(&self, server: &str) {
+        lock(&self.stats)
+            .entry(server.to_string())
+            .or_default()
+            .record_failure();
+    }
+
+    /// Record the latest room popularity.
+    pub(crate) fn set_popularity(&self, popularity: Popularity) {
+        *lock(&self.popularity) = Some(popularity);
+    }
+
+    /// Subscribe to lifecycle events of streams using this context.
+    ///
+    /// Events emitted before subscription are not received. Subscribe before connecting to receive
+    /// all of them.
+    ///
+    /// **Events are dropped if the subscriber falls behind.** Up to [`EVENT_BUFFER`](EVENT_BUFFER)
+    /// events are buffered for each subscriber, and newer events are discarded while the buffer is
+    /// full, so that an idle subscriber never blocks or leaks memory. Keep the receiver polled to
+    /// receive all events.
+    #[must_use]
+    pub fn subscribe(&self) -> Receiver<RetryEvent> {
+        let (tx, rx) = channel(EVENT_BUFFER);
+        lock(&self.subscribers).push(tx);
+        rx
+    }
+    /// Emit an event to all subscribers.
+    pub(crate) fn emit(&self, event: RetryEvent) {
+        debug!("retry event: {:?}", event);
+        lock(&self.subscribers).retain_mut(|tx| match tx.try_send(event.clone()) {
+            Ok(()) => true,
+            Err(e) if e.is_full() => {
+                warn!("retry event subscriber lagging, dropping event");
+                true
+            }
+            Err(_) => false,
+        });
+    }
+
+    /// Report a connection attempt to given server.
+    pub(crate) fn connecting(&self, server: &str, attempt: usize) {
+        lock(&self.outcome).server = server.to_string();
+        self.emit(RetryEvent::Connecting {
+            server: server.to_string(),
+            attempt,
+        });
+    }
+    /// Record the reason of a connection failure or disconnection, to be reported by the
+    /// callbacks of reconnect options.
+    pub(crate) fn set_reason(&self, reason: String) {
+        lock(&self.outcome).reason = reason;
+    }
+    /// Report that the connection is established. Called on connect by reconnect options.
+    pub(crate) fn on_connect(&self) {
+        let server = lock(&self.outcome).server.clone();
+        self.emit(RetryEvent::Connected { server });
+    }
+    /// Report a failed connection attempt. Called on connect failure by reconnect options.
+    pub(crate) fn on_connect_fail(&self) {
+        let event = {
+            let mut outcome = lock(&self.outcome);
+            outcome.retry_pending = true;
+            RetryEvent::ConnectFailed {
+                server: outcome.server.clone(),
+                reason: outcome.reason.clone(),
+            }
+        };
+        self.emit(event);
+    }
+    /// Report that the connection is lost. Called on disconnect by reconnect options.
+    pub(crate) fn on_disconnect(&self) {
+        let event = {
+            let mut outcome = lock(&self.outcome);
+            outcome.retry_pending = true;
+            RetryEvent::Disconnected {
+                reason: outcome.reason.clone(),
+            }
+        };
+        self.emit(event);
+    }
+    /// Whether a [`ConfigProvider`](ConfigProvider) is set.
+    pub(crate) fn has_provider(&self) -> bool {
+        self.provider.is_some()
+    }
+    /// Whether a failure or disconnection is reported and a retry is yet to be scheduled.
+    /// The flag is cleared.
+    pub(crate) fn take_retry_pending(&self) -> bool {
+        std::mem::take(&mut lock(&self.outcome).retry_pending)
+    }
+
+    /// Load the latest config shared between attempts, refreshing it first on reconnections if a
+    /// provider is set. Returns the number of this attempt, counting from 1.
+    pub(crate) async fn refresh(&mut self) -> usize {
+        let attempt = self.attempts.fetch_add(1, SeqCst) + 1;
+        if let (true, Some(provider)) = (attempt > 1, &self.provider) {
+            match provider.provide().await {
+                Ok(config) => {
+                    debug!("stream config refreshed");
+                    let mut latest = lock(&self.latest);
+                    *latest = merge(&latest, config);
+                    self.config_stale.store(false, SeqCst);
+                }
+                Err(e) => warn!("unable to refresh stream config: {}", e),
+            }
+        }
+        self.config = lock(&self.latest).clone();
+        attempt
+    }
+}
+
+impl Debug for RetryContext {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("RetryContext")
+            .field("config", &self.config)
+            .field("selector", &self.selector)
+            .field("stats", &self.stats)
+            .field("config_stale", &self.config_stale)
+            .field("limiter", &self.limiter)
+            .field(
+                "provider",
+                &self.provider.as_ref().map(|_| "<ConfigProvider>"),
+            )
+            .finish()
     }
 }
 
 impl From<StreamConfig> for RetryContext {
     fn from(config: StreamConfig) -> Self {
         Self {
+            latest: Arc::new(Mutex::new(config.clone())),
             config,
-            cursor: Arc::new(Default::default()),
+            selector: Arc::new(RoundRobin::new()),
+            stats: Arc::new(Default::default()),
+            attempts: Arc::new(Default::default()),
+            config_stale: Arc::new(Default::default()),
+            limiter: None,
+            provider: None,
+            subscribers: Arc::new(Default::default()),
+            outcome: Arc::new(Default::default()),
+            popularity: Arc::new(Default::default()),
         }
     }
 }
+
+/// Carry client-side settings of the previous config over to a refreshed one, unless it sets them.
+///
+/// The heartbeat config and compression are considered unset if they are the defaults, so a
+/// refreshed config can't reset them to the defaults.
+fn merge(previous: &StreamConfig, mut refreshed: StreamConfig) -> StreamConfig {
+    if refreshed.credential().is_none() {
+        if let Some(credential) = previous.credential() {
+            refreshed.set_credential(credential.clone());
+        }
+    }
+    if refreshed.heartbeat() == &HeartbeatConfig::default() {
+        refreshed.set_heartbeat(previous.heartbeat().clone());
+    }
+    if refreshed.compression() == Compression::default() {
+        refreshed.set_compression(previous.compression());
+    }
+    refreshed
+}
+
+/// Lock the mutex, ignoring poisoning since the data is always consistent.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
@@ -0,0 +1,151 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::{ready, Sink, Stream};
+
+use crate::errors::StreamError;
+use crate::events::Event;
+use crate::packet::{Operation, Packet};
+
+use super::{is_disconnect_error, RetryContext};
+
+/// Lifecycle events of an auto-retry stream.
+///
+/// Subscribe to them by [`RetryContext::subscribe`](RetryContext::subscribe).
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum RetryEvent {
+    /// Connecting to a server. `attempt` counts from 1 since the stream is created.
+    Connecting { server: String, attempt: usize },
+    /// Connected to the server and entered the room.
+    Connected { server: String },
+    /// Failed to connect to the server or enter the room.
+    ConnectFailed { server: String, reason: String },
+    /// The connection is lost.
+    Disconnected { reason: String },
+    /// The next connection attempt is scheduled after given delay.
+    RetryScheduled { delay: Duration },
+    /// Connection attempts have been exhausted, and the stream ends.
+    Exhausted,
+}
+
+/// A connected stream reporting the reason of its disconnection and the room popularity to the
+/// [`RetryContext`](RetryContext).
+#[derive(Debug)]
+pub struct Monitored<S> {
+    stream: S,
+    ctx: RetryContext,
+}
+
+impl<S> Monitored<S> {
+    pub(crate) const fn new(stream: S, ctx: RetryContext) -> Self {
+        Self { stream, ctx }
+    }
+
+    /// Consume the wrapper, returning the underlying stream.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+
+    fn check<T, E: std::error::Error>(
+        &self,
+        res: Result<T, StreamError<E>>,
+    ) -> Result<T, StreamError<E>> {
+        if let Err(e) = &res {
+            if is_disconnect_error(e) {
+                self.ctx.set_reason(e.to_string());
+            }
+        }
+        res
+    }
+}
+
+impl<S, E> Stream for Monitored<S>
+where
+    S: Stream<Item = Result<Packet, StreamError<E>>> + Unpin,
+    E: std::error::Error,
+{
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let item = ready!(Pin::new(&mut self.stream).poll_next(cx));
+        Poll::Ready(match item {
+            Some(Ok(pack)) => {
+                if pack.op() == Operation::HeartBeatResponse {
+                    if let Ok(Event::Popularity(popularity)) = pack.event() {
+                        self.ctx.set_popularity(popularity);
+                    }
+                }
+                Some(Ok(pack))
+            }
+            Some(res) => Some(self.check(res)),
+            None => {
+                self.ctx.set_reason(String::from("connection closed"));
+                None
+            }
+        })
+    }
+}
+
+impl<S, E> Sink<Packet> for Monitored<S>
+where
+    S: Sink<Packet, Error = StreamError<E>> + Unpin,
+    E: std::error::Error,
+{
+    type Error = S::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let res = ready!(Pin::new(&mut self.stream).poll_ready(cx));
+        Poll::Ready(self.check(res))
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Packet) -> Result<(), Self::Error> {
+        let res = Pin::new(&mut self.stream).start_send(item);
+        self.check(res)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let res = ready!(Pin::new(&mut self.stream).poll_flush(cx));
+        Poll::Ready(self.check(res))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.stream).poll_close(cx)
+    }
+}
+
+/// Retry delays reporting scheduled retries and exhaustion to the [`RetryContext`](RetryContext).
+///
+/// Only delays taken after a failure or disconnection is reported are retries. The initial
+/// connection takes its first delay in advance, which is not reported.
+///
+/// Retries are given up once the config is rejected by the server and there's no
+/// [`ConfigProvider`](crate::retry::ConfigProvider) to refresh it, as retrying it is in vain.
+pub(crate) struct Scheduled<I> {
+    delays: I,
+    ctx: RetryContext,
+}
+
+impl<I> Scheduled<I> {
+    pub(crate) const fn new(delays: I, ctx: RetryContext) -> Self {
+        Self { delays, ctx }
+    }
+}
+
+impl<I: Iterator<Item = Duration>> Iterator for Scheduled<I> {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let delay = if self.ctx.is_config_stale() && !self.ctx.has_provider() {
+            None
+        } else {
+            self.delays.next()
+        };
+        if self.ctx.take_retry_pending() {
+            self.ctx.emit(delay.map_or(RetryEvent::Exhausted, |delay| {
+                RetryEvent::RetryScheduled { delay }
+            }));
+        }
+        delay
+    }
+}
@@ -0,0 +1,78 @@
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use futures::future::poll_fn;
+
+/// A limit on concurrent connection attempts, shared by multiple streams.
+///
+/// When many streams are disconnected at the same time, e.g. due to a network failure, a shared
+/// limiter prevents them from reconnecting all at once. See
+/// [`RetryContext::with_limiter`](super::RetryContext::with_limiter).
+#[derive(Debug, Clone)]
+pub struct ConnectLimiter(Arc<Mutex<LimiterState>>);
+
+#[derive(Debug)]
+struct LimiterState {
+    permits: usize,
+    waiters: Vec<Waker>,
+}
+
+impl ConnectLimiter {
+    /// Construct a limiter allowing at most `permits` concurrent connection attempts.
+    ///
+    /// # Panics
+    /// Panics if `permits` is zero.
+    #[must_use]
+    pub fn new(permits: usize) -> Self {
+        assert!(permits > 0, "permits must be positive");
+        Self(Arc::new(Mutex::new(LimiterState {
+            permits,
+            waiters: vec![],
+        })))
+    }
+
+    /// Get the number of connection attempts that can be started right now.
+    #[allow(clippy::missing_panics_doc)]
+    #[must_use]
+    pub fn available(&self) -> usize {
+        self.0.lock().unwrap().permits
+    }
+
+    /// Wait until a connection attempt is allowed.
+    ///
+    /// The returned permit should be held until the attempt completes.
+    pub async fn acquire(&self) -> ConnectPermit {
+        poll_fn(|cx| self.poll_acquire(cx)).await
+    }
+
+    fn poll_acquire(&self, cx: &mut Context<'_>) -> Poll<ConnectPermit> {
+        let mut state = self.0.lock().unwrap();
+        if state.permits > 0 {
+            state.permits -= 1;
+            Poll::Ready(ConnectPermit(self.clone()))
+        } else {
+            if !state.waiters.iter().any(|w| w.will_wake(cx.waker())) {
+                state.waiters.push(cx.waker().clone());
+            }
+            Poll::Pending
+        }
+    }
+}
+
+/// Permit of a connection attempt. Released when dropped.
+#[derive(Debug)]
+pub struct ConnectPermit(ConnectLimiter);
+
+impl Drop for ConnectPermit {
+    fn drop(&mut self) {
+        let waiters = {
+            let mut state = self.0 .0.lock().unwrap();
+            state.permits += 1;
+            std::mem::take(&mut state.waiters)
+        };
+        // waiters may have been cancelled, so wake them all to avoid losing the permit
+        for waker in waiters {
+            waker.wake();
+        }
+    }
+}
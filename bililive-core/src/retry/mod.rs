@@ -5,21 +5,44 @@ use std::io;
 use std::io::ErrorKind;
 use std::marker::PhantomData;
 use std::pin::Pin;
+use std::time::{Duration, Instant};
 
-use futures::SinkExt;
+use futures::future::{poll_fn, select, Either};
 use futures::{Sink, Stream};
+use futures::{SinkExt, StreamExt};
+use log::{debug, warn};
+use serde::Deserialize;
 use stream_reconnect::UnderlyingStream;
 
 pub use config::RetryConfig;
-pub use context::RetryContext;
+pub use context::{RetryContext, EVENT_BUFFER};
+pub use events::{Monitored, RetryEvent};
+pub use limiter::{ConnectLimiter, ConnectPermit};
 pub use policy::BEBIterator;
+pub use provider::ConfigProvider;
+pub use selector::{FailureAware, LatencyWeighted, RoundRobin, ServerSelector, ServerStats};
 
+use crate::config::{HeartbeatConfig, StreamConfig};
 use crate::errors::StreamError;
-use crate::packet::Packet;
+use crate::packet::{Operation, Packet};
+use crate::stream::Timer;
 
 mod config;
 mod context;
+mod events;
+mod limiter;
 mod policy;
+mod provider;
+mod selector;
+#[cfg(all(
+    test,
+    not(feature = "not-send"),
+    any(feature = "tokio", feature = "async-std")
+))]
+mod tests;
+
+/// Time to wait for the room enter response after connection.
+const ROOM_ENTER_TIMEOUT: Duration = Duration::from_secs(10);
 
 /// Trait of helper objects to connect bilibili websocket server.
 ///
@@ -27,7 +50,11 @@ mod policy;
 ///
 /// An implementation of `WsStreamTrait` takes in a ws server url and decodes the data into a stream
 /// of [`Packet`](crate::packet::Packet) with heartbeat auto-response mechanism implemented
-/// (see [`HeartbeatStream`](crate::stream::HeartbeatStream) for details).
+/// (see [`HeartbeatStream`](crate::stream::HeartbeatStream) for details). The heartbeat config of
+/// the [`StreamConfig`](StreamConfig) is applied to the stream by
+/// [`set_heartbeat`](WsStreamTrait::set_heartbeat).
+///
+/// The [`Timer`](Timer) of the runtime the stream runs on is used to time out the room enter.
 #[cfg(feature = "not-send")]
 pub trait WsStreamTrait<E> {
     /// The returned stream type.
@@ -35,11 +62,19 @@ pub trait WsStreamTrait<E> {
         + Sink<Packet, Error = StreamError<E>>
         + Unpin
         + Sized;
+    /// The timer of the runtime.
+    type Timer: Timer;
     /// Connect to bilibili websocket server.
     ///
     /// # Errors
     /// Returns an error when websocket connection fails.
     fn connect(url: &str) -> Pin<Box<dyn Future<Output = Result<Self::Stream, E>> + '_>>;
+    /// Apply the heartbeat config to a connected stream before entering the room.
+    ///
+    /// By default the config is ignored.
+    fn set_heartbeat(stream: &mut Self::Stream, heartbeat: &HeartbeatConfig) {
+        let _ = (stream, heartbeat);
+    }
 }
 
 #[cfg(not(feature = "not-send"))]
@@ -50,11 +85,19 @@ pub trait WsStreamTrait<E> {
         + Unpin
         + Sized
         + Send;
+    /// The timer of the runtime.
+    type Timer: Timer + Send;
     /// Connect to bilibili websocket server.
     ///
     /// # Errors
     /// Returns an error when websocket connection fails.
     fn connect(url: &str) -> Pin<Box<dyn Future<Output = Result<Self::Stream, E>> + Send + '_>>;
+    /// Apply the heartbeat config to a connected stream before entering the room.
+    ///
+    /// By default the config is ignored.
+    fn set_heartbeat(stream: &mut Self::Stream, heartbeat: &HeartbeatConfig) {
+        let _ = (stream, heartbeat);
+    }
 }
 
 /// Wrapper for types implementing `WsStreamTrait`.
@@ -74,47 +117,140 @@ where
     pub async fn connect(url: &str) -> Result<T::Stream, E> {
         T::connect(url).await
     }
+
+    /// Connect to the next server and enter the live room.
+    ///
+    /// The room enter response is awaited and validated. If the server rejects the config,
+    /// it's marked as stale in the context.
+    ///
+    /// If a [`ConnectLimiter`](ConnectLimiter) is set in the context, a permit is held during the
+    /// attempt. If a [`ConfigProvider`](ConfigProvider) is set, the config is refreshed before
+    /// each reconnection.
+    ///
+    /// The result is recorded in the server stats, and reported as [`RetryEvent`](RetryEvent)s by
+    /// the callbacks of the reconnect options (see [`RetryConfig::into_options`](RetryConfig::into_options)).
+    /// Rejections of the config are not counted as failures of the server.
+    async fn connect_and_enter(
+        mut ctx: RetryContext,
+    ) -> Result<Monitored<T::Stream>, StreamError<E>>
+    where
+        E: std::error::Error,
+    {
+        // hold the permit until the room is entered
+        let _permit = match ctx.limiter() {
+            Some(limiter) => Some(limiter.acquire().await),
+            None => None,
+        };
+        let attempt = ctx.refresh().await;
+        let server = match ctx.get() {
+            Some(server) => server.to_string(),
+            None => {
+                warn!("no server to connect to");
+                ctx.set_reason(StreamError::<E>::NoServer.to_string());
+                return Err(StreamError::NoServer);
+            }
+        };
+        ctx.connecting(&server, attempt);
+
+        let start = Instant::now();
+        let res = Self::enter(&server, &ctx.config()).await;
+        match &res {
+            // the server answered the room enter, so it's reachable and healthy; a rejected
+            // config is a fault of the client and shouldn't push the server into cooldown
+            Ok(_) | Err(StreamError::Auth { .. }) => ctx.record_success(&server, start.elapsed()),
+            Err(_) => ctx.record_failure(&server),
+        }
+        match res {
+            Ok(stream) => Ok(Monitored::new(stream, ctx)),
+            Err(e) => {
+                if let StreamError::Auth { code } = e {
+                    warn!("room enter rejected by server: code {}", code);
+                    ctx.mark_config_stale();
+                }
+                ctx.set_reason(e.to_string());
+                Err(e)
+            }
+        }
+    }
+
+    /// Connect to given server and enter the live room.
+    async fn enter(server: &str, config: &StreamConfig) -> Result<T::Stream, StreamError<E>> {
+        let mut ws = Self::connect(server)
+            .await
+            .map_err(StreamError::from_ws_error)?;
+        T::set_heartbeat(&mut ws, config.heartbeat());
+        ws.send(Packet::new_room_enter(config)).await?;
+
+        let resp = async {
+            while let Some(pack) = ws.next().await {
+                let pack = pack?;
+                if pack.op() == Operation::RoomEnterResponse {
+                    return Ok(pack);
+                }
+                debug!("dropping packet received before room enter response");
+            }
+            Err(StreamError::IO(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "Connection closed before room enter response.",
+            )))
+        };
+        let mut timer = T::Timer::default();
+        timer.reset(Instant::now() + ROOM_ENTER_TIMEOUT);
+        let timeout = poll_fn(|cx| timer.poll_expired(cx));
+        let resp = match select(Box::pin(resp), timeout).await {
+            Either::Left((resp, _)) => resp?,
+            Either::Right(_) => return Err(StreamError::Timeout),
+        };
+
+        let code = resp.json::<RoomEnterResponse>()?.code;
+        if code != 0 {
+            return Err(StreamError::Auth { code });
+        }
+        Ok(ws)
+    }
+}
+
+/// Whether the error means the connection is lost.
+const fn is_disconnect_error<E>(err: &StreamError<E>) -> bool {
+    matches!(
+        err,
+        StreamError::WebSocket(_)
+            | StreamError::Closed { .. }
+            | StreamError::IO(_)
+            | StreamError::Timeout
+    )
+}
+
+#[derive(Deserialize)]
+struct RoomEnterResponse {
+    code: i64,
 }
 
 #[allow(clippy::type_complexity)]
 impl<T, E> UnderlyingStream<RetryContext, Result<Packet, StreamError<E>>, StreamError<E>>
     for WsStream<T, E>
 where
-    T: WsStreamTrait<E>,
-    E: std::error::Error,
+    T: WsStreamTrait<E> + 'static,
+    E: std::error::Error + 'static,
 {
-    type Stream = T::Stream;
+    type Stream = Monitored<T::Stream>;
 
     #[cfg(feature = "not-send")]
     fn establish(
-        mut ctor_arg: RetryContext,
+        ctor_arg: RetryContext,
     ) -> Pin<Box<dyn Future<Output = Result<Self::Stream, StreamError<E>>>>> {
-        Box::pin(async move {
-            let server = ctor_arg.get();
-            let mut ws = Self::connect(server)
-                .await
-                .map_err(StreamError::from_ws_error)?;
-            ws.send(Packet::new_room_enter(ctor_arg.config())).await?;
-            Ok(ws)
-        })
+        Box::pin(Self::connect_and_enter(ctor_arg))
     }
 
     #[cfg(not(feature = "not-send"))]
     fn establish(
-        mut ctor_arg: RetryContext,
+        ctor_arg: RetryContext,
     ) -> Pin<Box<dyn Future<Output = Result<Self::Stream, StreamError<E>>> + Send>> {
-        Box::pin(async move {
-            let server = ctor_arg.get();
-            let mut ws = Self::connect(server)
-                .await
-                .map_err(StreamError::from_ws_error)?;
-            ws.send(Packet::new_room_enter(ctor_arg.config())).await?;
-            Ok(ws)
-        })
+        Box::pin(Self::connect_and_enter(ctor_arg))
     }
 
     fn is_write_disconnect_error(err: &StreamError<E>) -> bool {
-        matches!(err, StreamError::WebSocket(_) | StreamError::IO(_))
+        is_disconnect_error(err)
     }
 
     fn is_read_disconnect_error(item: &Result<Packet, StreamError<E>>) -> bool {
@@ -0,0 +1,62 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::config::StreamConfig;
+use crate::errors::BuildError;
+
+/// Trait of providers of fresh stream configs.
+///
+/// A provider is called by auto-retry streams on each reconnection, so that expired danmaku server
+/// tokens are refreshed. It's usually implemented by a closure re-running
+/// [`fetch_conf`](crate::builder::ConfigBuilder::fetch_conf) or
+/// [`fetch_danmu_info`](crate::builder::ConfigBuilder::fetch_danmu_info).
+///
+/// The credential, heartbeat config and compression of the previous config are carried over,
+/// unless the provided config sets them. See
+/// [`RetryContext::with_config_provider`](super::RetryContext::with_config_provider).
+///
+/// The provider is shared between connection attempts, so it must be `Send` and `Sync` even with
+/// `not-send` enabled. The returned future needn't be.
+#[cfg(feature = "not-send")]
+pub trait ConfigProvider: Send + Sync {
+    /// Fetch a fresh stream config.
+    ///
+    /// # Errors
+    /// Returns an error if the config can't be fetched.
+    fn provide(&self) -> Pin<Box<dyn Future<Output = Result<StreamConfig, BuildError>> + '_>>;
+}
+
+#[cfg(not(feature = "not-send"))]
+pub trait ConfigProvider: Send + Sync {
+    /// Fetch a fresh stream config.
+    ///
+    /// # Errors
+    /// Returns an error if the config can't be fetched.
+    fn provide(
+        &self,
+    ) -> Pin<Box<dyn Future<Output = Result<StreamConfig, BuildError>> + Send + '_>>;
+}
+
+#[cfg(feature = "not-send")]
+impl<F, Fut> ConfigProvider for F
+where
+    F: Fn() -> Fut + Send + Sync,
+    Fut: Future<Output = Result<StreamConfig, BuildError>> + 'static,
+{
+    fn provide(&self) -> Pin<Box<dyn Future<Output = Result<StreamConfig, BuildError>> + '_>> {
+        Box::pin(self())
+    }
+}
+
+#[cfg(not(feature = "not-send"))]
+impl<F, Fut> ConfigProvider for F
+where
+    F: Fn() -> Fut + Send + Sync,
+    Fut: Future<Output = Result<StreamConfig, BuildError>> + Send + 'static,
+{
+    fn provide(
+        &self,
+    ) -> Pin<Box<dyn Future<Output = Result<StreamConfig, BuildError>> + Send + '_>> {
+        Box::pin(self())
+    }
+}
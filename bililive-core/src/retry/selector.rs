@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::SeqCst;
+use std::time::{Duration, Instant};
+
+use rand::distributions::{Distribution, WeightedIndex};
+
+/// Weight of the latest sample in the moving average of latency.
+const LATENCY_SMOOTHING: f64 = 0.3;
+
+/// Connection statistics of a server.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct ServerStats {
+    successes: u64,
+    failures: u64,
+    consecutive_failures: u64,
+    last_failure: Option<Instant>,
+    latency: Option<Duration>,
+}
+
+impl ServerStats {
+    /// Get the number of successful connection attempts.
+    #[must_use]
+    pub const fn successes(&self) -> u64 {
+        self.successes
+    }
+    /// Get the number of failed connection attempts.
+    #[must_use]
+    pub const fn failures(&self) -> u64 {
+        self.failures
+    }
+    /// Get the number of failed connection attempts since the last successful one.
+    #[must_use]
+    pub const fn consecutive_failures(&self) -> u64 {
+        self.consecutive_failures
+    }
+    /// Get the time of the last failed connection attempt.
+    #[must_use]
+    pub const fn last_failure(&self) -> Option<Instant> {
+        self.last_failure
+    }
+    /// Get the moving average of time taken to connect and enter the room.
+    #[must_use]
+    pub const fn latency(&self) -> Option<Duration> {
+        self.latency
+    }
+
+    pub(crate) fn record_success(&mut self, latency: Duration) {
+        self.successes += 1;
+        self.consecutive_failures = 0;
+        self.latency = Some(self.latency.map_or(latency, |avg| {
+            avg.mul_f64(1. - LATENCY_SMOOTHING) + latency.mul_f64(LATENCY_SMOOTHING)
+        }));
+    }
+    pub(crate) fn record_failure(&mut self) {
+        self.failures += 1;
+        self.consecutive_failures += 1;
+        self.last_failure = Some(Instant::now());
+    }
+}
+
+/// Trait of server selection policies used on (re)connection.
+///
+/// The selector is shared between connection attempts, so it must be `Send` and `Sync` even with
+/// `not-send` enabled.
+pub trait ServerSelector: Debug + Send + Sync {
+    /// Select the server to connect to, returning its index in `servers`.
+    ///
+    /// `servers` is never empty. Servers never tried have no entry in `stats`.
+    fn select(&self, servers: &[String], stats: &HashMap<String, ServerStats>) -> usize;
+}
+
+/// Walk through servers in order. This is the default policy.
+#[derive(Debug, Default)]
+pub struct RoundRobin {
+    cursor: AtomicUsize,
+}
+
+impl RoundRobin {
+    /// Construct a round-robin selector.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ServerSelector for RoundRobin {
+    fn select(&self, servers: &[String], _: &HashMap<String, ServerStats>) -> usize {
+        // the server list may change after a config refresh, so the cursor is taken modulo the
+        // current length
+        self.cursor.fetch_add(1, SeqCst) % servers.len().max(1)
+    }
+}
+
+/// Walk through servers in order, skipping servers that failed recently.
+///
+/// A server failed within the cooldown period is skipped until it cools down. If all servers are
+/// cooling down, the one failed the earliest is selected.
+#[derive(Debug)]
+pub struct FailureAware {
+    cooldown: Duration,
+    inner: RoundRobin,
+}
+
+impl FailureAware {
+    /// Construct a failure-aware selector with given cooldown period.
+    #[must_use]
+    pub fn new(cooldown: Duration) -> Self {
+        Self {
+            cooldown,
+            inner: RoundRobin::new(),
+        }
+    }
+}
+
+impl Default for FailureAware {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(30))
+    }
+}
+
+impl ServerSelector for FailureAware {
+    fn select(&self, servers: &[String], stats: &HashMap<String, ServerStats>) -> usize {
+        let failed_at = |i: usize| {
+            stats
+                .get(&servers[i])
+                .filter(|stats| stats.consecutive_failures > 0)
+                .and_then(|stats| stats.last_failure)
+                .filter(|at| at.elapsed() < self.cooldown)
+        };
+
+        let start = self.inner.select(servers, stats);
+        (0..servers.len())
+            .map(|offset| (start + offset) % servers.len())
+            .find(|&i| failed_at(i).is_none())
+            .or_else(|| (0..servers.len()).min_by_key(|&i| failed_at(i)))
+            .unwrap_or(start)
+    }
+}
+
+/// Select servers randomly, weighted by the inverse of their latency.
+///
+/// Servers never connected are weighted as the fastest known one so that they are explored.
+/// Servers failed consecutively are penalized.
+#[derive(Debug, Default)]
+pub struct LatencyWeighted;
+
+impl LatencyWeighted {
+    /// Construct a latency-weighted selector.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self
+    }
+}
+
+impl ServerSelector for LatencyWeighted {
+    fn select(&self, servers: &[String], stats: &HashMap<String, ServerStats>) -> usize {
+        let latency = |server: &String| {
+            stats
+                .get(server)
+                .and_then(ServerStats::latency)
+                .map(|latency| latency.as_secs_f64().max(1e-3))
+        };
+        let fastest = servers
+            .iter()
+            .filter_map(latency)
+            .fold(f64::INFINITY, f64::min);
+        let fastest = if fastest.is_finite() { fastest } else { 1. };
+
+        let weights = servers.iter().map(|server| {
+            let penalty = stats
+                .get(server)
+                .map_or(0, ServerStats::consecutive_failures)
+                .min(16) as i32;
+            1. / latency(server).unwrap_or(fastest) / 2f64.powi(penalty)
+        });
+        WeightedIndex::new(weights).map_or(0, |dist| dist.sample(&mut rand::thread_rng()))
+    }
+}
@@ -0,0 +1,442 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::{FutureExt, SinkExt, StreamExt};
+use stream_reconnect::{ReconnectStream, UnderlyingStream};
+
+use crate::config::{Compression, Credential, HeartbeatConfig, StreamConfig};
+use crate::errors::{BuildError, StreamError};
+use crate::events::Popularity;
+use crate::packet::{Operation, Packet, Protocol};
+use crate::stream::DefaultTimer;
+use crate::test_utils::{block_on, MockStream};
+
+use super::{
+    ConnectLimiter, FailureAware, LatencyWeighted, Monitored, RetryConfig, RetryContext,
+    RetryEvent, RoundRobin, ServerSelector, ServerStats, WsStream, WsStreamTrait, EVENT_BUFFER,
+};
+
+/// Connects to a fake server which answers room enter requests with the code given in the url.
+///
+/// An url without a code closes the connection instead, and `mock://silent` never answers.
+struct MockConnector;
+
+impl WsStreamTrait<io::Error> for MockConnector {
+    type Stream = MockStream;
+    type Timer = DefaultTimer;
+
+    fn connect(
+        url: &str,
+    ) -> Pin<Box<dyn Future<Output = Result<Self::Stream, io::Error>> + Send + '_>> {
+        Box::pin(async move {
+            Ok(MockStream {
+                alive: true,
+                enter_code: url.strip_prefix("mock://").and_then(|s| s.parse().ok()),
+                eof: url != "mock://silent",
+                ..Default::default()
+            })
+        })
+    }
+}
+
+fn config(server: &str) -> StreamConfig {
+    StreamConfig::new(1016, 0, String::from("token"), vec![server.to_string()])
+}
+
+fn establish(
+    server: &str,
+) -> (
+    RetryContext,
+    Result<Monitored<MockStream>, StreamError<io::Error>>,
+) {
+    let ctx = RetryContext::from(config(server));
+    let res = block_on(WsStream::<MockConnector, io::Error>::establish(ctx.clone()));
+    (ctx, res)
+}
+
+#[test]
+fn must_enter_room() {
+    let (ctx, res) = establish("mock://0");
+    assert!(res.is_ok());
+    assert!(!ctx.is_config_stale());
+}
+
+#[test]
+fn must_reject_auth_failure() {
+    let (ctx, res) = establish("mock://-101");
+    assert!(matches!(res, Err(StreamError::Auth { code: -101 })));
+    assert!(ctx.is_config_stale());
+}
+
+#[test]
+fn must_fail_on_early_close() {
+    let (ctx, res) = establish("mock://");
+    assert!(matches!(res, Err(StreamError::IO(_))));
+    assert!(!ctx.is_config_stale());
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn must_time_out_room_enter() {
+    let ctx = RetryContext::from(config("mock://silent"));
+    let res = tokio1::runtime::Builder::new_current_thread()
+        .enable_time()
+        .start_paused(true)
+        .build()
+        .unwrap()
+        .block_on(WsStream::<MockConnector, io::Error>::establish(ctx));
+    assert!(matches!(res, Err(StreamError::Timeout)));
+}
+
+#[test]
+fn must_track_popularity() {
+    let (ctx, res) = establish("mock://0");
+    let mut stream = res.unwrap();
+    assert_eq!(ctx.popularity(), None);
+
+    block_on(async {
+        stream
+            .send(Packet::new(Operation::HeartBeat, Protocol::Json, vec![]))
+            .await
+            .unwrap();
+        stream.next().await.unwrap().unwrap();
+    });
+    assert_eq!(ctx.popularity(), Some(Popularity(42)));
+}
+
+#[test]
+fn must_limit_connections() {
+    let limiter = ConnectLimiter::new(2);
+    let first = block_on(limiter.acquire());
+    let _second = block_on(limiter.acquire());
+    assert_eq!(limiter.available(), 0);
+
+    let mut third = Box::pin(limiter.acquire());
+    assert!((&mut third).now_or_never().is_none());
+    drop(first);
+    assert!(third.now_or_never().is_some());
+    assert_eq!(limiter.available(), 1);
+
+    // permits are held during connection attempts and released afterwards
+    let ctx = RetryContext::from(StreamConfig::new(
+        1016,
+        0,
+        String::from("token"),
+        vec![String::from("mock://0")],
+    ))
+    .with_limiter(limiter.clone());
+    assert!(block_on(WsStream::<MockConnector, io::Error>::establish(ctx)).is_ok());
+    assert_eq!(limiter.available(), 1);
+}
+
+#[test]
+fn must_refresh_config_on_reconnect() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let provider_calls = calls.clone();
+    let ctx = RetryContext::from(config("mock://-101")).with_config_provider(move || {
+        // the first refresh fails, and the previous config is used
+        let call = provider_calls.fetch_add(1, Ordering::SeqCst);
+        async move {
+            if call == 0 {
+                Err(BuildError::NoLiveRoom)
+            } else {
+                Ok(config("mock://0"))
+            }
+        }
+    });
+    let establish = || block_on(WsStream::<MockConnector, io::Error>::establish(ctx.clone()));
+
+    // the initial connection uses the given config
+    assert!(matches!(establish(), Err(StreamError::Auth { code: -101 })));
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+    assert!(ctx.is_config_stale());
+
+    assert!(matches!(establish(), Err(StreamError::Auth { code: -101 })));
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    assert!(establish().is_ok());
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+    assert!(!ctx.is_config_stale());
+}
+
+#[test]
+fn must_carry_settings_over_refresh() {
+    let heartbeat = HeartbeatConfig::new(Duration::from_secs(10), Duration::from_secs(20));
+    let mut initial = config("mock://-101");
+    initial.set_credential(Credential::new("sessdata", "bili_jct", "buvid3", 12345));
+    initial.set_heartbeat(heartbeat.clone());
+    let ctx = RetryContext::from(initial).with_config_provider(|| async { Ok(config("mock://0")) });
+    // the config is inspected by a clone which never connects
+    let inspector = ctx.clone();
+    let establish = || block_on(WsStream::<MockConnector, io::Error>::establish(ctx.clone()));
+
+    assert!(establish().is_err());
+    assert!(establish().is_ok());
+
+    let config = inspector.config();
+    assert_eq!(config.servers(), ["mock://0"]);
+    assert_eq!(config.credential().unwrap().uid(), 12345);
+    assert_eq!(config.uid(), 12345);
+    assert_eq!(config.heartbeat(), &heartbeat);
+}
+
+#[test]
+fn must_carry_compression_over_refresh() {
+    let mut initial = config("mock://-101");
+    initial.set_compression(Compression::Brotli);
+    let ctx = RetryContext::from(initial).with_config_provider(|| async { Ok(config("mock://0")) });
+    let inspector = ctx.clone();
+    let establish = || block_on(WsStream::<MockConnector, io::Error>::establish(ctx.clone()));
+
+    assert!(establish().is_err());
+    assert!(establish().is_ok());
+
+    let config = inspector.config();
+    assert_eq!(config.servers(), ["mock://0"]);
+    assert_eq!(config.compression(), Compression::Brotli);
+}
+
+fn servers(servers: &[&str]) -> StreamConfig {
+    StreamConfig::new(
+        1016,
+        0,
+        String::from("token"),
+        servers.iter().map(ToString::to_string).collect(),
+    )
+}
+
+#[test]
+fn must_reject_empty_server_list() {
+    let mut ctx = RetryContext::from(servers(&[]));
+    assert_eq!(ctx.get(), None);
+    assert!(matches!(
+        block_on(WsStream::<MockConnector, io::Error>::establish(ctx)),
+        Err(StreamError::NoServer)
+    ));
+}
+
+#[test]
+fn must_record_server_stats() {
+    let ctx = RetryContext::from(servers(&["mock://", "mock://0", "mock://-101"]));
+    for _ in 0..4 {
+        let _ = block_on(WsStream::<MockConnector, io::Error>::establish(ctx.clone()));
+    }
+
+    let stats = ctx.server_stats();
+    assert_eq!(stats["mock://"].failures(), 2);
+    assert_eq!(stats["mock://"].consecutive_failures(), 2);
+    assert!(stats["mock://"].last_failure().is_some());
+    assert_eq!(stats["mock://"].latency(), None);
+    assert_eq!(stats["mock://0"].successes(), 1);
+    assert!(stats["mock://0"].latency().is_some());
+    // rejected configs are not failures of the server
+    assert_eq!(stats["mock://-101"].failures(), 0);
+}
+
+#[test]
+fn must_skip_failed_servers() {
+    let ctx = RetryContext::from(servers(&["mock://", "mock://0"]))
+        .with_selector(FailureAware::new(Duration::from_secs(60)));
+    let establish = || block_on(WsStream::<MockConnector, io::Error>::establish(ctx.clone()));
+
+    assert!(establish().is_err());
+    for _ in 0..3 {
+        assert!(establish().is_ok());
+    }
+    let stats = ctx.server_stats();
+    assert_eq!(stats["mock://"].failures(), 1);
+    assert_eq!(stats["mock://0"].successes(), 3);
+}
+
+fn stats_of(records: &[(&str, Option<Duration>, u64)]) -> HashMap<String, ServerStats> {
+    records
+        .iter()
+        .map(|(server, latency, failures)| {
+            let mut stats = ServerStats::default();
+            if let Some(latency) = latency {
+                stats.record_success(*latency);
+            }
+            for _ in 0..*failures {
+                stats.record_failure();
+            }
+            ((*server).to_string(), stats)
+        })
+        .collect()
+}
+
+#[test]
+fn must_select_servers() {
+    let list: Vec<String> = ["a", "b", "c"].iter().map(ToString::to_string).collect();
+
+    let rr = RoundRobin::new();
+    let picks: Vec<_> = (0..4).map(|_| rr.select(&list, &HashMap::new())).collect();
+    assert_eq!(picks, [0, 1, 2, 0]);
+    // survives shrinking server lists
+    assert_eq!(rr.select(&list[..1], &HashMap::new()), 0);
+    // never panics on empty server lists
+    assert_eq!(rr.select(&[], &HashMap::new()), 0);
+    assert_eq!(FailureAware::default().select(&[], &HashMap::new()), 0);
+    assert_eq!(LatencyWeighted::new().select(&[], &HashMap::new()), 0);
+
+    // all servers are cooling down, so the one failed the earliest is selected
+    let stats = stats_of(&[("b", None, 1), ("c", None, 1), ("a", None, 1)]);
+    assert_eq!(FailureAware::default().select(&list, &stats), 1);
+    // recovered servers are not skipped
+    let stats = stats_of(&[("a", None, 1), ("b", None, 1), ("c", None, 0)]);
+    assert_eq!(FailureAware::default().select(&list, &stats), 2);
+    let mut stats = stats_of(&[("a", None, 1), ("b", None, 1)]);
+    stats
+        .get_mut("a")
+        .unwrap()
+        .record_success(Duration::from_millis(1));
+    assert_eq!(FailureAware::default().select(&list, &stats), 0);
+}
+
+#[test]
+fn must_prefer_fast_servers() {
+    let list: Vec<String> = ["fast", "slow", "failing"]
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    let stats = stats_of(&[
+        ("fast", Some(Duration::from_millis(10)), 0),
+        ("slow", Some(Duration::from_secs(1)), 0),
+        ("failing", Some(Duration::from_millis(10)), 8),
+    ]);
+
+    let mut picks = [0; 3];
+    for _ in 0..1000 {
+        picks[LatencyWeighted::new().select(&list, &stats)] += 1;
+    }
+    assert!(picks[0] > 900, "{:?}", picks);
+
+    // unknown servers are explored as the fastest known one
+    let stats = stats_of(&[
+        ("fast", Some(Duration::from_millis(10)), 0),
+        ("slow", Some(Duration::from_secs(1)), 0),
+    ]);
+    let mut picks = [0; 3];
+    for _ in 0..1000 {
+        picks[LatencyWeighted::new().select(&list, &stats)] += 1;
+    }
+    assert!(picks[2] > 400 && picks[1] < 100, "{:?}", picks);
+}
+
+/// Connect to given servers with auto retry until the stream ends, returning the events reported.
+fn retry_events(servers_list: &[&str], retries: usize) -> Vec<RetryEvent> {
+    let ctx = RetryContext::from(servers(servers_list));
+    let mut events = ctx.subscribe();
+    let options =
+        RetryConfig::new(move || vec![Duration::from_millis(10); retries]).into_options(&ctx);
+    block_on(async {
+        let mut stream = ReconnectStream::<
+            WsStream<MockConnector, io::Error>,
+            RetryContext,
+            Result<Packet, StreamError<io::Error>>,
+            StreamError<io::Error>,
+        >::connect_with_options(ctx.clone(), options)
+        .await
+        .unwrap();
+        while stream.next().await.is_some() {}
+    });
+    std::iter::from_fn(|| events.try_next().ok().flatten()).collect()
+}
+
+#[test]
+fn must_report_events() {
+    // the mock stream ends after the room enter response, and the reconnection fails
+    let events = retry_events(&["mock://0", "mock://-101"], 1);
+    assert_eq!(events.len(), 7, "{:?}", events);
+    assert_eq!(
+        events[0],
+        RetryEvent::Connecting {
+            server: String::from("mock://0"),
+            attempt: 1
+        }
+    );
+    assert_eq!(
+        events[1],
+        RetryEvent::Connected {
+            server: String::from("mock://0")
+        }
+    );
+    assert_eq!(
+        events[2],
+        RetryEvent::Disconnected {
+            reason: String::from("connection closed")
+        }
+    );
+    assert_eq!(
+        events[3],
+        RetryEvent::RetryScheduled {
+            delay: Duration::from_millis(10)
+        }
+    );
+    assert_eq!(
+        events[5],
+        RetryEvent::ConnectFailed {
+            server: String::from("mock://-101"),
+            reason: StreamError::<io::Error>::Auth { code: -101 }.to_string()
+        }
+    );
+    assert_eq!(events[6], RetryEvent::Exhausted);
+}
+
+#[test]
+fn must_drop_events_of_lagging_subscribers() {
+    let ctx = RetryContext::from(servers(&["mock://0"]));
+    let mut events = ctx.subscribe();
+    for _ in 0..EVENT_BUFFER * 2 {
+        ctx.emit(RetryEvent::Exhausted);
+    }
+    let received = std::iter::from_fn(|| events.try_next().ok().flatten()).count();
+    assert!((EVENT_BUFFER..EVENT_BUFFER * 2).contains(&received));
+
+    // the subscriber is kept after catching up
+    ctx.emit(RetryEvent::Exhausted);
+    assert_eq!(events.try_next().unwrap(), Some(RetryEvent::Exhausted));
+}
+
+#[test]
+fn must_report_retries() {
+    // the mock stream ends after the room enter response, and reconnections fail
+    let events: Vec<_> = retry_events(&["mock://0", "mock://", "mock://"], 2)
+        .into_iter()
+        .map(|event| match event {
+            RetryEvent::Connecting { attempt, .. } => format!("connecting {}", attempt),
+            RetryEvent::Connected { .. } => String::from("connected"),
+            RetryEvent::ConnectFailed { .. } => String::from("failed"),
+            RetryEvent::Disconnected { .. } => String::from("disconnected"),
+            RetryEvent::RetryScheduled { delay } => format!("retry in {}ms", delay.as_millis()),
+            RetryEvent::Exhausted => String::from("exhausted"),
+        })
+        .collect();
+    assert_eq!(
+        events,
+        [
+            "connecting 1",
+            "connected",
+            "disconnected",
+            "retry in 10ms",
+            "connecting 2",
+            "failed",
+            "retry in 10ms",
+            "connecting 3",
+            "failed",
+            "exhausted"
+        ]
+    );
+}
+
+#[test]
+fn must_give_up_rejected_config_without_provider() {
+    // the rejected config can't be refreshed, so the remaining retries are given up
+    let events = retry_events(&["mock://0", "mock://-101"], 5);
+    assert_eq!(events.len(), 7, "{:?}", events);
+    assert_eq!(events[6], RetryEvent::Exhausted);
+}
@@ -0,0 +1,211 @@
+//! Room actions and queries, e.g. sending danmaku and likes, and fetching room info.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+pub use types::{OutgoingDanmaku, RoomInfo};
+
+pub use crate::events::DanmakuMode;
+
+use crate::builder::{Requester, RoomInitInner, LIVE_API};
+use crate::config::{Credential, StreamConfig};
+use crate::errors::ActionError;
+
+use self::types::{ActionResp, AnchorInner, RoomInfoInner};
+
+#[cfg(all(test, not(feature = "not-send")))]
+mod tests;
+mod types;
+
+/// Client of bilibili live room apis.
+///
+/// Actions like sending danmaku are performed as the account of the [`Credential`](Credential),
+/// so it's required for them. Queries like [`room_info`](RoomClient::room_info) are anonymous.
+///
+/// See docs of downstream crates for details.
+#[derive(Debug)]
+pub struct RoomClient<H> {
+    http: H,
+    room_id: u64,
+    anchor_uid: Option<u64>,
+    credential: Option<Credential>,
+    api_host: Option<String>,
+}
+
+impl<H: Default> RoomClient<H> {
+    /// Construct a client of given live room with default requester client.
+    #[must_use]
+    pub fn new(room_id: u64) -> Self {
+        Self::new_with_client(H::default(), room_id)
+    }
+
+    /// Construct a client of the live room in given stream config with default requester client.
+    ///
+    /// The credential (if any) in the config is used. The user id of the streamer is looked up
+    /// when needed, unless set by [`anchor_uid`](RoomClient::anchor_uid).
+    #[must_use]
+    pub fn from_config(config: &StreamConfig) -> Self {
+        let client = Self::new(config.room_id());
+        match config.credential() {
+            Some(credential) => client.credential(credential.clone()),
+            None => client,
+        }
+    }
+}
+
+impl<H> RoomClient<H> {
+    /// Construct a client of given live room with given requester client.
+    #[must_use]
+    pub const fn new_with_client(client: H, room_id: u64) -> Self {
+        Self {
+            http: client,
+            room_id,
+            anchor_uid: None,
+            credential: None,
+            api_host: None,
+        }
+    }
+
+    /// Set the user id of the streamer. It's looked up when needed if not set.
+    #[must_use]
+    pub const fn anchor_uid(mut self, uid: u64) -> Self {
+        self.anchor_uid = Some(uid);
+        self
+    }
+
+    /// Set the credential to perform actions as.
+    #[must_use]
+    pub fn credential(mut self, credential: Credential) -> Self {
+        self.credential = Some(credential);
+        self
+    }
+
+    /// Override the host of bilibili apis, e.g. to use a proxy or a mock server.
+    ///
+    /// See [`ConfigBuilder::api_host`](crate::builder::ConfigBuilder::api_host) for details.
+    #[must_use]
+    pub fn api_host(mut self, host: &str) -> Self {
+        self.api_host = Some(host.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// Get the room id.
+    #[must_use]
+    pub const fn room_id(&self) -> u64 {
+        self.room_id
+    }
+
+    /// Url of the api at `path`, respecting the overridden api host.
+    fn api_url(&self, path: &str) -> String {
+        format!("{}{}", self.api_host.as_deref().unwrap_or(LIVE_API), path)
+    }
+}
+
+impl<H: Requester> RoomClient<H> {
+    /// Send a danmaku to the room.
+    ///
+    /// # Errors
+    /// Returns an error if there's no credential, the request fails, or the server rejects the
+    /// danmaku. Rate limits, mutes and filtered danmaku are reported as
+    /// [`RateLimited`](ActionError::RateLimited), [`Muted`](ActionError::Muted) and
+    /// [`Filtered`](ActionError::Filtered) respectively.
+    pub async fn send_danmaku(
+        &self,
+        danmaku: impl Into<OutgoingDanmaku>,
+    ) -> Result<(), ActionError> {
+        let danmaku = danmaku.into();
+        let rnd = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let mut form = danmaku.form();
+        form.push(("rnd", rnd.to_string()));
+        form.push(("roomid", self.room_id.to_string()));
+        self.post(&self.api_url("/msg/send"), form).await?;
+        Ok(())
+    }
+
+    /// Send likes to the room.
+    ///
+    /// # Errors
+    /// Returns an error if there's no credential, the request fails, or the server rejects the
+    /// likes.
+    pub async fn send_likes(&self, count: u32) -> Result<(), ActionError> {
+        let credential = self.credential.as_ref().ok_or(ActionError::NotLoggedIn)?;
+        let anchor_uid = match self.anchor_uid {
+            Some(uid) => uid,
+            None => self.fetch_anchor_uid().await?,
+        };
+
+        let form = vec![
+            ("click_time", count.to_string()),
+            ("room_id", self.room_id.to_string()),
+            ("uid", credential.uid().to_string()),
+            ("anchor_id", anchor_uid.to_string()),
+        ];
+        self.post(
+            &self.api_url("/xlive/app-ucenter/v1/like_info_v3/like/likeReportV3"),
+            form,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Fetch metadata of the room, e.g. title, area and live status.
+    ///
+    /// # Errors
+    /// Returns an error if the request fails, or the room doesn't exist.
+    pub async fn room_info(&self) -> Result<RoomInfo, ActionError> {
+        let room: RoomInfoInner = self
+            .get(&format!("/room/v1/Room/get_info?room_id={}", self.room_id))
+            .await?;
+        let anchor: AnchorInner = self
+            .get(&format!(
+                "/live_user/v1/UserInfo/get_anchor_in_room?roomid={}",
+                room.room_id
+            ))
+            .await?;
+        Ok(RoomInfo::new(room, anchor))
+    }
+
+    async fn fetch_anchor_uid(&self) -> Result<u64, ActionError> {
+        let room: RoomInitInner = self
+            .get(&format!("/room/v1/Room/room_init?id={}", self.room_id))
+            .await?;
+        Ok(room.uid())
+    }
+
+    /// Make an anonymous `GET` request to the api at `path`, and decode its data.
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, ActionError> {
+        let value: Value = self
+            .http
+            .get_json(&self.api_url(path))
+            .await
+            .map_err(ActionError::Http)?;
+        serde_json::from_value::<ActionResp>(value)?
+            .check()?
+            .decode()
+    }
+
+    /// Make a `POST` request carrying the credential and csrf token.
+    async fn post(
+        &self,
+        url: &str,
+        mut form: Vec<(&'static str, String)>,
+    ) -> Result<ActionResp, ActionError> {
+        let credential = self.credential.as_ref().ok_or(ActionError::NotLoggedIn)?;
+        form.push(("csrf", credential.bili_jct().to_string()));
+        form.push(("csrf_token", credential.bili_jct().to_string()));
+        let form: Vec<_> = form.iter().map(|(k, v)| (*k, v.as_str())).collect();
+
+        let value: Value = self
+            .http
+            .post_form(url, &form, &credential.cookie())
+            .await
+            .map_err(ActionError::Http)?;
+        serde_json::from_value::<ActionResp>(value)?.check()
+    }
+}
@@ -0,0 +1,295 @@
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::{Duration, UNIX_EPOCH};
+
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+use crate::builder::{LiveStatus, Requester};
+use crate::config::Credential;
+use crate::errors::{ActionError, BoxedError};
+
+use super::types::ActionResp;
+use super::{DanmakuMode, OutgoingDanmaku, RoomClient};
+
+/// Url, form and cookie of a request.
+type Request = (String, Vec<(String, String)>, String);
+
+/// A fake requester which answers canned responses in order, and records requests.
+#[derive(Default)]
+struct FakeRequester {
+    responses: Mutex<VecDeque<Value>>,
+    requests: Mutex<Vec<Request>>,
+}
+
+impl FakeRequester {
+    fn new(responses: Vec<Value>) -> Self {
+        Self {
+            responses: Mutex::new(responses.into()),
+            ..Default::default()
+        }
+    }
+
+    fn respond<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        form: &[(&str, &str)],
+        cookie: &str,
+    ) -> Pin<Box<dyn Future<Output = Result<T, BoxedError>> + Send + '_>> {
+        let form = form
+            .iter()
+            .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+            .collect();
+        self.requests
+            .lock()
+            .unwrap()
+            .push((url.to_string(), form, cookie.to_string()));
+        let resp = self.responses.lock().unwrap().pop_front().unwrap();
+        Box::pin(async move { Ok(serde_json::from_value(resp)?) })
+    }
+}
+
+impl Requester for FakeRequester {
+    fn get_json<T: DeserializeOwned>(
+        &self,
+        url: &str,
+    ) -> Pin<Box<dyn Future<Output = Result<T, BoxedError>> + Send + '_>> {
+        self.respond(url, &[], "")
+    }
+
+    fn get_json_with_cookie<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        cookie: &str,
+    ) -> Pin<Box<dyn Future<Output = Result<T, BoxedError>> + Send + '_>> {
+        self.respond(url, &[], cookie)
+    }
+
+    fn post_form<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        form: &[(&str, &str)],
+        cookie: &str,
+    ) -> Pin<Box<dyn Future<Output = Result<T, BoxedError>> + Send + '_>> {
+        self.respond(url, form, cookie)
+    }
+}
+
+fn ok() -> Value {
+    json!({"code": 0, "msg": "", "message": "", "data": {}})
+}
+
+fn client(responses: Vec<Value>) -> RoomClient<FakeRequester> {
+    RoomClient::new_with_client(FakeRequester::new(responses), 5440)
+        .credential(Credential::new("sess", "jct", "buvid", 12345))
+}
+
+fn form_value<'a>(form: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    form.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+}
+
+#[test]
+fn must_send_danmaku() {
+    let client = client(vec![ok()]);
+    let danmaku = OutgoingDanmaku::new("hello")
+        .color(0xff0000)
+        .mode(DanmakuMode::Top)
+        .reply_to(42);
+    futures::executor::block_on(client.send_danmaku(danmaku)).unwrap();
+
+    let requests = client.http.requests.lock().unwrap();
+    let (url, form, cookie) = &requests[0];
+    assert_eq!(url, "https://api.live.bilibili.com/msg/send");
+    assert!(cookie.contains("SESSDATA=sess"));
+    for (key, value) in [
+        ("msg", "hello"),
+        ("color", "16711680"),
+        ("mode", "5"),
+        ("fontsize", "25"),
+        ("reply_mid", "42"),
+        ("roomid", "5440"),
+        ("csrf", "jct"),
+        ("csrf_token", "jct"),
+    ] {
+        assert_eq!(form_value(form, key), Some(value), "{}", key);
+    }
+}
+
+#[test]
+fn must_send_likes() {
+    let room_init = json!({
+        "code": 0,
+        "msg": "ok",
+        "message": "ok",
+        "data": {"room_id": 5440, "uid": 9617619, "live_status": 1}
+    });
+    let client = client(vec![room_init, ok()]).api_host("http://localhost/");
+    futures::executor::block_on(client.send_likes(3)).unwrap();
+
+    let requests = client.http.requests.lock().unwrap();
+    assert_eq!(
+        requests[0].0,
+        "http://localhost/room/v1/Room/room_init?id=5440"
+    );
+    let (url, form, _) = &requests[1];
+    assert_eq!(
+        url,
+        "http://localhost/xlive/app-ucenter/v1/like_info_v3/like/likeReportV3"
+    );
+    for (key, value) in [
+        ("click_time", "3"),
+        ("room_id", "5440"),
+        ("uid", "12345"),
+        ("anchor_id", "9617619"),
+    ] {
+        assert_eq!(form_value(form, key), Some(value), "{}", key);
+    }
+}
+
+#[test]
+fn must_require_credential() {
+    let client = RoomClient::new_with_client(FakeRequester::default(), 5440);
+    let res = futures::executor::block_on(client.send_danmaku("hello"));
+    assert!(matches!(res, Err(ActionError::NotLoggedIn)));
+    assert!(client.http.requests.lock().unwrap().is_empty());
+}
+
+#[test]
+fn must_check_action_error() {
+    let check = |data: &str| {
+        serde_json::from_str::<ActionResp>(data)
+            .expect("unable to parse response")
+            .check()
+    };
+    assert!(check(r#"{"code":0,"msg":"","message":"","data":{}}"#).is_ok());
+    assert!(matches!(
+        check(r#"{"code":0,"msg":"f","message":"f","data":{}}"#),
+        Err(ActionError::Filtered)
+    ));
+    assert!(matches!(
+        check(r#"{"code":-101,"msg":"账号未登录","message":"账号未登录","data":{}}"#),
+        Err(ActionError::NotLoggedIn)
+    ));
+    assert!(matches!(
+        check(
+            r#"{"code":10030,"msg":"您发送弹幕的频率过快","message":"您发送弹幕的频率过快","data":{}}"#
+        ),
+        Err(ActionError::RateLimited { code: 10030, .. })
+    ));
+    assert!(matches!(
+        check(r#"{"code":1003,"msg":"你被禁言啦","message":"你被禁言啦","data":{}}"#),
+        Err(ActionError::Muted { message }) if message == "你被禁言啦"
+    ));
+    assert!(matches!(
+        check(r#"{"code":-400,"msg":"msg in 1s","message":"msg in 1s","data":{}}"#),
+        Err(ActionError::Api { code: -400, .. })
+    ));
+}
+
+fn room_info_resp(live_status: u8, live_time: &str) -> Value {
+    json!({
+        "code": 0,
+        "msg": "ok",
+        "message": "ok",
+        "data": {
+            "uid": 9617619,
+            "room_id": 5440,
+            "short_id": 1016,
+            "attention": 120000,
+            "online": 3456,
+            "description": "",
+            "live_status": live_status,
+            "area_id": 371,
+            "parent_area_id": 9,
+            "parent_area_name": "虚拟主播",
+            "area_name": "虚拟日常",
+            "title": "晚间杂谈",
+            "user_cover": "https://i0.hdslb.com/bfs/live/new_room_cover/cover.jpg",
+            "live_time": live_time,
+            "tags": ""
+        }
+    })
+}
+
+fn anchor_resp() -> Value {
+    json!({
+        "code": 0,
+        "msg": "success",
+        "message": "success",
+        "data": {
+            "info": {"uid": 9617619, "uname": "主播", "face": "", "gender": -1},
+            "level": {"uid": 9617619}
+        }
+    })
+}
+
+#[test]
+fn must_fetch_room_info() {
+    let client = RoomClient::new_with_client(
+        FakeRequester::new(vec![
+            room_info_resp(1, "2021-06-01 20:00:30"),
+            anchor_resp(),
+        ]),
+        1016,
+    );
+    let info = futures::executor::block_on(client.room_info()).unwrap();
+
+    let requests = client.http.requests.lock().unwrap();
+    assert_eq!(
+        requests[0].0,
+        "https://api.live.bilibili.com/room/v1/Room/get_info?room_id=1016"
+    );
+    assert_eq!(
+        requests[1].0,
+        "https://api.live.bilibili.com/live_user/v1/UserInfo/get_anchor_in_room?roomid=5440"
+    );
+
+    assert_eq!(info.room_id(), 5440);
+    assert_eq!(info.short_id(), 1016);
+    assert_eq!(info.anchor_uid(), 9617619);
+    assert_eq!(info.anchor_name(), "主播");
+    assert_eq!(info.title(), "晚间杂谈");
+    assert_eq!((info.area_id(), info.area_name()), (371, "虚拟日常"));
+    assert_eq!(
+        (info.parent_area_id(), info.parent_area_name()),
+        (9, "虚拟主播")
+    );
+    assert_eq!(info.live_status(), LiveStatus::Live);
+    // 2021-06-01T12:00:30Z
+    assert_eq!(
+        info.live_time(),
+        Some(UNIX_EPOCH + Duration::from_secs(1_622_548_830))
+    );
+    assert_eq!(
+        info.cover(),
+        "https://i0.hdslb.com/bfs/live/new_room_cover/cover.jpg"
+    );
+    assert_eq!(info.online(), 3456);
+}
+
+#[test]
+fn must_fetch_offline_room_info() {
+    let client = RoomClient::new_with_client(
+        FakeRequester::new(vec![
+            room_info_resp(0, "0000-00-00 00:00:00"),
+            anchor_resp(),
+        ]),
+        5440,
+    );
+    let info = futures::executor::block_on(client.room_info()).unwrap();
+    assert_eq!(info.live_status(), LiveStatus::Offline);
+    assert_eq!(info.live_time(), None);
+
+    let client = RoomClient::new_with_client(
+        FakeRequester::new(vec![
+            json!({"code": 1, "msg": "未找到该房间", "message": "未找到该房间", "data": []}),
+        ]),
+        1,
+    );
+    assert!(matches!(
+        futures::executor::block_on(client.room_info()),
+        Err(ActionError::Api { code: 1, .. })
+    ));
+}
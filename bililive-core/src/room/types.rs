@@ -0,0 +1,302 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::builder::{LiveStatus, NOT_LOGGED_IN};
+use crate::errors::ActionError;
+use crate::events::DanmakuMode;
+
+/// A danmaku to be sent to a live room.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct OutgoingDanmaku {
+    text: String,
+    color: u32,
+    mode: DanmakuMode,
+    font_size: u8,
+    reply_to: Option<u64>,
+}
+
+impl OutgoingDanmaku {
+    /// Construct a white scrolling danmaku.
+    #[must_use]
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            color: 0x00ff_ffff,
+            mode: DanmakuMode::Scroll,
+            font_size: 25,
+            reply_to: None,
+        }
+    }
+    /// Set the color in `0xRRGGBB`. Colors not unlocked by the account are rejected by the server.
+    #[must_use]
+    pub const fn color(mut self, color: u32) -> Self {
+        self.color = color;
+        self
+    }
+    /// Set the display mode.
+    #[must_use]
+    pub const fn mode(mut self, mode: DanmakuMode) -> Self {
+        self.mode = mode;
+        self
+    }
+    /// Set the font size. Defaults to `25`.
+    #[must_use]
+    pub const fn font_size(mut self, font_size: u8) -> Self {
+        self.font_size = font_size;
+        self
+    }
+    /// Reply to (mention) the user with given uid.
+    #[must_use]
+    pub const fn reply_to(mut self, uid: u64) -> Self {
+        self.reply_to = Some(uid);
+        self
+    }
+}
+
+impl OutgoingDanmaku {
+    /// Get the text.
+    #[must_use]
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+    /// Get the form fields of the `msg/send` api, excluding room id and csrf tokens.
+    pub(crate) fn form(&self) -> Vec<(&'static str, String)> {
+        let mut form = vec![
+            ("bubble", String::from("0")),
+            ("msg", self.text.clone()),
+            ("color", self.color.to_string()),
+            ("mode", self.mode.value().to_string()),
+            ("fontsize", self.font_size.to_string()),
+        ];
+        if let Some(uid) = self.reply_to {
+            form.push(("reply_mid", uid.to_string()));
+        }
+        form
+    }
+}
+
+impl From<&str> for OutgoingDanmaku {
+    fn from(text: &str) -> Self {
+        Self::new(text)
+    }
+}
+
+impl From<String> for OutgoingDanmaku {
+    fn from(text: String) -> Self {
+        Self::new(&text)
+    }
+}
+
+/// Muted by the streamer, or the room is in mute mode.
+const MUTED: i64 = 1003;
+/// Danmaku sent too frequently.
+const TOO_FREQUENT: i64 = 10030;
+/// The same danmaku sent too frequently.
+const REPEATED: i64 = 10031;
+
+#[derive(Debug, Deserialize)]
+pub struct ActionResp {
+    code: i64,
+    #[serde(default)]
+    message: String,
+    /// Set to `f` or `k` if the danmaku is dropped by the sensitive word or room keyword filter.
+    #[serde(default)]
+    msg: String,
+    #[serde(default)]
+    data: Option<Value>,
+}
+
+impl ActionResp {
+    /// Reject the response if the api reports a failure.
+    pub fn check(self) -> Result<Self, ActionError> {
+        match self.code {
+            0 if self.msg == "f" || self.msg == "k" => Err(ActionError::Filtered),
+            0 => Ok(self),
+            NOT_LOGGED_IN => Err(ActionError::NotLoggedIn),
+            MUTED => Err(ActionError::Muted {
+                message: self.message,
+            }),
+            TOO_FREQUENT | REPEATED => Err(ActionError::RateLimited {
+                code: self.code,
+                message: self.message,
+            }),
+            code => Err(ActionError::Api {
+                code,
+                message: self.message,
+            }),
+        }
+    }
+    /// Decode the data into the given type.
+    pub fn decode<T: DeserializeOwned>(self) -> Result<T, ActionError> {
+        Ok(serde_json::from_value(self.data.unwrap_or_default())?)
+    }
+}
+
+/// Metadata of a live room.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct RoomInfo {
+    room_id: u64,
+    short_id: u64,
+    anchor_uid: u64,
+    anchor_name: String,
+    title: String,
+    area_id: u64,
+    area_name: String,
+    parent_area_id: u64,
+    parent_area_name: String,
+    live_status: LiveStatus,
+    live_time: Option<SystemTime>,
+    cover: String,
+    online: u64,
+}
+
+impl RoomInfo {
+    pub(crate) fn new(room: RoomInfoInner, anchor: AnchorInner) -> Self {
+        Self {
+            room_id: room.room_id,
+            short_id: room.short_id,
+            anchor_uid: room.uid,
+            anchor_name: anchor.info.uname,
+            title: room.title,
+            area_id: room.area_id,
+            area_name: room.area_name,
+            parent_area_id: room.parent_area_id,
+            parent_area_name: room.parent_area_name,
+            live_status: room.live_status,
+            live_time: parse_live_time(&room.live_time),
+            cover: room.user_cover,
+            online: room.online,
+        }
+    }
+}
+
+impl RoomInfo {
+    /// Get the long room id.
+    #[must_use]
+    pub const fn room_id(&self) -> u64 {
+        self.room_id
+    }
+    /// Get the short room id, or `0` if the room has none.
+    #[must_use]
+    pub const fn short_id(&self) -> u64 {
+        self.short_id
+    }
+    /// Get the user id of the streamer.
+    #[must_use]
+    pub const fn anchor_uid(&self) -> u64 {
+        self.anchor_uid
+    }
+    /// Get the user name of the streamer.
+    #[must_use]
+    pub fn anchor_name(&self) -> &str {
+        &self.anchor_name
+    }
+    /// Get the room title.
+    #[must_use]
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+    /// Get the area id.
+    #[must_use]
+    pub const fn area_id(&self) -> u64 {
+        self.area_id
+    }
+    /// Get the area name.
+    #[must_use]
+    pub fn area_name(&self) -> &str {
+        &self.area_name
+    }
+    /// Get the parent area id.
+    #[must_use]
+    pub const fn parent_area_id(&self) -> u64 {
+        self.parent_area_id
+    }
+    /// Get the parent area name.
+    #[must_use]
+    pub fn parent_area_name(&self) -> &str {
+        &self.parent_area_name
+    }
+    /// Get the live status.
+    #[must_use]
+    pub const fn live_status(&self) -> LiveStatus {
+        self.live_status
+    }
+    /// Get the time the current live started at, or `None` if the room isn't live.
+    #[must_use]
+    pub const fn live_time(&self) -> Option<SystemTime> {
+        self.live_time
+    }
+    /// Get the cover url.
+    #[must_use]
+    pub fn cover(&self) -> &str {
+        &self.cover
+    }
+    /// Get the online count reported by the room page.
+    #[must_use]
+    pub const fn online(&self) -> u64 {
+        self.online
+    }
+}
+
+/// Parse the `YYYY-MM-DD hh:mm:ss` time in China Standard Time (UTC+8) returned by bilibili apis.
+///
+/// The zero time `0000-00-00 00:00:00` returned for offline rooms is parsed as `None`.
+fn parse_live_time(s: &str) -> Option<SystemTime> {
+    let (date, time) = s.split_once(' ')?;
+    let mut date = date.splitn(3, '-').map(str::parse::<i64>);
+    let (year, month, day) = (date.next()?.ok()?, date.next()?.ok()?, date.next()?.ok()?);
+    let mut time = time.splitn(3, ':').map(str::parse::<i64>);
+    let (hour, min, sec) = (time.next()?.ok()?, time.next()?.ok()?, time.next()?.ok()?);
+    if year == 0 || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    // days from civil, see http://howardhinnant.github.io/date_algorithms.html
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+
+    let secs = days * 86400 + hour * 3600 + min * 60 + sec - 8 * 3600;
+    Some(UNIX_EPOCH + Duration::from_secs(u64::try_from(secs).ok()?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RoomInfoInner {
+    pub room_id: u64,
+    #[serde(default)]
+    short_id: u64,
+    uid: u64,
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    area_id: u64,
+    #[serde(default)]
+    area_name: String,
+    #[serde(default)]
+    parent_area_id: u64,
+    #[serde(default)]
+    parent_area_name: String,
+    live_status: LiveStatus,
+    #[serde(default)]
+    live_time: String,
+    #[serde(default)]
+    user_cover: String,
+    #[serde(default)]
+    online: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AnchorInner {
+    info: AnchorInfo,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnchorInfo {
+    uname: String,
+}
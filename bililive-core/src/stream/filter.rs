@@ -0,0 +1,149 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::{ready, Sink, Stream};
+use log::trace;
+
+use crate::events::Cmd;
+use crate::packet::{Operation, Packet};
+
+/// A rule matching notification `cmd`s.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+enum Pattern {
+    /// Matches the command with its version parameters stripped.
+    Exact(String),
+    /// Matches any command starting with the prefix.
+    Prefix(String),
+}
+
+impl Pattern {
+    fn matches(&self, cmd: &Cmd) -> bool {
+        match self {
+            Self::Exact(name) => cmd.is(name),
+            Self::Prefix(prefix) => cmd.as_str().starts_with(prefix.as_str()),
+        }
+    }
+}
+
+/// Wrapper that drops unwanted notifications on a [`Packet`](crate::packet::Packet) stream by
+/// their `cmd`.
+///
+/// The `cmd` is peeked without decoding the rest of the body, so discarding a notification costs
+/// little more than scanning it.
+///
+/// If any allow rule is set, only notifications matching one of them are kept. Notifications
+/// matching any deny rule are then dropped. Notifications without a readable `cmd` are treated as
+/// having an empty one. Other packets, e.g. heartbeat responses, and errors are passed through.
+///
+/// The sink half is forwarded to the underlying stream.
+#[derive(Debug)]
+pub struct CmdFilter<T> {
+    stream: T,
+    allow: Vec<Pattern>,
+    deny: Vec<Pattern>,
+}
+
+impl<T> CmdFilter<T> {
+    /// Wrap the stream with a filter passing everything until rules are added.
+    pub const fn new(stream: T) -> Self {
+        Self {
+            stream,
+            allow: Vec::new(),
+            deny: Vec::new(),
+        }
+    }
+
+    /// Keep notifications of given command, ignoring version parameters.
+    ///
+    /// E.g. `DANMU_MSG` matches both `DANMU_MSG` and `DANMU_MSG:4:0:2:2:2:0`.
+    #[must_use]
+    pub fn allow(mut self, cmd: impl Into<String>) -> Self {
+        self.allow.push(Pattern::Exact(cmd.into()));
+        self
+    }
+    /// Keep notifications whose command starts with given prefix.
+    #[must_use]
+    pub fn allow_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.allow.push(Pattern::Prefix(prefix.into()));
+        self
+    }
+    /// Drop notifications of given command, ignoring version parameters.
+    #[must_use]
+    pub fn deny(mut self, cmd: impl Into<String>) -> Self {
+        self.deny.push(Pattern::Exact(cmd.into()));
+        self
+    }
+    /// Drop notifications whose command starts with given prefix.
+    #[must_use]
+    pub fn deny_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.deny.push(Pattern::Prefix(prefix.into()));
+        self
+    }
+
+    /// Get a reference to the underlying stream.
+    pub const fn get_ref(&self) -> &T {
+        &self.stream
+    }
+    /// Get a mutable reference to the underlying stream.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.stream
+    }
+    /// Consume the filter, returning the underlying stream.
+    pub fn into_inner(self) -> T {
+        self.stream
+    }
+
+    /// Whether the packet should be yielded.
+    fn accepts(&self, pack: &Packet) -> bool {
+        if pack.op() != Operation::Notification || (self.allow.is_empty() && self.deny.is_empty()) {
+            return true;
+        }
+
+        let cmd = pack.cmd().unwrap_or_else(|| Cmd::parse(""));
+        let accepted = (self.allow.is_empty() || self.allow.iter().any(|p| p.matches(&cmd)))
+            && !self.deny.iter().any(|p| p.matches(&cmd));
+        if !accepted {
+            trace!("dropping notification {}", cmd);
+        }
+        accepted
+    }
+}
+
+impl<T, E> Stream for CmdFilter<T>
+where
+    T: Stream<Item = Result<Packet, E>> + Unpin,
+{
+    type Item = T::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            match ready!(Pin::new(&mut self.stream).poll_next(cx)) {
+                Some(Ok(pack)) if !self.accepts(&pack) => continue,
+                item => return Poll::Ready(item),
+            }
+        }
+    }
+}
+
+impl<T> Sink<Packet> for CmdFilter<T>
+where
+    T: Sink<Packet> + Unpin,
+{
+    type Error = T::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.stream).poll_ready(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Packet) -> Result<(), Self::Error> {
+        Pin::new(&mut self.stream).start_send(item)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.stream).poll_close(cx)
+    }
+}
@@ -3,15 +3,18 @@ use std::pin::Pin;
 use std::sync::Arc;
 use std::task::Waker;
 use std::task::{Context, Poll};
-use std::time::{Duration, Instant};
+use std::time::Instant;
 
 use futures::ready;
 use futures::{Sink, Stream};
-use log::debug;
+use log::{debug, warn};
 
+use crate::config::HeartbeatConfig;
 use crate::errors::StreamError;
+use crate::events::{Event, Popularity};
 use crate::packet::{Operation, Packet, Protocol};
 
+use super::timer::{DefaultTimer, Timer};
 use super::waker::WakerProxy;
 
 /// Wrapper that implement heartbeat auto-response mechanism on a [`Packet`](crate::packet::Packet) stream.
@@ -19,29 +22,71 @@ use super::waker::WakerProxy;
 /// Bilibili server requires that every client must respond to a ping packet in 60 seconds. If no
 /// response is sent, the connection will be closed remotely.
 ///
-/// `HeartbeatStream` ensures that a pong packet is sent every 30 seconds.
-pub struct HeartbeatStream<T, E> {
+/// `HeartbeatStream` ensures that a pong packet is sent every 30 seconds by default. If nothing is
+/// received within the timeout after a heartbeat is sent, a [`Timeout`](StreamError::Timeout)
+/// error is yielded so that the connection can be re-established. See
+/// [`HeartbeatConfig`](HeartbeatConfig) for details.
+///
+/// Heartbeats are scheduled by the owned [`Timer`](Timer) `R`, so no task is spawned and the timer
+/// is cancelled once the stream is dropped. It defaults to the [`DefaultTimer`](DefaultTimer)
+/// of the enabled runtime.
+///
+/// The room popularity carried by heartbeat responses is tracked, and can be queried by
+/// [`popularity`](HeartbeatStream::popularity).
+pub struct HeartbeatStream<T, E, R = DefaultTimer> {
     /// underlying bilibili stream
     stream: T,
     /// waker proxy for tx, see WakerProxy for details
     tx_waker: Arc<WakerProxy>,
+    /// heartbeat behavior
+    config: HeartbeatConfig,
     /// last time when heart beat is sent
     last_hb: Option<Instant>,
+    /// time of the earliest heartbeat that hasn't been answered by any packet
+    awaiting_since: Option<Instant>,
+    /// timer to wake the stream for the next heartbeat or timeout
+    timer: R,
+    /// deadline the timer is armed with
+    timer_deadline: Option<Instant>,
+    /// latest popularity received
+    popularity: Option<Popularity>,
     __marker: PhantomData<E>,
 }
 
-impl<T: Unpin, E> Unpin for HeartbeatStream<T, E> {}
+impl<T: Unpin, E, R: Unpin> Unpin for HeartbeatStream<T, E, R> {}
 
-impl<T, E> HeartbeatStream<T, E> {
+impl<T, E, R: Timer> HeartbeatStream<T, E, R> {
     /// Add heartbeat response mechanism to the underlying bililive stream.
     pub fn new(stream: T) -> Self {
+        Self::with_config(stream, HeartbeatConfig::default())
+    }
+
+    /// Add heartbeat response mechanism with given config to the underlying bililive stream.
+    pub fn with_config(stream: T, config: HeartbeatConfig) -> Self {
         Self {
             stream,
             tx_waker: Arc::new(Default::default()),
+            config,
             last_hb: None,
+            awaiting_since: None,
+            timer: R::default(),
+            timer_deadline: None,
+            popularity: None,
             __marker: PhantomData,
         }
     }
+}
+
+impl<T, E, R> HeartbeatStream<T, E, R> {
+    /// Replace the heartbeat config. It takes effect from the next heartbeat.
+    pub fn set_config(&mut self, config: HeartbeatConfig) {
+        self.config = config;
+    }
+
+    /// Get the latest room popularity received, if any.
+    pub const fn popularity(&self) -> Option<Popularity> {
+        self.popularity
+    }
 
     fn with_context<F, U>(&mut self, f: F) -> U
     where
@@ -54,10 +99,11 @@ impl<T, E> HeartbeatStream<T, E> {
     }
 }
 
-impl<T, E> Stream for HeartbeatStream<T, E>
+impl<T, E, R> Stream for HeartbeatStream<T, E, R>
 where
     T: Stream<Item = Result<Packet, StreamError<E>>> + Sink<Packet, Error = StreamError<E>> + Unpin,
     E: std::error::Error,
+    R: Timer,
 {
     type Item = Result<Packet, StreamError<E>>;
 
@@ -65,57 +111,80 @@ where
         // register current task to be waken on poll_ready
         self.tx_waker.rx(cx.waker());
 
-        // ensure that all pending write op are completed
-        ready!(self.with_context(|cx, s| Pin::new(s).poll_ready(cx)))?;
-
-        // check whether we need to send heartbeat now.
-        let now = Instant::now();
-        let need_hb = self
-            .last_hb
-            .map_or(true, |last_hb| now - last_hb >= Duration::from_secs(30));
+        loop {
+            // ensure that all pending write op are completed
+            ready!(self.with_context(|cx, s| Pin::new(s).poll_ready(cx)))?;
+
+            // check whether we need to send heartbeat now.
+            let now = Instant::now();
+            let interval = self.config.interval();
+            let need_hb = self.last_hb.is_none_or(|last_hb| now - last_hb >= interval);
+
+            if need_hb {
+                // we need to send heartbeat, so push it into the sink
+                debug!("sending heartbeat");
+                let payload = self.config.payload().to_vec();
+                self.as_mut().start_send(Packet::new(
+                    Operation::HeartBeat,
+                    Protocol::Json,
+                    payload,
+                ))?;
+
+                // Update the time we sent the heartbeat.
+                // It must be earlier than other non-blocking op so that heartbeat
+                // won't be sent repeatedly.
+                self.last_hb = Some(now);
+                self.awaiting_since.get_or_insert(now);
+
+                // ensure that heartbeat is sent
+                ready!(self.with_context(|cx, s| Pin::new(s).poll_flush(cx)))?;
+            }
 
-        if need_hb {
-            // we need to send heartbeat, so push it into the sink
-            debug!("sending heartbeat");
-            self.as_mut()
-                .start_send(Packet::new(Operation::HeartBeat, Protocol::Json, vec![]))?;
+            if let Poll::Ready(item) = Pin::new(&mut self.stream).poll_next(cx) {
+                // any traffic proves that the connection is alive
+                self.awaiting_since = None;
+                if let Some(Ok(pack)) = &item {
+                    if pack.op() == Operation::HeartBeatResponse {
+                        if let Ok(Event::Popularity(popularity)) = pack.event() {
+                            debug!("popularity: {}", popularity.0);
+                            self.popularity = Some(popularity);
+                        }
+                    }
+                }
+                return Poll::Ready(item);
+            }
 
-            // Update the time we sent the heartbeat.
-            // It must be earlier than other non-blocking op so that heartbeat
-            // won't be sent repeatedly.
-            self.last_hb = Some(now);
+            // no traffic, check whether the connection is dead
+            let timeout = self.config.timeout();
+            let timeout_at = self.awaiting_since.map(|since| since + timeout);
+            if timeout_at.is_some_and(|timeout_at| now >= timeout_at) {
+                warn!("no response to heartbeat in {:?}", timeout);
+                self.awaiting_since = None;
+                return Poll::Ready(Some(Err(StreamError::Timeout)));
+            }
 
             // Schedule current task to be waken in case there's no incoming
-            // websocket message in a long time.
-            #[cfg(feature = "tokio")]
-            {
-                let waker = cx.waker().clone();
-                tokio1::spawn(async {
-                    tokio1::time::sleep(Duration::from_secs(30)).await;
-                    waker.wake();
-                });
-            }
-            #[cfg(feature = "async-std")]
-            {
-                let waker = cx.waker().clone();
-                async_std1::task::spawn(async {
-                    async_std1::task::sleep(Duration::from_secs(30)).await;
-                    waker.wake();
-                });
+            // websocket message in a long time, either for the next heartbeat
+            // or for the timeout deadline.
+            let next_hb = self.last_hb.map_or(now, |last_hb| last_hb + interval);
+            let deadline = timeout_at.map_or(next_hb, |timeout_at| timeout_at.min(next_hb));
+            if self.timer_deadline != Some(deadline) {
+                self.timer.reset(deadline);
+                self.timer_deadline = Some(deadline);
             }
+            ready!(self.timer.poll_expired(cx));
 
-            // ensure that heartbeat is sent
-            ready!(self.with_context(|cx, s| Pin::new(s).poll_flush(cx)))?;
+            // the deadline is reached, check again
+            self.timer_deadline = None;
         }
-
-        Pin::new(&mut self.stream).poll_next(cx)
     }
 }
 
-impl<T, E> Sink<Packet> for HeartbeatStream<T, E>
+impl<T, E, R> Sink<Packet> for HeartbeatStream<T, E, R>
 where
     T: Sink<Packet, Error = StreamError<E>> + Unpin,
     E: std::error::Error,
+    R: Unpin,
 {
     type Error = StreamError<E>;
 
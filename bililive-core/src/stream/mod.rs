@@ -1,6 +1,16 @@
 //! Stream types.
 
+pub use filter::CmdFilter;
 pub use heartbeat::HeartbeatStream;
+#[cfg(feature = "async-std")]
+pub use timer::AsyncStdTimer;
+#[cfg(feature = "tokio")]
+pub use timer::TokioTimer;
+pub use timer::{DefaultTimer, Timer};
 
+mod filter;
 mod heartbeat;
+#[cfg(all(test, any(feature = "tokio", feature = "async-std")))]
+mod tests;
+mod timer;
 pub mod waker;
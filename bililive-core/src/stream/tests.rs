@@ -0,0 +1,122 @@
+use std::io;
+use std::time::{Duration, Instant};
+
+use futures::{stream, StreamExt};
+
+use crate::config::HeartbeatConfig;
+use crate::errors::StreamError;
+use crate::events::Popularity;
+use crate::packet::{Operation, Packet, Protocol};
+
+use crate::test_utils::{block_on, MockStream};
+
+use super::{CmdFilter, HeartbeatStream};
+
+#[test]
+fn must_send_configured_heartbeat() {
+    let mock = MockStream {
+        alive: true,
+        ..Default::default()
+    };
+    let sent = mock.sent.clone();
+    let config = HeartbeatConfig::new(Duration::from_millis(50), Duration::from_secs(10))
+        .with_payload("[object Object]");
+    let mut stream: HeartbeatStream<_, _> = HeartbeatStream::with_config(mock, config);
+
+    block_on(async {
+        for _ in 0..2 {
+            let pack = stream.next().await.unwrap().unwrap();
+            assert_eq!(pack.op(), Operation::HeartBeatResponse);
+        }
+    });
+    assert_eq!(stream.popularity(), Some(Popularity(42)));
+
+    let sent = sent.lock().unwrap();
+    assert_eq!(sent.len(), 2);
+    assert!(sent
+        .iter()
+        .all(|pack| pack.op() == Operation::HeartBeat && pack.bytes() == b"[object Object]"));
+}
+
+#[test]
+fn must_time_out_without_response() {
+    let config = HeartbeatConfig::new(Duration::from_secs(30), Duration::from_millis(100));
+    let mut stream: HeartbeatStream<_, _> =
+        HeartbeatStream::with_config(MockStream::default(), config);
+
+    let start = Instant::now();
+    let res = block_on(stream.next());
+    assert!(matches!(res, Some(Err(StreamError::Timeout))));
+    assert!(start.elapsed() >= Duration::from_millis(100));
+}
+
+fn notification(cmd: &str) -> Packet {
+    Packet::new(
+        Operation::Notification,
+        Protocol::Json,
+        format!(r#"{{"cmd": "{}", "data": {{}}}}"#, cmd),
+    )
+}
+
+type PacketIter = stream::Iter<std::vec::IntoIter<Result<Packet, StreamError<io::Error>>>>;
+
+fn filter_cmds(filter: impl FnOnce(CmdFilter<PacketIter>) -> CmdFilter<PacketIter>) -> Vec<String> {
+    let packs = vec![
+        Ok(notification("DANMU_MSG:4:0:2:2:2:0")),
+        Ok(notification("DANMU_MSG_MIRROR")),
+        Ok(notification("SEND_GIFT")),
+        Ok(notification("LIKE_INFO_V3_CLICK")),
+        Ok(notification("LIKE_INFO_V3_UPDATE")),
+        Ok(Packet::new(
+            Operation::Notification,
+            Protocol::Json,
+            "garbage",
+        )),
+        Ok(Packet::new(
+            Operation::HeartBeatResponse,
+            Protocol::Int32BE,
+            42i32.to_be_bytes(),
+        )),
+        Err(StreamError::Timeout),
+    ];
+    let stream = filter(CmdFilter::new(stream::iter(packs)));
+
+    block_on(stream.collect::<Vec<_>>())
+        .into_iter()
+        .map(|item| match item {
+            Ok(pack) if pack.op() == Operation::Notification => pack
+                .json::<serde_json::Value>()
+                .map_or_else(|_| String::from("<garbage>"), |v| v["cmd"].to_string()),
+            Ok(_) => String::from("<heartbeat>"),
+            Err(_) => String::from("<error>"),
+        })
+        .collect()
+}
+
+#[test]
+fn must_filter_cmds() {
+    assert_eq!(filter_cmds(|f| f).len(), 8);
+    assert_eq!(
+        filter_cmds(|f| f.allow("DANMU_MSG").allow("SEND_GIFT")),
+        [
+            r#""DANMU_MSG:4:0:2:2:2:0""#,
+            r#""SEND_GIFT""#,
+            "<heartbeat>",
+            "<error>"
+        ]
+    );
+    assert_eq!(
+        filter_cmds(|f| f.allow_prefix("DANMU_MSG").deny("DANMU_MSG")),
+        [r#""DANMU_MSG_MIRROR""#, "<heartbeat>", "<error>"]
+    );
+    assert_eq!(
+        filter_cmds(|f| f.deny_prefix("LIKE_INFO_V3_").deny("")),
+        [
+            r#""DANMU_MSG:4:0:2:2:2:0""#,
+            r#""DANMU_MSG_MIRROR""#,
+            r#""SEND_GIFT""#,
+            "<heartbeat>",
+            "<error>"
+        ]
+    );
+}
@@ -0,0 +1,78 @@
+//! Resettable timers used to schedule heartbeats.
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+use std::future::Future;
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+/// An owned, resettable timer.
+///
+/// [`HeartbeatStream`](super::HeartbeatStream) uses a timer to wake itself when it's time to send
+/// the next heartbeat or the heartbeat response times out. Implement this trait to drive heartbeats
+/// with your own runtime.
+///
+/// The timer must be cancelled when dropped. An unarmed timer never fires.
+pub trait Timer: Default + Unpin {
+    /// Arm the timer to fire at `deadline`, replacing the previous deadline.
+    fn reset(&mut self, deadline: Instant);
+    /// Poll whether the deadline has been reached.
+    ///
+    /// If not, the waker in `cx` is woken when the deadline is reached.
+    fn poll_expired(&mut self, cx: &mut Context<'_>) -> Poll<()>;
+}
+
+/// Timer of the enabled runtime, preferring `tokio` if both are enabled.
+#[cfg(feature = "tokio")]
+pub type DefaultTimer = TokioTimer;
+/// Timer of the enabled runtime, preferring `tokio` if both are enabled.
+#[cfg(all(feature = "async-std", not(feature = "tokio")))]
+pub type DefaultTimer = AsyncStdTimer;
+
+/// Timer backed by `tokio`.
+#[cfg(feature = "tokio")]
+#[derive(Debug, Default)]
+pub struct TokioTimer(Option<Pin<Box<tokio1::time::Sleep>>>);
+
+#[cfg(feature = "tokio")]
+impl Timer for TokioTimer {
+    fn reset(&mut self, deadline: Instant) {
+        let deadline = tokio1::time::Instant::from_std(deadline);
+        match &mut self.0 {
+            Some(sleep) => sleep.as_mut().reset(deadline),
+            None => self.0 = Some(Box::pin(tokio1::time::sleep_until(deadline))),
+        }
+    }
+
+    fn poll_expired(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        self.0
+            .as_mut()
+            .map_or(Poll::Pending, |sleep| sleep.as_mut().poll(cx))
+    }
+}
+
+/// Timer backed by `async-std`.
+#[cfg(feature = "async-std")]
+#[derive(Default)]
+pub struct AsyncStdTimer(Option<Pin<Box<dyn Future<Output = ()> + Send>>>);
+
+#[cfg(feature = "async-std")]
+impl Timer for AsyncStdTimer {
+    fn reset(&mut self, deadline: Instant) {
+        let dur = deadline.saturating_duration_since(Instant::now());
+        self.0 = Some(Box::pin(async_std1::task::sleep(dur)));
+    }
+
+    fn poll_expired(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        let poll = self
+            .0
+            .as_mut()
+            .map_or(Poll::Pending, |sleep| sleep.as_mut().poll(cx));
+        if poll.is_ready() {
+            // the sleep future can't be polled after completion
+            self.0 = None;
+        }
+        poll
+    }
+}
@@ -0,0 +1,122 @@
+//! Helpers shared by unit tests.
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use futures::{Sink, Stream};
+use serde_json::{json, Value};
+
+use crate::errors::StreamError;
+use crate::packet::{Operation, Packet, Protocol};
+
+/// Run a future to completion on the enabled runtime.
+pub fn block_on<F: Future>(fut: F) -> F::Output {
+    #[cfg(feature = "tokio")]
+    return tokio1::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .unwrap()
+        .block_on(fut);
+    #[cfg(not(feature = "tokio"))]
+    return futures::executor::block_on(fut);
+}
+
+/// A minimal valid `DANMU_MSG` with given text, sent by user 1 `user`.
+pub fn danmu_msg(text: &str) -> Value {
+    json!({
+        "cmd": "DANMU_MSG",
+        "info": [[0, 1, 25, 0x00ff_ffff, 1_600_000_000_000_u64], text, [1, "user"]]
+    })
+}
+
+/// A danmaku notification with given text. See [`danmu_msg`](danmu_msg).
+pub fn danmaku(text: &str) -> Packet {
+    Packet::new(
+        Operation::Notification,
+        Protocol::Json,
+        serde_json::to_vec(&danmu_msg(text)).unwrap(),
+    )
+}
+
+/// A fake connection which records sent packets.
+///
+/// Heartbeats are answered with popularity 42 if `alive`, and room enters are answered with
+/// `enter_code` if set. When there's nothing to receive, the stream ends if `eof`, or stays
+/// pending otherwise.
+#[derive(Default)]
+pub struct MockStream {
+    pub alive: bool,
+    pub enter_code: Option<i64>,
+    pub eof: bool,
+    pub sent: Arc<Mutex<Vec<Packet>>>,
+    pub rx: VecDeque<Packet>,
+    pub waker: Option<Waker>,
+}
+
+impl MockStream {
+    fn push(&mut self, pack: Packet) {
+        self.rx.push_back(pack);
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Stream for MockStream {
+    type Item = Result<Packet, StreamError<io::Error>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.rx.pop_front() {
+            Some(pack) => Poll::Ready(Some(Ok(pack))),
+            None if self.eof => Poll::Ready(None),
+            None => {
+                self.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Sink<Packet> for MockStream {
+    type Error = StreamError<io::Error>;
+
+    fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Packet) -> Result<(), Self::Error> {
+        if self.alive && item.op() == Operation::HeartBeat {
+            self.push(Packet::new(
+                Operation::HeartBeatResponse,
+                Protocol::Int32BE,
+                42i32.to_be_bytes(),
+            ));
+        }
+        if let (Operation::RoomEnter, Some(code)) = (item.op(), self.enter_code) {
+            self.push(Packet::new(
+                Operation::HeartBeatResponse,
+                Protocol::Int32BE,
+                1i32.to_be_bytes(),
+            ));
+            self.push(Packet::new(
+                Operation::RoomEnterResponse,
+                Protocol::Json,
+                serde_json::to_vec(&json!({ "code": code })).unwrap(),
+            ));
+        }
+        self.sent.lock().unwrap().push(item);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
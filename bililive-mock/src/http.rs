@@ -0,0 +1,283 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use log::{debug, warn};
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::{lock, FormRequest, State};
+
+/// Max size of a request head.
+const MAX_REQUEST_SIZE: usize = 16 * 1024;
+
+/// Serve mock bilibili apis.
+pub async fn serve(listener: TcpListener, ws_addr: SocketAddr, state: Arc<State>) {
+    while let Ok((tcp, _)) = listener.accept().await {
+        tokio::spawn(handle(tcp, ws_addr, state.clone()));
+    }
+}
+
+/// A parsed HTTP request.
+struct Request {
+    method: String,
+    path: String,
+    cookie: String,
+    body: Vec<u8>,
+}
+
+/// Serve requests on a keep-alive connection until the client closes it.
+async fn handle(mut tcp: TcpStream, ws_addr: SocketAddr, state: Arc<State>) {
+    let mut buf = Vec::new();
+    while let Some(req) = read_request(&mut tcp, &mut buf).await {
+        debug!("{} {}", req.method, req.path);
+
+        let body = if req.method == "POST" {
+            post(&req, &state)
+        } else {
+            route(&req.path, ws_addr, &state)
+        };
+        let (status, body) = body.map_or_else(
+            || {
+                (
+                    "404 Not Found",
+                    json!({"code": -404, "message": "啥都木有"}),
+                )
+            },
+            |body| ("200 OK", body),
+        );
+        let body = body.to_string();
+        let resp = format!(
+            "HTTP/1.1 {}\r\nContent-Type: application/json; charset=utf-8\r\nContent-Length: {}\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+        if tcp.write_all(resp.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Read the next request.
+///
+/// Returns `None` if the connection is closed or the request is malformed.
+async fn read_request(tcp: &mut TcpStream, buf: &mut Vec<u8>) -> Option<Request> {
+    let mut chunk = [0; 1024];
+    let end = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        let n = tcp.read(&mut chunk).await.ok()?;
+        if n == 0 || buf.len() > MAX_REQUEST_SIZE {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+
+    let head: Vec<u8> = buf.drain(..end).collect();
+    let head = String::from_utf8_lossy(&head);
+    let mut lines = head.lines();
+    let mut request_line = lines.next()?.split(' ');
+    let (method, path) = match (request_line.next(), request_line.next()) {
+        (Some(method @ ("GET" | "POST")), Some(path)) => (method.to_string(), path.to_string()),
+        _ => {
+            warn!("malformed http request");
+            return None;
+        }
+    };
+
+    let mut cookie = String::new();
+    let mut content_length = 0;
+    for (name, value) in lines.filter_map(|line| line.split_once(':')) {
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("cookie") {
+            cookie = value.to_string();
+        } else if name.eq_ignore_ascii_case("content-length") {
+            content_length = value.parse().ok()?;
+        }
+    }
+    if content_length > MAX_REQUEST_SIZE {
+        return None;
+    }
+
+    while buf.len() < content_length {
+        let n = tcp.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    let body = buf.drain(..content_length).collect();
+
+    Some(Request {
+        method,
+        path,
+        cookie,
+        body,
+    })
+}
+
+/// Record a form request and get the response body of the requested api.
+fn post(req: &Request, state: &State) -> Option<Value> {
+    let path = req
+        .path
+        .split_once('?')
+        .map_or(req.path.as_str(), |(path, _)| path);
+    if !matches!(
+        path,
+        "/msg/send" | "/xlive/app-ucenter/v1/like_info_v3/like/likeReportV3"
+    ) {
+        return None;
+    }
+
+    let form = FormRequest {
+        path: path.to_string(),
+        cookie: req.cookie.clone(),
+        form: url::form_urlencoded::parse(&req.body)
+            .into_owned()
+            .collect(),
+    };
+    let logged_in = form.cookie.contains("SESSDATA=") && form.field("csrf").is_some();
+    lock(&state.posts).push(form);
+
+    Some(if logged_in {
+        json!({"code": 0, "msg": "", "message": "", "data": {}})
+    } else {
+        json!({"code": -101, "msg": "账号未登录", "message": "账号未登录", "data": {}})
+    })
+}
+
+/// Get the response body of the requested api.
+fn route(path: &str, ws_addr: SocketAddr, state: &State) -> Option<Value> {
+    let room = &state.room;
+    let (path, query) = path.split_once('?').unwrap_or((path, ""));
+    let param = |key: &str| {
+        query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| v.to_string())
+    };
+    let server = json!({
+        "host": ws_addr.ip().to_string(),
+        "port": ws_addr.port(),
+        "wss_port": ws_addr.port(),
+        "ws_port": ws_addr.port()
+    });
+
+    Some(match path {
+        "/room/v1/Danmu/getConf" => json!({
+            "code": 0,
+            "msg": "ok",
+            "message": "ok",
+            "data": {
+                "token": *lock(&state.token),
+                "host_server_list": [server]
+            }
+        }),
+        "/xlive/web-room/v1/index/getDanmuInfo" => {
+            if param("w_rid").is_none() {
+                return Some(json!({"code": -352, "message": "-352"}));
+            }
+            json!({
+                "code": 0,
+                "message": "0",
+                "data": {
+                    "token": *lock(&state.token),
+                    "host_list": [server]
+                }
+            })
+        }
+        "/x/web-interface/nav" => json!({
+            "code": -101,
+            "message": "账号未登录",
+            "data": {
+                "isLogin": false,
+                "wbi_img": {
+                    "img_url": "https://i0.hdslb.com/bfs/wbi/7cd084941338484aae1ad9425b84077c.png",
+                    "sub_url": "https://i0.hdslb.com/bfs/wbi/4932caff0ff746eab6f01bf08b70ac45.png"
+                }
+            }
+        }),
+        "/room/v1/Room/room_init" => {
+            let id: Option<u64> = param("id").and_then(|id| id.parse().ok());
+            if id != Some(room.room_id) && id != Some(room.short_id) {
+                return Some(
+                    json!({"code": 60004, "msg": "直播间不存在", "message": "直播间不存在", "data": []}),
+                );
+            }
+            json!({
+                "code": 0,
+                "msg": "ok",
+                "message": "ok",
+                "data": {
+                    "room_id": room.room_id,
+                    "short_id": room.short_id,
+                    "uid": room.uid,
+                    "live_status": 1,
+                    "is_locked": false,
+                    "lock_till": 0,
+                    "encrypted": false,
+                    "pwd_verified": false
+                }
+            })
+        }
+        "/room/v1/Room/get_info" => {
+            let id: Option<u64> = param("room_id").and_then(|id| id.parse().ok());
+            if id != Some(room.room_id) && id != Some(room.short_id) {
+                return Some(
+                    json!({"code": 1, "msg": "未找到该房间", "message": "未找到该房间", "data": []}),
+                );
+            }
+            json!({
+                "code": 0,
+                "msg": "ok",
+                "message": "ok",
+                "data": {
+                    "uid": room.uid,
+                    "room_id": room.room_id,
+                    "short_id": room.short_id,
+                    "online": room.popularity,
+                    "live_status": 1,
+                    "area_id": 371,
+                    "area_name": "虚拟日常",
+                    "parent_area_id": 9,
+                    "parent_area_name": "虚拟主播",
+                    "title": room.title,
+                    "user_cover": "https://i0.hdslb.com/bfs/live/new_room_cover/mock.jpg",
+                    "live_time": "2021-06-01 20:00:30"
+                }
+            })
+        }
+        "/live_user/v1/UserInfo/get_anchor_in_room" => {
+            if param("roomid").and_then(|id| id.parse().ok()) != Some(room.room_id) {
+                return Some(
+                    json!({"code": 0, "msg": "success", "message": "success", "data": []}),
+                );
+            }
+            json!({
+                "code": 0,
+                "msg": "success",
+                "message": "success",
+                "data": {
+                    "info": {"uid": room.uid, "uname": room.uname, "face": ""}
+                }
+            })
+        }
+        _ => {
+            let uid: u64 = path.strip_prefix("/bili/living_v2/")?.parse().ok()?;
+            let url = if uid == room.uid {
+                format!("https://live.bilibili.com/{}", room.room_id)
+            } else {
+                String::new()
+            };
+            json!({
+                "code": 0,
+                "msg": "",
+                "message": "",
+                "data": {"status": 0, "url": url}
+            })
+        }
+    })
+}
@@ -0,0 +1,396 @@
+//! An in-process mock bilibili live server for testing bililive clients offline.
+//!
+//! [`MockServer`](MockServer) serves both the danmaku websocket protocol and the HTTP apis used by
+//! `ConfigBuilder`, on a background thread with its own runtime. So it works under any executor.
+//!
+//! - Validates room enter packets and answers heartbeats with popularity.
+//! - Replays scripted [`Action`](Action)s, e.g. plain notifications, `Zlib`/`Brotli` compressed
+//!   batches, disconnects and close frames.
+//! - Mocks `getConf`, `getDanmuInfo`, `living_v2`, `room_init`, `get_info`, `get_anchor_in_room`
+//!   and `nav` apis.
+//! - Accepts danmaku and likes sent by `RoomClient`, and records them.
+//!
+//! # Example
+//! ```rust
+//! use bililive_mock::{danmu_msg, Action, MockServer};
+//!
+//! let mock = MockServer::builder()
+//!     .action(Action::Notification(danmu_msg("hello")))
+//!     .action(Action::Disconnect)
+//!     .start();
+//!
+//! // Point `ConfigBuilder` to the mock server by `api_host`,
+//! let api_host = mock.api_host();
+//! // or use the prepared stream config directly.
+//! let config = mock.config();
+//! ```
+
+#![allow(clippy::module_name_repetitions)]
+
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tokio::sync::{oneshot, Notify};
+
+use bililive_core::config::{Compression, StreamConfig};
+
+mod http;
+mod ws;
+
+/// A scripted action performed by the mock server on the danmaku connection.
+///
+/// Actions are performed in order, and each connection keeps its own position in the script, so
+/// concurrent connections don't take actions from each other. A new connection starts right after
+/// the action that ended the previous one, so the script continues after a disconnect.
+#[derive(Debug, Clone)]
+pub enum Action {
+    /// Send a notification in a plain `Json` packet.
+    Notification(Value),
+    /// Send notifications in a single compressed batch.
+    Batch {
+        notifications: Vec<Value>,
+        compression: Compression,
+    },
+    /// Send raw bytes in a binary message.
+    Raw(Vec<u8>),
+    /// Wait before performing the next action. Heartbeats are still answered meanwhile.
+    Delay(Duration),
+    /// Send a close frame with given code and reason.
+    Close { code: u16, reason: String },
+    /// Drop the connection without close handshake.
+    Disconnect,
+}
+
+/// A minimal valid `DANMU_MSG` notification with given text, sent by user 1 `user`.
+#[must_use]
+pub fn danmu_msg(text: &str) -> Value {
+    json!({
+        "cmd": "DANMU_MSG",
+        "info": [[0, 1, 25, 0x00ff_ffff, 1_600_000_000_000_u64], text, [1, "user"]]
+    })
+}
+
+/// A form request received by the mock server.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct FormRequest {
+    /// Requested path without query.
+    pub path: String,
+    /// Value of the `Cookie` header.
+    pub cookie: String,
+    /// Decoded form fields.
+    pub form: Vec<(String, String)>,
+}
+
+impl FormRequest {
+    /// Get the value of a form field.
+    #[must_use]
+    pub fn field(&self, key: &str) -> Option<&str> {
+        self.form
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Builder of [`MockServer`](MockServer).
+#[derive(Debug, Clone)]
+pub struct MockServerBuilder {
+    room: MockRoom,
+    script: Vec<Action>,
+}
+
+impl MockServerBuilder {
+    /// Set the long room id. Defaults to `5440`.
+    #[must_use]
+    pub const fn room_id(mut self, room_id: u64) -> Self {
+        self.room.room_id = room_id;
+        self
+    }
+    /// Set the short room id. Defaults to `1016`.
+    #[must_use]
+    pub const fn short_id(mut self, short_id: u64) -> Self {
+        self.room.short_id = short_id;
+        self
+    }
+    /// Set the user id of the streamer. Defaults to `9617619`.
+    #[must_use]
+    pub const fn uid(mut self, uid: u64) -> Self {
+        self.room.uid = uid;
+        self
+    }
+    /// Set the user name of the streamer. Defaults to `mock-anchor`.
+    #[must_use]
+    pub fn uname(mut self, uname: &str) -> Self {
+        self.room.uname = uname.to_string();
+        self
+    }
+    /// Set the room title. Defaults to `mock-room`.
+    #[must_use]
+    pub fn title(mut self, title: &str) -> Self {
+        self.room.title = title.to_string();
+        self
+    }
+    /// Set the danmaku server token. Defaults to `mock-token`.
+    #[must_use]
+    pub fn token(mut self, token: &str) -> Self {
+        self.room.token = token.to_string();
+        self
+    }
+    /// Set the popularity answered to heartbeats. Defaults to `1`.
+    #[must_use]
+    pub const fn popularity(mut self, popularity: u32) -> Self {
+        self.room.popularity = popularity;
+        self
+    }
+    /// Set the time after which a connection without heartbeat is dropped. Defaults to 60 seconds.
+    #[must_use]
+    pub const fn heartbeat_timeout(mut self, timeout: Duration) -> Self {
+        self.room.heartbeat_timeout = timeout;
+        self
+    }
+    /// Append an action to the script.
+    #[must_use]
+    pub fn action(mut self, action: Action) -> Self {
+        self.script.push(action);
+        self
+    }
+
+    /// Start the mock server on a background thread.
+    ///
+    /// # Panics
+    /// Panics if the server fails to start.
+    #[must_use]
+    pub fn start(self) -> MockServer {
+        let state = Arc::new(State {
+            token: Mutex::new(self.room.token.clone()),
+            room: self.room,
+            script: Mutex::new(self.script),
+            script_start: Mutex::new(0),
+            script_notify: Notify::new(),
+            room_enters: Mutex::new(vec![]),
+            posts: Mutex::new(vec![]),
+        });
+
+        let (addr_tx, addr_rx) = std::sync::mpsc::channel();
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+        let thread_state = state.clone();
+        let thread = std::thread::spawn(move || {
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("unable to build runtime");
+            rt.block_on(async move {
+                let ws_listener = TcpListener::bind("127.0.0.1:0")
+                    .await
+                    .expect("unable to bind ws listener");
+                let http_listener = TcpListener::bind("127.0.0.1:0")
+                    .await
+                    .expect("unable to bind http listener");
+                let addrs = (
+                    ws_listener.local_addr().unwrap(),
+                    http_listener.local_addr().unwrap(),
+                );
+                addr_tx.send(addrs).unwrap();
+
+                tokio::select! {
+                    () = ws::serve(ws_listener, thread_state.clone()) => {}
+                    () = http::serve(http_listener, addrs.0, thread_state) => {}
+                    _ = shutdown_rx => {}
+                }
+            });
+        });
+        let (ws_addr, http_addr) = addr_rx.recv().expect("mock server failed to start");
+
+        MockServer {
+            ws_addr,
+            http_addr,
+            state,
+            shutdown: Some(shutdown_tx),
+            thread: Some(thread),
+        }
+    }
+}
+
+/// An in-process mock bilibili live server.
+///
+/// The server is shut down when dropped.
+#[derive(Debug)]
+pub struct MockServer {
+    ws_addr: SocketAddr,
+    http_addr: SocketAddr,
+    state: Arc<State>,
+    shutdown: Option<oneshot::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MockServer {
+    /// Construct a builder of mock server.
+    #[must_use]
+    pub fn builder() -> MockServerBuilder {
+        MockServerBuilder {
+            room: MockRoom::default(),
+            script: vec![],
+        }
+    }
+
+    /// Start a mock server with default settings and an empty script.
+    #[must_use]
+    pub fn start() -> Self {
+        Self::builder().start()
+    }
+}
+
+impl MockServer {
+    /// Url of the danmaku websocket server.
+    #[must_use]
+    pub fn ws_url(&self) -> String {
+        format!("ws://{}/sub", self.ws_addr)
+    }
+    /// Host of the mock HTTP apis, to be passed to `ConfigBuilder::api_host`.
+    #[must_use]
+    pub fn api_host(&self) -> String {
+        format!("http://{}", self.http_addr)
+    }
+    /// Long room id.
+    #[must_use]
+    pub fn room_id(&self) -> u64 {
+        self.state.room.room_id
+    }
+    /// Short room id.
+    #[must_use]
+    pub fn short_id(&self) -> u64 {
+        self.state.room.short_id
+    }
+    /// User id of the streamer.
+    #[must_use]
+    pub fn uid(&self) -> u64 {
+        self.state.room.uid
+    }
+    /// Current danmaku server token.
+    #[must_use]
+    pub fn token(&self) -> String {
+        lock(&self.state.token).clone()
+    }
+    /// Replace the danmaku server token, as the server does when a token expires.
+    ///
+    /// Room enter packets carrying the previous token are rejected afterwards.
+    pub fn rotate_token(&self, token: &str) {
+        *lock(&self.state.token) = token.to_string();
+    }
+    /// A stream config connecting to this server.
+    #[must_use]
+    pub fn config(&self) -> StreamConfig {
+        StreamConfig::new(
+            self.room_id(),
+            self.uid(),
+            self.token(),
+            vec![self.ws_url()],
+        )
+    }
+    /// Payloads of all room enter packets received, including rejected ones.
+    #[must_use]
+    pub fn room_enters(&self) -> Vec<Value> {
+        lock(&self.state.room_enters).clone()
+    }
+    /// All form requests received by room action apis, e.g. sending danmaku.
+    #[must_use]
+    pub fn posts(&self) -> Vec<FormRequest> {
+        lock(&self.state.posts).clone()
+    }
+    /// Append an action to the script.
+    pub fn push(&self, action: Action) {
+        lock(&self.state.script).push(action);
+        self.state.script_notify.notify_waiters();
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Parameters of the mocked live room.
+#[derive(Debug, Clone)]
+struct MockRoom {
+    room_id: u64,
+    short_id: u64,
+    uid: u64,
+    uname: String,
+    title: String,
+    token: String,
+    popularity: u32,
+    heartbeat_timeout: Duration,
+}
+
+impl Default for MockRoom {
+    fn default() -> Self {
+        Self {
+            room_id: 5440,
+            short_id: 1016,
+            uid: 9_617_619,
+            uname: String::from("mock-anchor"),
+            title: String::from("mock-room"),
+            token: String::from("mock-token"),
+            popularity: 1,
+            heartbeat_timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+/// State shared between the server and its handle.
+#[derive(Debug)]
+struct State {
+    room: MockRoom,
+    token: Mutex<String>,
+    script: Mutex<Vec<Action>>,
+    /// position where new connections start in the script
+    script_start: Mutex<usize>,
+    script_notify: Notify,
+    room_enters: Mutex<Vec<Value>>,
+    posts: Mutex<Vec<FormRequest>>,
+}
+
+impl State {
+    /// Position in the script where a new connection starts.
+    fn script_start(&self) -> usize {
+        *lock(&self.script_start)
+    }
+
+    /// Let new connections start from given position, after a connection is ended by the script.
+    fn resume_script(&self, cursor: usize) {
+        let mut start = lock(&self.script_start);
+        *start = (*start).max(cursor);
+    }
+
+    /// Wait for the action at the cursor, and advance the cursor.
+    async fn next_action(&self, cursor: &mut usize) -> Action {
+        loop {
+            let notified = self.script_notify.notified();
+            tokio::pin!(notified);
+            // register before checking so that no push is missed
+            notified.as_mut().enable();
+            if let Some(action) = lock(&self.script).get(*cursor).cloned() {
+                *cursor += 1;
+                return action;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// Lock the mutex, ignoring poisoning caused by panicked tests.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
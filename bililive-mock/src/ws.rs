@@ -0,0 +1,166 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_tungstenite::tokio::{accept_async, TokioAdapter};
+use async_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use async_tungstenite::tungstenite::protocol::CloseFrame;
+use async_tungstenite::tungstenite::Message;
+use async_tungstenite::WebSocketStream;
+use futures::{SinkExt, StreamExt};
+use log::{debug, warn};
+use serde_json::{json, Value};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{sleep_until, timeout, Instant};
+
+use bililive_core::errors::IncompleteResult;
+use bililive_core::packet::{Operation, Packet, Protocol};
+
+use crate::{lock, Action, State};
+
+type WsStream = WebSocketStream<TokioAdapter<TcpStream>>;
+
+/// Time to wait for the room enter packet after connection.
+const ROOM_ENTER_TIMEOUT: Duration = Duration::from_secs(10);
+/// Time to wait for the client to complete the close handshake.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Accept danmaku connections.
+pub async fn serve(listener: TcpListener, state: Arc<State>) {
+    while let Ok((tcp, _)) = listener.accept().await {
+        tokio::spawn(handle(tcp, state.clone()));
+    }
+}
+
+async fn handle(tcp: TcpStream, state: Arc<State>) {
+    let mut ws = match accept_async(tcp).await {
+        Ok(ws) => ws,
+        Err(e) => {
+            warn!("websocket handshake failed: {}", e);
+            return;
+        }
+    };
+    if !enter_room(&mut ws, &state).await {
+        return;
+    }
+
+    let mut hb_deadline = Instant::now() + state.room.heartbeat_timeout;
+    let mut cursor = state.script_start();
+    // the script is paused by a delay until this instant
+    let mut resume_at: Option<Instant> = None;
+    loop {
+        tokio::select! {
+            msg = ws.next() => match msg {
+                Some(Ok(Message::Binary(data))) => match parse(&data) {
+                    Some(pack) if pack.op() == Operation::HeartBeat => {
+                        hb_deadline = Instant::now() + state.room.heartbeat_timeout;
+                        let resp = Packet::new(
+                            Operation::HeartBeatResponse,
+                            Protocol::Int32BE,
+                            state.room.popularity.to_be_bytes(),
+                        );
+                        if ws.send(Message::binary(resp.encode())).await.is_err() {
+                            return;
+                        }
+                    }
+                    Some(pack) => debug!("ignoring packet: {:?}", pack.op()),
+                    None => warn!("malformed packet from client"),
+                },
+                Some(Ok(_)) => {}
+                Some(Err(_)) | None => return,
+            },
+            action = state.next_action(&mut cursor), if resume_at.is_none() => match action {
+                Action::Delay(dur) => resume_at = Some(Instant::now() + dur),
+                action => {
+                    if !perform(&mut ws, action).await {
+                        state.resume_script(cursor);
+                        return;
+                    }
+                }
+            },
+            () = sleep_until(resume_at.unwrap_or(hb_deadline)), if resume_at.is_some() => {
+                resume_at = None;
+            }
+            () = sleep_until(hb_deadline) => {
+                debug!("no heartbeat received, dropping connection");
+                return;
+            }
+        }
+    }
+}
+
+/// Validate the room enter packet and answer it. Returns whether the room is entered.
+async fn enter_room(ws: &mut WsStream, state: &State) -> bool {
+    let payload = match timeout(ROOM_ENTER_TIMEOUT, ws.next()).await {
+        Ok(Some(Ok(Message::Binary(data)))) => parse(&data)
+            .filter(|pack| pack.op() == Operation::RoomEnter)
+            .and_then(|pack| pack.json::<Value>().ok()),
+        _ => None,
+    };
+    let payload = if let Some(payload) = payload {
+        payload
+    } else {
+        warn!("no valid room enter packet received");
+        return false;
+    };
+
+    let valid = payload["roomid"] == json!(state.room.room_id)
+        && payload["key"] == json!(*lock(&state.token));
+    lock(&state.room_enters).push(payload);
+
+    let code = if valid { 0 } else { -101 };
+    let resp = Packet::new(
+        Operation::RoomEnterResponse,
+        Protocol::Json,
+        json!({ "code": code }).to_string(),
+    );
+    if ws.send(Message::binary(resp.encode())).await.is_err() || !valid {
+        let _ = ws.close(None).await;
+        return false;
+    }
+    true
+}
+
+/// Perform an action. Returns whether the connection is still alive.
+async fn perform(ws: &mut WsStream, action: Action) -> bool {
+    let data = match action {
+        Action::Notification(value) => notification(&value).encode(),
+        Action::Batch {
+            notifications,
+            compression,
+        } => {
+            let packs: Vec<_> = notifications.iter().map(notification).collect();
+            Packet::compress_batch(&packs, compression)
+                .expect("unable to compress batch")
+                .encode()
+        }
+        Action::Raw(data) => data,
+        // delays are handled by the connection loop without blocking heartbeats
+        Action::Delay(_) => return true,
+        Action::Close { code, reason } => {
+            let frame = CloseFrame {
+                code: CloseCode::from(code),
+                reason: reason.into(),
+            };
+            let _ = ws.close(Some(frame)).await;
+            // wait for the close handshake to complete
+            let _ = timeout(CLOSE_TIMEOUT, async {
+                while let Some(Ok(_)) = ws.next().await {}
+            })
+            .await;
+            return false;
+        }
+        Action::Disconnect => return false,
+    };
+    ws.send(Message::binary(data)).await.is_ok()
+}
+
+fn notification(value: &Value) -> Packet {
+    Packet::new(Operation::Notification, Protocol::Json, value.to_string())
+}
+
+fn parse(data: &[u8]) -> Option<Packet> {
+    match Packet::parse(data) {
+        IncompleteResult::Ok((_, pack)) => Some(pack),
+        _ => None,
+    }
+}
@@ -46,6 +46,7 @@ async fn run() {
     }
 }
 
+#[allow(clippy::needless_return)]
 fn main() {
     #[cfg(feature = "tokio")]
     {
@@ -4,6 +4,7 @@ use std::pin::Pin;
 use http_client::h1::H1Client as Client;
 use http_client::HttpClient;
 use serde::de::DeserializeOwned;
+use url::form_urlencoded;
 
 use crate::core::builder::Requester;
 
@@ -26,7 +27,41 @@ impl Requester for H1Client {
         let req = http_client::Request::get(url);
         Box::pin(async move {
             Ok(serde_json::from_slice(
-                &*self.0.send(req).await?.body_bytes().await?,
+                &self.0.send(req).await?.body_bytes().await?,
+            )?)
+        })
+    }
+
+    fn get_json_with_cookie<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        cookie: &str,
+    ) -> Pin<Box<dyn Future<Output = Result<T, BoxedError>> + Send + '_>> {
+        let mut req = http_client::Request::get(url);
+        req.insert_header("Cookie", cookie);
+        Box::pin(async move {
+            Ok(serde_json::from_slice(
+                &self.0.send(req).await?.body_bytes().await?,
+            )?)
+        })
+    }
+
+    fn post_form<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        form: &[(&str, &str)],
+        cookie: &str,
+    ) -> Pin<Box<dyn Future<Output = Result<T, BoxedError>> + Send + '_>> {
+        let body = form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(form)
+            .finish();
+        let mut req = http_client::Request::post(url);
+        req.insert_header("Cookie", cookie);
+        req.set_body(body);
+        req.set_content_type("application/x-www-form-urlencoded".into());
+        Box::pin(async move {
+            Ok(serde_json::from_slice(
+                &self.0.send(req).await?.body_bytes().await?,
             )?)
         })
     }
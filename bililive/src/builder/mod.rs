@@ -6,8 +6,13 @@
 //!
 //! [`by_uid`](ConfigBuilder::by_uid) fetches room id by given user id.
 //!
+//! [`by_room_id`](ConfigBuilder::by_room_id) resolves the real room id and user id by given (short) room id.
+//!
 //! [`fetch_conf`](ConfigBuilder::fetch_conf) fetches danmaku server token and list without any input parameter.
 //!
+//! [`fetch_danmu_info`](ConfigBuilder::fetch_danmu_info) fetches danmaku server token and list bound to the room and the
+//! [`credential`](ConfigBuilder::credential), which is required for an authenticated connection.
+//!
 //! # Example
 //!
 //! ```rust
@@ -15,10 +20,13 @@
 //! #
 //! # use bililive::ConfigBuilder;
 //! # use bililive::core::errors::BuildError;
+//! # use bililive_mock::MockServer;
 //! #
+//! # let mock = MockServer::builder().uid(1472906636).start();
 //! # let fut = async {
 //! # Ok::<_, BuildError>(
 //! ConfigBuilder::new()
+//! #   .api_host(&mock.api_host())
 //!     .by_uid(1472906636)
 //!     .await?
 //!     .fetch_conf()
@@ -39,6 +47,15 @@ pub(crate) mod tests;
 
 type BoxedError = Box<dyn std::error::Error + Send + Sync>;
 
+/// HTTP client used by [`ConfigBuilder`](ConfigBuilder).
+#[cfg(feature = "reqwest")]
+pub(crate) type HttpClient = reqwest::ReqwestClient;
+
+/// HTTP client used by [`ConfigBuilder`](ConfigBuilder).
+#[cfg(feature = "h1-client")]
+#[cfg(not(feature = "reqwest"))]
+pub(crate) type HttpClient = h1::H1Client;
+
 /// `bililive` stream config builder.
 ///
 /// Stream config can be built via given live room parameters (room id and user id) & danmaku server configs (server token and list).
@@ -49,7 +66,12 @@ type BoxedError = Box<dyn std::error::Error + Send + Sync>;
 ///
 /// [`by_uid`](ConfigBuilder::by_uid) fetches room id by given user id.
 ///
+/// [`by_room_id`](ConfigBuilder::by_room_id) resolves the real room id and user id by given (short) room id.
+///
 /// [`fetch_conf`](ConfigBuilder::fetch_conf) fetches danmaku server token and list without any input parameter.
+///
+/// [`fetch_danmu_info`](ConfigBuilder::fetch_danmu_info) fetches danmaku server token and list bound to the room and the
+/// [`credential`](ConfigBuilder::credential), which is required for an authenticated connection.
 #[cfg(feature = "reqwest")]
 pub type ConfigBuilder<R, U, T, S> =
     bililive_core::builder::ConfigBuilder<reqwest::ReqwestClient, R, U, T, S>;
@@ -64,8 +86,26 @@ pub type ConfigBuilder<R, U, T, S> =
 ///
 /// [`by_uid`](ConfigBuilder::by_uid) fetches room id by given user id.
 ///
+/// [`by_room_id`](ConfigBuilder::by_room_id) resolves the real room id and user id by given (short) room id.
+///
 /// [`fetch_conf`](ConfigBuilder::fetch_conf) fetches danmaku server token and list without any input parameter.
+///
+/// [`fetch_danmu_info`](ConfigBuilder::fetch_danmu_info) fetches danmaku server token and list bound to the room and the
+/// [`credential`](ConfigBuilder::credential), which is required for an authenticated connection.
 #[cfg(feature = "h1-client")]
 #[cfg(not(feature = "reqwest"))]
 pub type ConfigBuilder<R, U, T, S> =
     bililive_core::builder::ConfigBuilder<h1::H1Client, R, U, T, S>;
+
+/// `bililive` live room client, sending danmaku and likes, and querying room info.
+///
+/// See the generic type [`RoomClient`](bililive_core::room::RoomClient) for details.
+#[cfg(feature = "reqwest")]
+pub type RoomClient = bililive_core::room::RoomClient<reqwest::ReqwestClient>;
+
+/// `bililive` live room client, sending danmaku and likes, and querying room info.
+///
+/// See the generic type [`RoomClient`](bililive_core::room::RoomClient) for details.
+#[cfg(feature = "h1-client")]
+#[cfg(not(feature = "reqwest"))]
+pub type RoomClient = bililive_core::room::RoomClient<h1::H1Client>;
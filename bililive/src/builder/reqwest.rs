@@ -2,6 +2,7 @@ use std::future::Future;
 use std::pin::Pin;
 use std::str::FromStr;
 
+use reqwest::header::COOKIE;
 use reqwest::Client;
 use serde::de::DeserializeOwned;
 use url::Url;
@@ -27,8 +28,29 @@ impl Requester for ReqwestClient {
         let url = Url::from_str(url).unwrap();
         Box::pin(async move {
             Ok(serde_json::from_slice(
-                &*self.0.get(url).send().await?.bytes().await?,
+                &self.0.get(url).send().await?.bytes().await?,
             )?)
         })
     }
+
+    fn get_json_with_cookie<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        cookie: &str,
+    ) -> Pin<Box<dyn Future<Output = Result<T, BoxedError>> + Send + '_>> {
+        let url = Url::from_str(url).unwrap();
+        let req = self.0.get(url).header(COOKIE, cookie);
+        Box::pin(async move { Ok(serde_json::from_slice(&req.send().await?.bytes().await?)?) })
+    }
+
+    fn post_form<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        form: &[(&str, &str)],
+        cookie: &str,
+    ) -> Pin<Box<dyn Future<Output = Result<T, BoxedError>> + Send + '_>> {
+        let url = Url::from_str(url).unwrap();
+        let req = self.0.post(url).header(COOKIE, cookie).form(form);
+        Box::pin(async move { Ok(serde_json::from_slice(&req.send().await?.bytes().await?)?) })
+    }
 }
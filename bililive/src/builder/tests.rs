@@ -1,31 +1,159 @@
-use bililive_core::config::StreamConfig;
+use bililive_core::builder::LiveStatus;
+use bililive_core::config::{Credential, StreamConfig};
+use bililive_core::errors::{ActionError, BuildError};
+use bililive_core::room::DanmakuMode;
+use bililive_mock::MockServer;
 
-use super::ConfigBuilder;
+use super::{ConfigBuilder, RoomClient};
 
-pub(crate) async fn build_real_config(override_servers: bool) -> StreamConfig {
-    let builder = ConfigBuilder::new()
-        .by_uid(419220)
+pub(crate) async fn build_config(mock: &MockServer) -> StreamConfig {
+    ConfigBuilder::new()
+        .api_host(&mock.api_host())
+        .by_uid(mock.uid())
         .await
         .expect("unable to fetch room_id")
         .fetch_conf()
         .await
-        .expect("unable to fetch server conf");
-    let builder = if override_servers {
-        builder.servers(&["wss://broadcastlv.chat.bilibili.com/sub".to_string()])
-    } else {
-        builder
-    };
-    builder.build()
+        .expect("unable to fetch server conf")
+        .build()
+}
+
+async fn test_build_config() {
+    let mock = MockServer::start();
+
+    let config = build_config(&mock).await;
+    assert_eq!(config.room_id(), mock.room_id());
+    assert_eq!(config.token(), mock.token());
+    assert_eq!(config.servers(), [mock.ws_url()]);
+
+    let builder = ConfigBuilder::new()
+        .api_host(&mock.api_host())
+        .by_room_id(mock.short_id())
+        .await
+        .expect("unable to resolve room_id");
+    assert_eq!(builder.live_status(), Some(LiveStatus::Live));
+    let config = builder
+        .fetch_danmu_info()
+        .await
+        .expect("unable to fetch danmu info")
+        .build();
+    assert_eq!(config.room_id(), mock.room_id());
+    assert_eq!(config.uid(), mock.uid());
+    assert_eq!(config.servers(), [mock.ws_url()]);
+
+    assert!(matches!(
+        ConfigBuilder::new()
+            .api_host(&mock.api_host())
+            .by_uid(1)
+            .await,
+        Err(BuildError::NoLiveRoom)
+    ));
+    assert!(matches!(
+        ConfigBuilder::new()
+            .api_host(&mock.api_host())
+            .by_room_id(1)
+            .await,
+        Err(BuildError::Api { code: 60004, .. })
+    ));
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn must_build_config_tokio() {
+    test_build_config().await;
+}
+
+#[cfg(feature = "async-std")]
+#[async_std::test]
+async fn must_build_config_async_std() {
+    test_build_config().await;
+}
+
+async fn test_room_info() {
+    let mock = MockServer::builder()
+        .title("晚间杂谈")
+        .uname("主播")
+        .start();
+
+    let info = RoomClient::new(mock.short_id())
+        .api_host(&mock.api_host())
+        .room_info()
+        .await
+        .expect("unable to fetch room info");
+    assert_eq!(info.room_id(), mock.room_id());
+    assert_eq!(info.anchor_uid(), mock.uid());
+    assert_eq!(info.anchor_name(), "主播");
+    assert_eq!(info.title(), "晚间杂谈");
+    assert_eq!(info.live_status(), LiveStatus::Live);
+    assert!(info.live_time().is_some());
+
+    assert!(matches!(
+        RoomClient::new(1)
+            .api_host(&mock.api_host())
+            .room_info()
+            .await,
+        Err(ActionError::Api { code: 1, .. })
+    ));
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn must_fetch_room_info_tokio() {
+    test_room_info().await;
+}
+
+#[cfg(feature = "async-std")]
+#[async_std::test]
+async fn must_fetch_room_info_async_std() {
+    test_room_info().await;
+}
+
+async fn test_room_actions() {
+    let mock = MockServer::start();
+    let config = build_config(&mock).await;
+
+    let res = RoomClient::from_config(&config)
+        .api_host(&mock.api_host())
+        .send_danmaku("hello")
+        .await;
+    assert!(matches!(res, Err(ActionError::NotLoggedIn)));
+
+    let mut config = config;
+    config.set_credential(Credential::new("sess", "jct", "buvid", 12345));
+    let client = RoomClient::from_config(&config).api_host(&mock.api_host());
+    client
+        .send_danmaku(
+            bililive_core::room::OutgoingDanmaku::new("你好")
+                .mode(DanmakuMode::Bottom)
+                .color(0x00ff00),
+        )
+        .await
+        .expect("unable to send danmaku");
+    client.send_likes(5).await.expect("unable to send likes");
+
+    let posts = mock.posts();
+    assert_eq!(posts.len(), 2);
+    assert_eq!(posts[0].path, "/msg/send");
+    assert!(posts[0].cookie.contains("SESSDATA=sess"));
+    assert_eq!(posts[0].field("msg"), Some("你好"));
+    assert_eq!(posts[0].field("mode"), Some("4"));
+    assert_eq!(posts[0].field("color"), Some("65280"));
+    assert_eq!(posts[0].field("csrf"), Some("jct"));
+    assert_eq!(posts[1].field("click_time"), Some("5"));
+    assert_eq!(
+        posts[1].field("anchor_id"),
+        Some(mock.uid().to_string().as_str())
+    );
 }
 
 #[cfg(feature = "tokio")]
 #[tokio::test]
-async fn must_build_real_config_tokio() {
-    build_real_config(false).await;
+async fn must_perform_room_actions_tokio() {
+    test_room_actions().await;
 }
 
 #[cfg(feature = "async-std")]
 #[async_std::test]
-async fn must_build_real_config_async_std() {
-    build_real_config(false).await;
+async fn must_perform_room_actions_async_std() {
+    test_room_actions().await;
 }
@@ -1,6 +1,6 @@
 //! Connection related functions and types.
 macro_rules! impl_connect_mod {
-    ($adapter:ident) => {
+    ($adapter:ident, $timer:ident) => {
         use std::future::Future;
         use std::pin::Pin;
         use std::str::FromStr;
@@ -8,20 +8,21 @@ macro_rules! impl_connect_mod {
         use async_tungstenite::tungstenite::error::Error as WsError;
         use async_tungstenite::$adapter::{connect_async, ConnectStream};
         use async_tungstenite::WebSocketStream;
+        use futures::future::BoxFuture;
         use stream_reconnect::{ReconnectStream, UnderlyingStream};
         use url::Url;
 
-        use crate::core::config::StreamConfig;
+        use crate::core::config::{HeartbeatConfig, StreamConfig};
         use crate::core::errors::StreamError;
         use crate::core::packet::Packet;
-        use crate::core::retry::{RetryConfig, RetryContext, WsStream, WsStreamTrait};
-        use crate::core::stream::HeartbeatStream;
+        use crate::core::retry::{Monitored, RetryConfig, RetryContext, WsStream, WsStreamTrait};
+        use crate::core::stream::{$timer, HeartbeatStream};
         use crate::stream::CodecStream;
 
         /// Raw websocket stream type.
         pub type InnerStream = WebSocketStream<ConnectStream>;
         /// Bililive stream type.
-        pub type DefaultStream = HeartbeatStream<CodecStream<InnerStream>, WsError>;
+        pub type DefaultStream = HeartbeatStream<CodecStream<InnerStream>, WsError, $timer>;
         /// Bililive stream type with auto-reconnect mechanism.
         pub type RetryStream = ReconnectStream<
             WsStream<Connector, WsError>,
@@ -35,6 +36,7 @@ macro_rules! impl_connect_mod {
 
         impl WsStreamTrait<WsError> for Connector {
             type Stream = DefaultStream;
+            type Timer = $timer;
             fn connect(
                 url: &str,
             ) -> Pin<Box<dyn Future<Output = Result<Self::Stream, WsError>> + Send + '_>> {
@@ -45,40 +47,73 @@ macro_rules! impl_connect_mod {
                     )))
                 })
             }
+            fn set_heartbeat(stream: &mut Self::Stream, heartbeat: &HeartbeatConfig) {
+                stream.set_config(heartbeat.clone());
+            }
         }
 
         /// Connect to bilibili live room.
         ///
+        /// Heartbeat behavior is configured by [`StreamConfig::set_heartbeat`](StreamConfig::set_heartbeat).
+        ///
         /// # Errors
         /// Returns an error when websocket connection fails.
         pub async fn connect(config: StreamConfig) -> Result<DefaultStream, StreamError<WsError>> {
-            WsStream::<Connector, WsError>::establish(config.into()).await
+            WsStream::<Connector, WsError>::establish(config.into())
+                .await
+                .map(Monitored::into_inner)
         }
 
         /// Connect to bilibili live room with auto retry.
         ///
+        /// A heartbeat timeout (see [`StreamConfig::set_heartbeat`](StreamConfig::set_heartbeat))
+        /// triggers a reconnection.
+        ///
+        /// Either a [`StreamConfig`](StreamConfig) or a [`RetryContext`](RetryContext) can be
+        /// given. The latter allows sharing a [`ConnectLimiter`](crate::core::retry::ConnectLimiter)
+        /// between streams, refreshing expired tokens on reconnection by a
+        /// [`ConfigProvider`](crate::core::retry::ConfigProvider), and selecting servers by a
+        /// [`ServerSelector`](crate::core::retry::ServerSelector). Reconnections can be observed
+        /// by [`RetryContext::subscribe`](RetryContext::subscribe).
+        ///
         /// # Errors
         /// Returns an error when websocket connection fails.
         pub async fn connect_with_retry(
-            stream_config: StreamConfig,
+            stream_config: impl Into<RetryContext>,
             retry_config: RetryConfig,
         ) -> Result<RetryStream, StreamError<WsError>> {
-            let inner: RetryStream =
-                ReconnectStream::connect_with_options(stream_config.into(), retry_config.into())
-                    .await?;
+            let ctx = stream_config.into();
+            let options = retry_config.into_options(&ctx);
+            let inner: RetryStream = ReconnectStream::connect_with_options(ctx, options).await?;
             Ok(inner)
         }
+
+        impl crate::pool::RetryConnector for Connector {
+            type Stream = RetryStream;
+
+            fn connect_with_retry(
+                ctx: RetryContext,
+                retry_config: RetryConfig,
+            ) -> BoxFuture<'static, Result<Self::Stream, StreamError<WsError>>> {
+                Box::pin(connect_with_retry(ctx, retry_config))
+            }
+        }
+
+        /// A pool of auto-retry connections to multiple live rooms.
+        ///
+        /// See [`RoomPool`](crate::pool::RoomPool) for details.
+        pub type RoomPool = crate::pool::RoomPool<Connector>;
     };
 }
 
 #[cfg(feature = "tokio")]
 pub mod tokio {
     //! `tokio` integration.
-    impl_connect_mod!(tokio);
+    impl_connect_mod!(tokio, TokioTimer);
 }
 
 #[cfg(feature = "async-std")]
 pub mod async_std {
     //! `async_std` integration.
-    impl_connect_mod!(async_std);
+    impl_connect_mod!(async_std, AsyncStdTimer);
 }
@@ -1,7 +1,17 @@
 //! Error types.
 use async_tungstenite::tungstenite::Error as WsError;
+use thiserror::Error;
 
 pub use crate::core::errors::{BuildError, IncompleteResult, ParseError};
 
 /// Errors that may occur when consuming a stream.
 pub type StreamError = crate::core::errors::StreamError<WsError>;
+
+/// Errors that may occur in a [`RoomPool`](crate::pool::RoomPool).
+#[derive(Debug, Error)]
+pub enum PoolError {
+    #[error("build error: {0}")]
+    Build(#[from] BuildError),
+    #[error("stream error: {0}")]
+    Stream(#[from] StreamError),
+}
@@ -20,7 +20,10 @@
 //! - Easy establishment of connection via given live room id.
 //! - Handles heartbeat packets automatically.
 //! - Auto retry when connection fails (optional).
-//! - Decompresses `Zlib` payloads automatically.
+//! - Connection pool of multiple live rooms.
+//! - Sends danmaku and likes, and queries room info via [`RoomClient`](RoomClient).
+//! - Decompresses `Zlib` and `Brotli` payloads automatically.
+//! - Drops unwanted notifications by `cmd` cheaply via [`CmdFilter`](crate::core::stream::CmdFilter).
 //!
 //! ## Example
 //!
@@ -65,26 +68,27 @@
 //! ## Crate Features
 //!
 //! * `tokio-native-tls`(default): Enables `tokio` support with TLS implemented
-//! via [tokio-native-tls](https://crates.io/crates/tokio-native-tls).
+//!   via [tokio-native-tls](https://crates.io/crates/tokio-native-tls).
 //! * `tokio-rustls-native-certs`: Enables `tokio` support with TLS implemented
-//! via [tokio-rustls](https://crates.io/crates/tokio-rustls) and uses native system certificates found
-//! with [rustls-native-certs](https://github.com/rustls/rustls-native-certs).
+//!   via [tokio-rustls](https://crates.io/crates/tokio-rustls) and uses native system certificates found
+//!   with [rustls-native-certs](https://github.com/rustls/rustls-native-certs).
 //! * `tokio-rustls-webpki-roots`: Enables `tokio` support with TLS implemented
-//! via [tokio-rustls](https://crates.io/crates/tokio-rustls) and uses the
-//! certificates [webpki-roots](https://github.com/rustls/webpki-roots) provides.
+//!   via [tokio-rustls](https://crates.io/crates/tokio-rustls) and uses the
+//!   certificates [webpki-roots](https://github.com/rustls/webpki-roots) provides.
 //! * `async-native-tls`: Enables `async_std` support with TLS implemented
-//! via [async-native-tls](https://crates.io/crates/async-native-tls).
+//!   via [async-native-tls](https://crates.io/crates/async-native-tls).
 
 #![allow(clippy::default_trait_access, clippy::module_name_repetitions)]
 
 pub use bililive_core as core;
 
 #[doc(inline)]
-pub use crate::builder::ConfigBuilder;
+pub use crate::builder::{ConfigBuilder, RoomClient};
 pub use crate::core::packet::*;
 pub use crate::core::retry::RetryConfig;
 
 mod builder;
 pub mod connect;
 pub mod errors;
+pub mod pool;
 pub mod stream;
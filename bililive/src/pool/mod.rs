@@ -0,0 +1,289 @@
+//! Connection pool of multiple live rooms.
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::{Arc, OnceLock};
+use std::task::{Context, Poll, Waker};
+
+use futures::future::{ready, BoxFuture};
+use futures::stream::{self, AbortHandle, BoxStream, SelectAll};
+use futures::{Stream, StreamExt};
+
+use crate::builder::HttpClient;
+use crate::core::builder::ConfigBuilder;
+use crate::core::config::StreamConfig;
+use crate::core::events::Popularity;
+use crate::core::packet::Packet;
+use crate::core::retry::{ConnectLimiter, RetryConfig, RetryContext};
+use crate::errors::{BuildError, PoolError, StreamError};
+
+#[cfg(test)]
+mod tests;
+
+/// Trait of helper objects to establish auto-retry connections for a [`RoomPool`](RoomPool).
+#[doc(hidden)]
+pub trait RetryConnector {
+    /// The returned stream type.
+    type Stream: Stream<Item = Result<Packet, StreamError>> + Send + Unpin + 'static;
+    /// Connect to bilibili live room with auto retry.
+    fn connect_with_retry(
+        ctx: RetryContext,
+        retry_config: RetryConfig,
+    ) -> BoxFuture<'static, Result<Self::Stream, StreamError>>;
+}
+
+/// Item of a room stream. `None` marks the end of the stream.
+type RoomItem = (u64, u64, Option<Result<Packet, PoolError>>);
+
+/// A room in the pool.
+struct Room {
+    /// generation of the room, to tell it from a room re-added with the same id
+    generation: u64,
+    abort: AbortHandle,
+    /// retry context of the room, set once its config is resolved
+    ctx: Arc<OnceLock<RetryContext>>,
+}
+
+/// A pool of auto-retry connections to multiple live rooms.
+///
+/// Rooms can be added or removed at any time. Packets of all rooms are yielded together, tagged
+/// with the room id they are added by.
+///
+/// Connections are established in the background while the pool is polled. Concurrent attempts,
+/// including config lookups and reconnections, are capped by a shared
+/// [`ConnectLimiter`](ConnectLimiter), so that a mass reconnect doesn't hammer the servers.
+/// All config lookups share one HTTP client.
+///
+/// If a room fails to connect or exhausts its retries, the error is yielded and the room is
+/// removed from the pool. The pool itself never ends, even if it's empty.
+///
+/// # Example
+/// ```rust,no_run
+/// # #[cfg(feature = "tokio")]
+/// use bililive::connect::tokio::RoomPool;
+/// use futures::StreamExt;
+///
+/// # #[cfg(feature = "tokio")]
+/// # async fn test() {
+/// let mut pool = RoomPool::new(4);
+/// pool.add_room(1016);
+/// pool.add_room(5440);
+///
+/// while let Some((room_id, packet)) = pool.next().await {
+///     println!("{}: {:?}", room_id, packet);
+/// }
+/// # }
+/// ```
+pub struct RoomPool<C> {
+    client: Arc<HttpClient>,
+    limiter: ConnectLimiter,
+    retry_config: RetryConfig,
+    api_host: Option<String>,
+    streams: SelectAll<BoxStream<'static, RoomItem>>,
+    rooms: HashMap<u64, Room>,
+    generation: u64,
+    waker: Option<Waker>,
+    __marker: PhantomData<C>,
+}
+
+impl<C> Unpin for RoomPool<C> {}
+
+impl<C: RetryConnector> RoomPool<C> {
+    /// Construct an empty pool allowing at most `max_connecting` concurrent connection attempts.
+    ///
+    /// # Panics
+    /// Panics if `max_connecting` is zero.
+    #[must_use]
+    pub fn new(max_connecting: usize) -> Self {
+        Self {
+            client: Default::default(),
+            limiter: ConnectLimiter::new(max_connecting),
+            retry_config: RetryConfig::default(),
+            api_host: None,
+            streams: SelectAll::new(),
+            rooms: HashMap::new(),
+            generation: 0,
+            waker: None,
+            __marker: PhantomData,
+        }
+    }
+
+    /// Set the retry config used by rooms added afterwards.
+    #[must_use]
+    pub fn retry_config(mut self, retry_config: RetryConfig) -> Self {
+        self.retry_config = retry_config;
+        self
+    }
+
+    /// Set the host of bilibili apis used for config lookups.
+    ///
+    /// See [`ConfigBuilder::api_host`](ConfigBuilder::api_host) for details.
+    #[must_use]
+    pub fn api_host(mut self, host: &str) -> Self {
+        self.api_host = Some(host.to_string());
+        self
+    }
+
+    /// Add a live room by (short) room id. Its config is looked up when connecting, and looked up
+    /// again on each reconnection so that expired tokens are refreshed.
+    ///
+    /// Returns `false` if the room is already in the pool.
+    pub fn add_room(&mut self, room_id: u64) -> bool {
+        if self.rooms.contains_key(&room_id) {
+            return false;
+        }
+
+        let client = self.client.clone();
+        let api_host = self.api_host.clone();
+        // the permit is held by the connection attempt on reconnection, so it's not acquired here
+        let lookup = move || {
+            let builder = ConfigBuilder::new_with_client(client.clone());
+            let builder = match &api_host {
+                Some(host) => builder.api_host(host),
+                None => builder,
+            };
+            async move {
+                Ok::<_, BuildError>(
+                    builder
+                        .by_room_id(room_id)
+                        .await?
+                        .fetch_conf()
+                        .await?
+                        .build(),
+                )
+            }
+        };
+        let limiter = self.limiter.clone();
+        let initial = lookup();
+        let config = async move {
+            let _permit = limiter.acquire().await;
+            initial.await
+        };
+        self.insert(room_id, config, move |ctx| ctx.with_config_provider(lookup));
+        true
+    }
+
+    /// Add a live room with given config. The room is tagged with the room id in the config.
+    ///
+    /// Returns `false` if the room is already in the pool.
+    pub fn add_config(&mut self, config: StreamConfig) -> bool {
+        let room_id = config.room_id();
+        if self.rooms.contains_key(&room_id) {
+            return false;
+        }
+
+        self.insert(room_id, ready(Ok(config)), |ctx| ctx);
+        true
+    }
+
+    /// Remove a live room, closing its connection.
+    ///
+    /// Returns `false` if the room isn't in the pool.
+    pub fn remove_room(&mut self, room_id: u64) -> bool {
+        self.rooms
+            .remove(&room_id)
+            .map(|room| room.abort.abort())
+            .is_some()
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn insert(
+        &mut self,
+        room_id: u64,
+        config: impl std::future::Future<Output = Result<StreamConfig, BuildError>> + Send + 'static,
+        with_provider: impl FnOnce(RetryContext) -> RetryContext + Send + 'static,
+    ) {
+        let limiter = self.limiter.clone();
+        let retry_config = self.retry_config.clone();
+        let ctx_slot = Arc::new(OnceLock::new());
+        let slot = ctx_slot.clone();
+        let connect = async move {
+            let ctx = with_provider(RetryContext::from(config.await?).with_limiter(limiter));
+            let _ = slot.set(ctx.clone());
+            Ok::<_, PoolError>(C::connect_with_retry(ctx, retry_config).await?)
+        };
+        let room = stream::once(connect).flat_map(|res| match res {
+            Ok(stream) => stream
+                .map(|item| item.map_err(PoolError::from))
+                .left_stream(),
+            Err(e) => stream::once(ready(Err(e))).right_stream(),
+        });
+        let (room, handle) = stream::abortable(room);
+
+        self.generation += 1;
+        let generation = self.generation;
+        let room = room
+            .map(move |item| (room_id, generation, Some(item)))
+            .chain(stream::once(ready((room_id, generation, None))));
+        self.streams.push(room.boxed());
+        self.rooms.insert(
+            room_id,
+            Room {
+                generation,
+                abort: handle,
+                ctx: ctx_slot,
+            },
+        );
+
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl<C> RoomPool<C> {
+    /// Get the ids of rooms in the pool.
+    pub fn rooms(&self) -> impl Iterator<Item = u64> + '_ {
+        self.rooms.keys().copied()
+    }
+    /// Check whether a room is in the pool.
+    #[must_use]
+    pub fn contains(&self, room_id: u64) -> bool {
+        self.rooms.contains_key(&room_id)
+    }
+    /// Get the latest popularity of a room, if the room is in the pool and any is received.
+    #[must_use]
+    pub fn popularity(&self, room_id: u64) -> Option<Popularity> {
+        self.rooms
+            .get(&room_id)
+            .and_then(|room| room.ctx.get())
+            .and_then(RetryContext::popularity)
+    }
+    /// Get the number of rooms in the pool.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.rooms.len()
+    }
+    /// Check whether the pool is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.rooms.is_empty()
+    }
+}
+
+impl<C> Stream for RoomPool<C> {
+    type Item = (u64, Result<Packet, PoolError>);
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            match self.streams.poll_next_unpin(cx) {
+                Poll::Ready(Some((room_id, _, Some(item)))) => {
+                    return Poll::Ready(Some((room_id, item)))
+                }
+                Poll::Ready(Some((room_id, generation, None))) => {
+                    // the room may have been removed, or replaced by a new one
+                    if matches!(self.rooms.get(&room_id), Some(room) if room.generation == generation)
+                    {
+                        self.rooms.remove(&room_id);
+                    }
+                }
+                Poll::Ready(None) => {
+                    // wait for new rooms
+                    self.waker = Some(cx.waker().clone());
+                    return Poll::Pending;
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use std::time::Duration;
+
+use bililive_mock::{danmu_msg, Action, MockServer};
+use futures::{FutureExt, Stream, StreamExt};
+use serde_json::Value;
+
+use crate::core::errors::BuildError;
+use crate::core::events::Popularity;
+use crate::core::packet::{Operation, Packet};
+use crate::core::retry::RetryConfig;
+use crate::errors::PoolError;
+
+use super::{RetryConnector, RoomPool};
+
+fn danmaku_mock(room_id: u64, text: &str) -> MockServer {
+    MockServer::builder()
+        .room_id(room_id)
+        .action(Action::Notification(danmu_msg(text)))
+        .start()
+}
+
+/// Receive notifications until `count` danmaku are collected, returning their rooms and texts.
+async fn recv_danmaku(
+    pool: &mut (impl Stream<Item = (u64, Result<Packet, PoolError>)> + Unpin),
+    count: usize,
+) -> Vec<(u64, String)> {
+    let mut danmaku = vec![];
+    while danmaku.len() < count {
+        let (room_id, pack) = pool.next().await.expect("pool closed");
+        let pack = pack.expect("pool error");
+        if pack.op() == Operation::Notification {
+            let text = pack.json::<Value>().unwrap()["info"][1]
+                .as_str()
+                .unwrap()
+                .to_string();
+            danmaku.push((room_id, text));
+        }
+    }
+    danmaku.sort();
+    danmaku
+}
+
+async fn test_pool<C: RetryConnector>() {
+    let first = danmaku_mock(5440, "first");
+    let second = danmaku_mock(5441, "second");
+
+    let mut pool = RoomPool::<C>::new(1)
+        .api_host(&first.api_host())
+        .retry_config(RetryConfig::new(|| vec![Duration::from_millis(10); 3]));
+    assert!(pool.add_room(first.short_id()));
+    assert!(!pool.add_room(first.short_id()));
+    assert!(pool.add_config(second.config()));
+    assert_eq!(pool.len(), 2);
+
+    assert_eq!(
+        recv_danmaku(&mut pool, 2).await,
+        [
+            (first.short_id(), String::from("first")),
+            (5441, String::from("second"))
+        ]
+    );
+    assert_eq!(first.room_enters().len(), 1);
+    assert_eq!(second.room_enters().len(), 1);
+
+    // heartbeat responses may not have been received yet
+    while pool.popularity(5441).is_none() {
+        pool.next()
+            .await
+            .expect("pool closed")
+            .1
+            .expect("pool error");
+    }
+    assert_eq!(pool.popularity(5441), Some(Popularity(1)));
+    assert_eq!(pool.popularity(1), None);
+
+    assert!(pool.remove_room(first.short_id()));
+    assert!(!pool.remove_room(first.short_id()));
+    assert_eq!(pool.rooms().collect::<Vec<_>>(), [5441]);
+}
+
+async fn test_pool_error<C: RetryConnector>() {
+    let mock = MockServer::start();
+
+    let mut pool = RoomPool::<C>::new(1).api_host(&mock.api_host());
+    assert!(pool.add_room(1));
+
+    let (room_id, res) = pool.next().await.unwrap();
+    assert_eq!(room_id, 1);
+    assert!(matches!(
+        res,
+        Err(PoolError::Build(BuildError::Api { code: 60004, .. }))
+    ));
+    // the failed room is removed, and the empty pool doesn't end
+    assert!(pool.next().now_or_never().is_none());
+    assert!(pool.is_empty());
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test(flavor = "multi_thread", worker_threads = 6)]
+async fn must_pool_rooms_tokio() {
+    test_pool::<crate::connect::tokio::Connector>().await;
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test(flavor = "multi_thread", worker_threads = 6)]
+async fn must_yield_pool_error_tokio() {
+    test_pool_error::<crate::connect::tokio::Connector>().await;
+}
+
+#[cfg(feature = "async-std")]
+#[async_std::test]
+async fn must_pool_rooms_async_std() {
+    test_pool::<crate::connect::async_std::Connector>().await;
+}
+
+#[cfg(feature = "async-std")]
+#[async_std::test]
+async fn must_yield_pool_error_async_std() {
+    test_pool_error::<crate::connect::async_std::Connector>().await;
+}
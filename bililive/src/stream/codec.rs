@@ -7,16 +7,23 @@ use futures::ready;
 use futures::{Sink, Stream};
 use log::{debug, warn};
 
-use crate::core::errors::IncompleteResult;
 use crate::core::errors::StreamError;
-use crate::core::packet::Packet;
+use crate::core::packet::{Packet, PacketBuffer};
 
 /// A stream/sink interface to underlying websocket frame stream. Encodes/decodes bilibili live packets.
+///
+/// A close frame sent by the server is yielded as [`StreamError::Closed`](StreamError::Closed), and
+/// other websocket errors as [`StreamError::WebSocket`](StreamError::WebSocket). The stream
+/// terminates after either of them.
 pub struct CodecStream<T> {
     /// underlying tungstenite stream
     stream: T,
     /// rx buffer
-    read_buffer: Vec<u8>,
+    read_buffer: PacketBuffer,
+    /// whether a close frame has been received
+    closing: bool,
+    /// whether the connection is closed or broken
+    terminated: bool,
 }
 
 impl<T> CodecStream<T> {
@@ -26,7 +33,9 @@ impl<T> CodecStream<T> {
     pub const fn new(stream: T) -> Self {
         Self {
             stream,
-            read_buffer: vec![],
+            read_buffer: PacketBuffer::new(),
+            closing: false,
+            terminated: false,
         }
     }
 }
@@ -39,40 +48,47 @@ where
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         loop {
+            // yield packets parsed from previous messages first
+            if let Some(pack) = self.read_buffer.pop() {
+                return Poll::Ready(Some(Ok(pack)));
+            }
+
+            if self.terminated {
+                return Poll::Ready(None);
+            }
+
             // poll the underlying websocket stream
             if let Some(msg) = ready!(Pin::new(&mut self.stream).poll_next(cx)) {
                 match msg {
                     Ok(msg) => {
                         if msg.is_binary() {
-                            // append data to the end of the buffer
-                            self.read_buffer.extend(msg.into_data());
-                            // parse the message
-                            match Packet::parse(&self.read_buffer) {
-                                IncompleteResult::Ok((remaining, pack)) => {
-                                    debug!("packet parsed, {} bytes remaining", remaining.len());
-
-                                    // remove parsed bytes
-                                    let consume_len = self.read_buffer.len() - remaining.len();
-                                    drop(self.read_buffer.drain(..consume_len));
-
-                                    return Poll::Ready(Some(Ok(pack)));
-                                }
-                                IncompleteResult::Incomplete(needed) => {
-                                    debug!("incomplete packet, {:?} needed", needed);
-                                }
-                                IncompleteResult::Err(e) => {
-                                    warn!("error occurred when parsing incoming packet");
-                                    return Poll::Ready(Some(Err(e.into())));
-                                }
+                            if let Err(e) = self.read_buffer.feed(msg.into_data().into()) {
+                                warn!("error occurred when parsing incoming packet");
+                                return Poll::Ready(Some(Err(e.into())));
                             }
+                        } else if let Message::Close(frame) = msg {
+                            // server closing connection
+                            let (code, reason) = frame.map_or((1005, String::new()), |frame| {
+                                (frame.code.into(), frame.reason.into_owned())
+                            });
+                            debug!("connection closed by server: {} {}", code, reason);
+                            // keep polling so that the close handshake can be completed
+                            self.closing = true;
+                            return Poll::Ready(Some(Err(StreamError::Closed { code, reason })));
                         } else {
                             debug!("not a binary message, dropping");
                         }
                     }
+                    Err(WsError::ConnectionClosed | WsError::AlreadyClosed) if self.closing => {
+                        // close handshake completed
+                        self.terminated = true;
+                        return Poll::Ready(None);
+                    }
                     Err(e) => {
                         // underlying websocket error, closing connection
                         warn!("error occurred when receiving message: {:?}", e);
-                        return Poll::Ready(None);
+                        self.terminated = true;
+                        return Poll::Ready(Some(Err(StreamError::from_ws_error(e))));
                     }
                 }
             } else {
@@ -1,12 +1,25 @@
 use std::time::Duration;
 
+use async_std::net::{TcpListener, TcpStream};
+use async_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use async_tungstenite::tungstenite::protocol::CloseFrame;
 use async_tungstenite::tungstenite::Error as WsError;
-use futures::{Future, Sink, SinkExt, Stream, StreamExt};
+use async_tungstenite::tungstenite::Message;
+use async_tungstenite::WebSocketStream;
+use bililive_mock::{danmu_msg, Action, MockServer};
+use futures::channel::mpsc::Receiver;
+use futures::executor::block_on;
+use futures::{stream, Future, Sink, SinkExt, Stream, StreamExt};
+use serde_json::Value;
 
-use crate::builder::tests::build_real_config;
+use crate::builder::tests::build_config;
+use crate::core::config::{Compression, HeartbeatConfig, StreamConfig};
 use crate::core::errors::StreamError;
+use crate::core::events::Event;
 use crate::core::packet::{Operation, Packet, Protocol};
-use crate::core::retry::RetryConfig;
+use crate::core::retry::{RetryConfig, RetryContext, RetryEvent};
+use crate::stream::CodecStream;
+use crate::ConfigBuilder;
 
 async fn must_future_timeout(dur: Duration, fut: impl Future) {
     if cfg!(feature = "tokio") {
@@ -24,42 +37,256 @@ async fn must_future_timeout(dur: Duration, fut: impl Future) {
     };
 }
 
+#[test]
+fn must_decode_batches() {
+    let messages = vec![
+        Ok(Message::binary(
+            &include_bytes!("../../../bililive-core/tests/raw/zlib_batch.packet")[..],
+        )),
+        Ok(Message::text("dropped")),
+        Ok(Message::binary(
+            &include_bytes!("../../../bililive-core/tests/raw/brotli_batch.packet")[..],
+        )),
+    ];
+    let stream = CodecStream::new(stream::iter(messages));
+
+    let cmds: Vec<_> = block_on(stream.collect::<Vec<_>>())
+        .into_iter()
+        .map(|pack| {
+            pack.expect("stream error").json::<Value>().unwrap()["cmd"]
+                .as_str()
+                .unwrap()
+                .to_string()
+        })
+        .collect();
+    assert_eq!(
+        cmds,
+        [
+            "DANMU_MSG",
+            "DANMU_MSG",
+            "ONLINE_RANK_COUNT",
+            "DANMU_MSG",
+            "DANMU_MSG",
+            "ONLINE_RANK_COUNT"
+        ]
+    );
+}
+
+/// Spawn a local websocket server which runs `serve` on the first connection, and connect to it.
+async fn local_stream<F, Fut>(serve: F) -> CodecStream<WebSocketStream<TcpStream>>
+where
+    F: FnOnce(WebSocketStream<TcpStream>) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    async_std::task::spawn(async move {
+        let (tcp, _) = listener.accept().await.unwrap();
+        serve(async_tungstenite::accept_async(tcp).await.unwrap()).await;
+    });
+
+    let tcp = TcpStream::connect(addr).await.unwrap();
+    let (ws, _) = async_tungstenite::client_async(format!("ws://{}", addr), tcp)
+        .await
+        .unwrap();
+    CodecStream::new(ws)
+}
+
+#[async_std::test]
+async fn must_yield_close_frame() {
+    let mut stream = local_stream(|mut ws| async move {
+        ws.send(Message::binary(
+            Packet::new(
+                Operation::HeartBeatResponse,
+                Protocol::Int32BE,
+                1i32.to_be_bytes(),
+            )
+            .encode(),
+        ))
+        .await
+        .unwrap();
+        ws.close(Some(CloseFrame {
+            code: CloseCode::Again,
+            reason: "try again later".into(),
+        }))
+        .await
+        .unwrap();
+        while ws.next().await.is_some() {}
+    })
+    .await;
+
+    let pack = stream.next().await.unwrap().expect("stream error");
+    assert_eq!(pack.op(), Operation::HeartBeatResponse);
+    match stream.next().await {
+        Some(Err(StreamError::Closed { code, reason })) => {
+            assert_eq!(code, 1013);
+            assert_eq!(reason, "try again later");
+        }
+        e => panic!("unexpected item: {:?}", e),
+    }
+    assert!(stream.next().await.is_none());
+}
+
+#[async_std::test]
+async fn must_yield_ws_error() {
+    // drop the connection without close handshake
+    let mut stream = local_stream(|ws| async move { drop(ws) }).await;
+
+    assert!(matches!(
+        stream.next().await,
+        Some(Err(StreamError::WebSocket(WsError::Protocol(_))))
+    ));
+    assert!(stream.next().await.is_none());
+}
+
+/// A mock server replaying a plain notification and zlib/brotli compressed batches, then pausing
+/// the script while still answering heartbeats.
+fn notification_mock() -> MockServer {
+    MockServer::builder()
+        .popularity(42)
+        .action(Action::Notification(danmu_msg("plain")))
+        .action(Action::Batch {
+            notifications: vec![danmu_msg("zlib 1"), danmu_msg("zlib 2")],
+            compression: Compression::Zlib,
+        })
+        .action(Action::Batch {
+            notifications: vec![danmu_msg("brotli")],
+            compression: Compression::Brotli,
+        })
+        .action(Action::Delay(Duration::from_secs(60)))
+        .start()
+}
+
+/// Receive notifications until `count` danmaku are collected, returning their decoded texts.
+async fn recv_danmaku(
+    stream: &mut (impl Stream<Item = Result<Packet, StreamError<WsError>>> + Unpin),
+    count: usize,
+) -> Vec<String> {
+    let mut texts = vec![];
+    while texts.len() < count {
+        let pack = stream
+            .next()
+            .await
+            .expect("stream closed")
+            .expect("stream error");
+        if pack.op() == Operation::Notification {
+            match pack.event() {
+                Ok(Event::Danmaku(danmaku)) => texts.push(danmaku.text),
+                other => panic!("expect danmaku, got {:?}", other),
+            }
+        }
+    }
+    texts
+}
+
 async fn test_stream(
     mut stream: impl Stream<Item = Result<Packet, StreamError<WsError>>>
         + Sink<Packet, Error = StreamError<WsError>>
         + Unpin,
 ) {
-    let mut msg_count = 0;
-
-    let stream_try = async {
-        while let Some(msg) = stream.next().await {
-            msg.expect("stream error");
-            msg_count += 1;
-        }
-    };
-    // err means timeout indicating there's no early stop on stream
-    must_future_timeout(Duration::from_secs(3), stream_try).await;
+    assert_eq!(
+        recv_danmaku(&mut stream, 4).await,
+        ["plain", "zlib 1", "zlib 2", "brotli"]
+    );
 
     stream
         .send(Packet::new(Operation::HeartBeat, Protocol::Json, vec![]))
         .await
         .expect("sink error");
-    let mut hb_resp_received = false;
-    let stream_try = async {
-        while let Some(msg) = stream.next().await {
-            let msg = msg.expect("stream error");
-            if msg.op() == Operation::HeartBeatResponse {
-                hb_resp_received = true;
-            }
+    loop {
+        let pack = stream
+            .next()
+            .await
+            .expect("stream closed")
+            .expect("stream error");
+        if pack.op() == Operation::HeartBeatResponse {
+            assert_eq!(pack.int32_be().unwrap(), 42);
+            break;
         }
-    };
-    // err means timeout indicating there's no early stop on stream
-    must_future_timeout(Duration::from_secs(1), stream_try).await;
-    assert!(hb_resp_received, "no heart beat response received");
+    }
 
     stream.close().await.expect("unable to close stream");
 }
 
+/// A mock server which disconnects after the first danmaku.
+fn disconnect_mock() -> MockServer {
+    MockServer::builder()
+        .action(Action::Notification(danmu_msg("before")))
+        .action(Action::Disconnect)
+        .action(Action::Notification(danmu_msg("after")))
+        .start()
+}
+
+fn fast_retry() -> RetryConfig {
+    RetryConfig::new(|| vec![Duration::from_millis(10); 3])
+}
+
+async fn test_retry(
+    mock: &MockServer,
+    mut stream: impl Stream<Item = Result<Packet, StreamError<WsError>>> + Unpin,
+    mut events: Receiver<RetryEvent>,
+) {
+    assert_eq!(recv_danmaku(&mut stream, 2).await, ["before", "after"]);
+    assert_eq!(mock.room_enters().len(), 2);
+
+    let events: Vec<_> = std::iter::from_fn(|| events.try_next().ok().flatten()).collect();
+    assert!(
+        matches!(
+            events.as_slice(),
+            [
+                RetryEvent::Connecting { attempt: 1, .. },
+                RetryEvent::Connected { .. },
+                RetryEvent::Disconnected { .. },
+                RetryEvent::RetryScheduled { .. },
+                RetryEvent::Connecting { attempt: 2, .. },
+                RetryEvent::Connected { .. },
+            ]
+        ),
+        "{:?}",
+        events
+    );
+}
+
+/// A retry context refetching the config from the mock server on reconnection.
+fn refreshing_context(mock: &MockServer) -> RetryContext {
+    let (api_host, uid) = (mock.api_host(), mock.uid());
+    RetryContext::from(mock.config()).with_config_provider(move || {
+        let builder = ConfigBuilder::new().api_host(&api_host);
+        async move { Ok(builder.by_uid(uid).await?.fetch_conf().await?.build()) }
+    })
+}
+
+async fn test_token_rotation(
+    mock: &MockServer,
+    mut stream: impl Stream<Item = Result<Packet, StreamError<WsError>>> + Unpin,
+) {
+    mock.push(Action::Notification(danmu_msg("before")));
+    assert_eq!(recv_danmaku(&mut stream, 1).await, ["before"]);
+
+    // the old token is rejected on reconnection, so the config must be refreshed
+    mock.rotate_token("rotated-token");
+    mock.push(Action::Disconnect);
+    mock.push(Action::Notification(danmu_msg("after")));
+    assert_eq!(recv_danmaku(&mut stream, 1).await, ["after"]);
+
+    let room_enters = mock.room_enters();
+    assert_eq!(room_enters.len(), 2);
+    assert_eq!(room_enters[1]["key"], "rotated-token");
+}
+
+/// A mock server which drops connections without heartbeat in one second.
+fn heartbeat_mock() -> (MockServer, StreamConfig) {
+    let mock = MockServer::builder()
+        .heartbeat_timeout(Duration::from_secs(1))
+        .start();
+    let mut config = mock.config();
+    config.set_heartbeat(HeartbeatConfig::new(
+        Duration::from_millis(200),
+        Duration::from_secs(1),
+    ));
+    (mock, config)
+}
+
 async fn test_stream_heartbeat(
     mut stream: impl Stream<Item = Result<Packet, StreamError<WsError>>>
         + Sink<Packet, Error = StreamError<WsError>>
@@ -70,15 +297,26 @@ async fn test_stream_heartbeat(
         panic!("connection closed (heartbeat not sent)");
     };
     // err means timeout indicating there's no early stop on stream
-    must_future_timeout(Duration::from_secs(120), stream_try).await;
+    must_future_timeout(Duration::from_secs(3), stream_try).await;
 
     stream.close().await.expect("unable to close stream");
 }
 
+/// A stream config with a token the mock server rejects.
+fn bad_token_config(mock: &MockServer) -> StreamConfig {
+    StreamConfig::new(
+        mock.room_id(),
+        mock.uid(),
+        String::from("bad-token"),
+        vec![mock.ws_url()],
+    )
+}
+
 #[cfg(feature = "tokio")]
 #[tokio::test(flavor = "multi_thread", worker_threads = 6)]
 async fn must_stream_tokio() {
-    let config = build_real_config(true).await;
+    let mock = notification_mock();
+    let config = build_config(&mock).await;
 
     let stream = crate::connect::tokio::connect(config)
         .await
@@ -89,7 +327,8 @@ async fn must_stream_tokio() {
 #[cfg(feature = "tokio")]
 #[tokio::test(flavor = "multi_thread", worker_threads = 6)]
 async fn must_retry_stream_tokio() {
-    let config = build_real_config(false).await;
+    let mock = notification_mock();
+    let config = build_config(&mock).await;
 
     let stream = crate::connect::tokio::connect_with_retry(config, RetryConfig::default())
         .await
@@ -97,10 +336,35 @@ async fn must_retry_stream_tokio() {
     test_stream(stream).await;
 }
 
+#[cfg(feature = "tokio")]
+#[tokio::test(flavor = "multi_thread", worker_threads = 6)]
+async fn must_retry_after_disconnect_tokio() {
+    let mock = disconnect_mock();
+    let ctx = RetryContext::from(mock.config());
+    let events = ctx.subscribe();
+
+    let stream = crate::connect::tokio::connect_with_retry(ctx, fast_retry())
+        .await
+        .expect("unable to establish connection");
+    test_retry(&mock, stream, events).await;
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test(flavor = "multi_thread", worker_threads = 6)]
+async fn must_refresh_config_tokio() {
+    let mock = MockServer::start();
+
+    let stream = crate::connect::tokio::connect_with_retry(refreshing_context(&mock), fast_retry())
+        .await
+        .expect("unable to establish connection");
+    test_token_rotation(&mock, stream).await;
+}
+
 #[cfg(feature = "async-std")]
 #[async_std::test]
 async fn must_stream_async_std() {
-    let config = build_real_config(true).await;
+    let mock = notification_mock();
+    let config = build_config(&mock).await;
 
     let stream = crate::connect::async_std::connect(config)
         .await
@@ -111,7 +375,8 @@ async fn must_stream_async_std() {
 #[cfg(feature = "async-std")]
 #[async_std::test]
 async fn must_retry_async_std() {
-    let config = build_real_config(false).await;
+    let mock = notification_mock();
+    let config = build_config(&mock).await;
 
     let stream = crate::connect::async_std::connect_with_retry(config, RetryConfig::default())
         .await
@@ -119,14 +384,35 @@ async fn must_retry_async_std() {
     test_stream(stream).await;
 }
 
+#[cfg(feature = "async-std")]
+#[async_std::test]
+async fn must_retry_after_disconnect_async_std() {
+    let mock = disconnect_mock();
+    let ctx = RetryContext::from(mock.config());
+    let events = ctx.subscribe();
+
+    let stream = crate::connect::async_std::connect_with_retry(ctx, fast_retry())
+        .await
+        .expect("unable to establish connection");
+    test_retry(&mock, stream, events).await;
+}
+
+#[cfg(feature = "async-std")]
+#[async_std::test]
+async fn must_refresh_config_async_std() {
+    let mock = MockServer::start();
+
+    let stream =
+        crate::connect::async_std::connect_with_retry(refreshing_context(&mock), fast_retry())
+            .await
+            .expect("unable to establish connection");
+    test_token_rotation(&mock, stream).await;
+}
+
 #[cfg(feature = "tokio")]
 #[tokio::test(flavor = "multi_thread", worker_threads = 6)]
 async fn must_hb_tokio() {
-    if option_env!("FAST_TEST").is_some() {
-        return;
-    }
-
-    let config = build_real_config(true).await;
+    let (_mock, config) = heartbeat_mock();
 
     let stream = crate::connect::tokio::connect(config)
         .await
@@ -137,14 +423,28 @@ async fn must_hb_tokio() {
 #[cfg(feature = "async-std")]
 #[async_std::test]
 async fn must_hb_async_std() {
-    if option_env!("FAST_TEST").is_some() {
-        return;
-    }
-
-    let config = build_real_config(true).await;
+    let (_mock, config) = heartbeat_mock();
 
     let stream = crate::connect::async_std::connect(config)
         .await
         .expect("unable to establish connection");
     test_stream_heartbeat(stream).await;
 }
+
+#[cfg(feature = "tokio")]
+#[tokio::test(flavor = "multi_thread", worker_threads = 6)]
+async fn must_reject_bad_token_tokio() {
+    let mock = MockServer::start();
+
+    let res = crate::connect::tokio::connect(bad_token_config(&mock)).await;
+    assert!(matches!(res, Err(StreamError::Auth { code: -101 })));
+}
+
+#[cfg(feature = "async-std")]
+#[async_std::test]
+async fn must_reject_bad_token_async_std() {
+    let mock = MockServer::start();
+
+    let res = crate::connect::async_std::connect(bad_token_config(&mock)).await;
+    assert!(matches!(res, Err(StreamError::Auth { code: -101 })));
+}